@@ -0,0 +1,61 @@
+use std::io;
+
+use axum::http::StatusCode;
+use axum::Json;
+use axum::response::{IntoResponse, Response};
+use serde::Serialize;
+
+use crate::error::PreconditionFailed;
+
+// ApiError 是接口统一的错误类型, 返回对应的 HTTP 状态码和 JSON 错误信息:
+// {"error": {"status": 404, "message": "No such file or directory (os error 2)"}}
+#[derive(Debug)]
+pub struct ApiError {
+    pub status: StatusCode,
+    pub message: String,
+}
+
+#[derive(Serialize)]
+struct ErrorEnvelope<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    status: u16,
+    message: &'a str,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, message: impl Into<String>) -> Self {
+        Self { status, message: message.into() }
+    }
+}
+
+impl From<io::Error> for ApiError {
+    fn from(e: io::Error) -> Self {
+        let status = match e.kind() {
+            _ if e.get_ref().is_some_and(|inner| inner.is::<PreconditionFailed>()) => StatusCode::PRECONDITION_FAILED,
+            io::ErrorKind::NotFound => StatusCode::NOT_FOUND,
+            io::ErrorKind::PermissionDenied => StatusCode::FORBIDDEN,
+            io::ErrorKind::AlreadyExists => StatusCode::CONFLICT,
+            io::ErrorKind::InvalidInput => StatusCode::BAD_REQUEST,
+            io::ErrorKind::InvalidData => StatusCode::UNPROCESSABLE_ENTITY,
+            _ if e.raw_os_error() == Some(libc::ESRCH) => StatusCode::NOT_FOUND,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        Self::new(status, e.to_string())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let body = ErrorEnvelope {
+            error: ErrorBody {
+                status: self.status.as_u16(),
+                message: &self.message,
+            },
+        };
+        (self.status, Json(body)).into_response()
+    }
+}
@@ -1,23 +1,50 @@
-use std::fs::File;
-use std::io;
-use std::io::BufReader;
+use std::convert::Infallible;
+use std::io::{self, SeekFrom, Write};
+use std::os::unix::fs::MetadataExt;
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
 use axum::{Json, Router, routing::{get, post}};
+use axum::body::{Body, Bytes};
+use axum::extract::{Query, State};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::routing::put;
-use serde::Deserialize;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use chrono::{TimeZone, Utc};
+use glob::Pattern;
+use http_body::Frame;
+use http_body_util::StreamBody;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncSeekExt, AsyncWriteExt};
+use tokio::sync::{mpsc, Semaphore};
+use tokio_stream::Stream;
+use tokio_stream::wrappers::ReceiverStream;
+use tokio_util::io::ReaderStream;
 
-use crate::node_exporter::file_utils::fileinfo::{FileInfo, get_file_contents, get_file_contents_by_line};
-use crate::hand::node::file_operation::{copy_file, create_file, delete_file, mkdir, move_file};
+use crate::api::api_error::ApiError;
+use crate::api::path_policy::{Access, PathPolicy};
+use crate::config::agent_config::{AgentConfig, FileConfig};
+use crate::node_exporter::file_utils::filefollow::{FileFollower, FollowEvent};
+use crate::node_exporter::file_utils::fileinfo::{DirEntryInfo, FileInfo, FileStat, get_file_contents, get_file_contents_by_line, LinePage, list_dir, ListOptions, ListSortBy, read_range};
+use crate::hand::node::file_operation::{ArchiveFormat, ArchiveProgress, copy_file, create_file, create_tar, create_zip, delete_file, extract_tar, extract_zip, file_etag, IfExists, list_zip, mkdir, move_file, download, open_upload_temp, TransferResult, upload_file, upload_received, upload_temp_path, UploadResult, write_file, WriteOptions, WriteResult, ZipEntryInfo};
+use crate::hand::node::file_permission::{Acl, chmod, chown, get_acl, ModeSpec, set_acl};
+use crate::hand::node::user::{get_gid_by_groupname, get_uid_by_username};
 
-pub fn linux_file_action_api() -> Router{
+pub fn linux_file_action_api(config: &AgentConfig) -> Router {
     Router::new()
         .route("/uid", post(get_file_uid_handler))
         .route("/gid", post(get_file_gid_handler))
         .route("/id", post(get_file_id_handler))
         .route("/createTime", get(get_create_time_handler))
         .route("/updateTime", get(get_update_time_handler))
+        .route("/stat", get(stat_handler))
+        .route("/list", get(list_handler))
         .route("/contents", post(get_file_contents_handler))
         .route("/contentsButBig", post(get_file_contents_by_line_handler))
         .route("/create", put(create_handler))
@@ -25,114 +52,1888 @@ pub fn linux_file_action_api() -> Router{
         .route("/delete", put(delete_handler))
         .route("/copy-file", put(copy_file_handler))
         .route("/move-file", put(move_file_handler))
-        .route("/mkdir", put(mkdir_handler))
+        // /mkdir 是 /createDir 的别名
+        .route("/mkdir", put(create_dir_handler))
+        .route("/read", get(read_handler))
+        .route("/follow", get(follow_handler))
+        .route("/download", get(download_handler))
+        .route("/archive/tar", put(tar_handler))
+        .route("/archive/untar", put(untar_handler))
+        .route("/archive/zip", put(zip_handler))
+        .route("/archive/unzip", put(unzip_handler))
+        .route("/upload", get(upload_status_handler).put(upload_handler).delete(upload_abort_handler))
+        .route("/write", put(write_handler))
+        .route("/chmod", put(chmod_handler))
+        .route("/chown", put(chown_handler))
+        .route("/acl", get(get_acl_handler).put(set_acl_handler))
+        .with_state(Arc::new(FileState {
+            config: config.file.clone(),
+            policy: PathPolicy::new(&config.file),
+            followers: Arc::new(Semaphore::new(config.file.max_followers)),
+        }))
+}
+
+// FileState 是文件接口共享的配置, 每个接口先用 policy 检查路径, 再使用检查后返回的路径
+struct FileState {
+    config: FileConfig,
+    policy: PathPolicy,
+    // /file/follow 的连接数, 每个连接持有一个许可直到断开
+    followers: Arc<Semaphore>,
 }
 
 #[derive(Deserialize)]
 struct FilePathRequest {
     path: PathBuf,
-    dest: PathBuf
 }
 
-async fn init_file_info(path: &Path) -> io::Result<FileInfo> {
-    FileInfo::from(path)
+#[derive(Deserialize)]
+struct FileStatQuery {
+    path: PathBuf,
+    // 路径是符号链接时是否返回链接指向的文件, 默认与 stat 命令一样返回链接本身
+    #[serde(default)]
+    follow: bool,
+}
+
+#[derive(Deserialize)]
+struct FileListQuery {
+    path: PathBuf,
+    // 递归的层数, 1 只列出直接子项
+    #[serde(default = "default_list_depth")]
+    depth: usize,
+    // 是否包含隐藏文件, 与 ls -a 相同
+    #[serde(default)]
+    all: bool,
+    // 按文件名过滤的通配符, 如 *.log
+    pattern: Option<String>,
+    #[serde(default)]
+    sort: ListSortBy,
+    #[serde(default)]
+    order: SortOrder,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_line_limit")]
+    limit: usize,
+    // 是否计算每个子目录的总大小, 与 du -s 相同, 目录较大时比较慢
+    #[serde(default)]
+    du: bool,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+fn default_list_depth() -> usize {
+    1
+}
+
+#[derive(Deserialize)]
+struct FileReadQuery {
+    path: PathBuf,
+    #[serde(default)]
+    format: ReadFormat,
+    // format=lines 时跳过的行数和最多返回的行数
+    #[serde(default)]
+    offset: usize,
+    #[serde(default = "default_line_limit")]
+    limit: usize,
+}
+
+#[derive(Deserialize, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum ReadFormat {
+    // application/octet-stream 原样返回, 支持 Range
+    #[default]
+    Raw,
+    // JSON 中返回 base64 编码的内容, 同样支持 Range
+    Base64,
+    // JSON 中按行分页返回
+    Lines,
+}
+
+#[derive(Deserialize)]
+struct FileTransferRequest {
+    path: PathBuf,
+    dest: PathBuf,
+    // 目标已存在时 fail、skip 或 overwrite, 默认 fail
+    #[serde(default)]
+    if_exists: IfExists,
+}
+
+// 写入时 body 为完整的新内容, 可以用 If-Match、If-None-Match 请求头或 mtime、sha256 参数
+// 指定期望的当前文件, 文件已经被修改时返回 412
+#[derive(Deserialize)]
+struct WriteQuery {
+    path: PathBuf,
+    // 是否保留修改前的文件为 <name>.<时间>.bak
+    #[serde(default = "default_backup")]
+    backup: bool,
+    // 当前文件的修改时间, Unix 时间戳
+    mtime: Option<i64>,
+    // 当前文件内容的 SHA-256
+    sha256: Option<String>,
+}
+
+fn default_backup() -> bool {
+    true
+}
+
+#[derive(Deserialize)]
+struct ChmodRequest {
+    path: PathBuf,
+    // 八进制如 0755, 或符号形式如 u+x,go-w、a=rX
+    mode: String,
+    #[serde(default)]
+    recursive: bool,
+}
+
+#[derive(Deserialize)]
+struct ChownRequest {
+    path: PathBuf,
+    // 用户名、组名或数字 id, 至少指定一个
+    owner: Option<String>,
+    group: Option<String>,
+    #[serde(default)]
+    recursive: bool,
+}
+
+#[derive(Deserialize)]
+struct AclRequest {
+    path: PathBuf,
+    // getfacl 的文本形式, 如 user:nginx:r-x。不指定时不修改, 为空数组时删除扩展 ACL
+    access: Option<Vec<String>>,
+    default: Option<Vec<String>>,
+}
+
+#[derive(Deserialize)]
+struct ArchiveRequest {
+    // 打包时为要打包的文件或目录, 解包时为压缩包
+    path: PathBuf,
+    // 打包时为生成的压缩包, 解包时为目标目录
+    dest: PathBuf,
+    // tar、tar.gz 或 tar.zst, 不指定时根据压缩包的扩展名判断
+    format: Option<ArchiveFormat>,
+}
+
+#[derive(Deserialize)]
+struct ZipRequest {
+    path: PathBuf,
+    dest: PathBuf,
+    // 0 只存储不压缩, 1~9 为 deflate 的压缩级别
+    level: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct UnzipRequest {
+    path: PathBuf,
+    // list 为 true 时不需要
+    dest: Option<PathBuf>,
+    // 只列出 zip 包中的内容, 不解压
+    #[serde(default)]
+    list: bool,
+}
+
+// 上传时每个请求的 body 是文件的一段, 从 offset 开始写入临时文件。
+// 最后一段带上 complete=true 和整个文件的 sha256, 校验通过后 rename 到 path
+#[derive(Deserialize)]
+struct UploadQuery {
+    path: PathBuf,
+    // 必须等于已经收到的字节数, 为 0 时重新开始上传
+    #[serde(default)]
+    offset: u64,
+    #[serde(default)]
+    complete: bool,
+    sha256: Option<String>,
+    // 八进制, 如 0644
+    mode: Option<String>,
+    // 用户名、组名或数字 id
+    owner: Option<String>,
+    group: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct DownloadQuery {
+    path: PathBuf,
+    // 是否返回 SHA-256, 文件在响应头中, 目录在 trailer 中
+    #[serde(default)]
+    sha256: bool,
+}
+
+#[derive(Deserialize)]
+struct FileLinesRequest {
+    path: PathBuf,
+    // 跳过的行数
+    #[serde(default)]
+    offset: usize,
+    // 最多返回的行数
+    #[serde(default = "default_line_limit")]
+    limit: usize,
+}
+
+fn default_line_limit() -> usize {
+    1000
 }
 
-async fn get_file_uid_handler(request: Json<FilePathRequest>) -> Json<Option<i16>> {
-    let path = &request.path;
+// /file/follow 检查文件变化的间隔
+const FOLLOW_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Deserialize)]
+struct FileFollowQuery {
+    path: PathBuf,
+    // 开始时先返回最后几行, 与 tail -n 相同
+    #[serde(default = "default_follow_lines")]
+    lines: usize,
+    // 只推送匹配该正则表达式的行
+    filter: Option<String>,
+}
 
-    match init_file_info(path).await {
-        Ok(metadata) => {
-            let (user_id, _) = metadata.user_id()?;
-            Json(Some(user_id as i16))
+fn default_follow_lines() -> usize {
+    10
+}
+
+// 下载时返回 SHA-256 的响应头, 下载目录时作为 trailer
+const CHECKSUM_HEADER: &str = "x-checksum-sha256";
+
+// 以 SSE 返回打包进度时两次 progress 事件的最小间隔
+const ARCHIVE_PROGRESS_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Serialize)]
+struct FileUidResponse {
+    path: PathBuf,
+    uid: u32,
+}
+
+#[derive(Serialize)]
+struct FileGidResponse {
+    path: PathBuf,
+    gid: u32,
+}
+
+#[derive(Serialize)]
+struct FileIdResponse {
+    path: PathBuf,
+    uid: u32,
+    gid: u32,
+}
+
+#[derive(Serialize)]
+struct FileTimeResponse {
+    path: PathBuf,
+    // unix 时间戳 (秒), 文件系统不记录创建时间时为空
+    time: Option<u64>,
+}
+
+#[derive(Serialize)]
+struct FileContentsResponse {
+    path: PathBuf,
+    contents: String,
+}
+
+#[derive(Serialize)]
+struct FileLinesResponse {
+    path: PathBuf,
+    offset: usize,
+    lines: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct FileChunkResponse {
+    path: PathBuf,
+    // 文件总大小
+    size: u64,
+    // 本次返回的内容在文件中的起始位置和长度
+    start: u64,
+    length: u64,
+    encoding: &'static str,
+    data: String,
+}
+
+#[derive(Serialize)]
+struct FileListResponse {
+    path: PathBuf,
+    // 过滤后的总数, 用于分页
+    total: usize,
+    offset: usize,
+    // 遍历的项数超过 max_list_entries, 结果不完整
+    truncated: bool,
+    entries: Vec<DirEntryInfo>,
+}
+
+#[derive(Serialize)]
+struct FileLinePageResponse {
+    path: PathBuf,
+    #[serde(flatten)]
+    page: LinePage,
+}
+
+#[derive(Serialize)]
+struct ArchiveResponse {
+    path: PathBuf,
+    dest: PathBuf,
+    format: &'static str,
+    #[serde(flatten)]
+    progress: ArchiveProgress,
+}
+
+#[derive(Serialize)]
+struct ZipListResponse {
+    path: PathBuf,
+    entries: Vec<ZipEntryInfo>,
+}
+
+#[derive(Serialize)]
+struct UploadResponse {
+    path: PathBuf,
+    // 已经收到的字节数, 续传时从这里开始
+    received: u64,
+    complete: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<UploadResult>,
+}
+
+#[derive(Serialize)]
+struct PermissionResponse {
+    // 实际修改了的文件数, 已经是目标权限或属主的不计入
+    changed: u64,
+    file: FileStat,
+}
+
+#[derive(Serialize)]
+struct FileActionResponse {
+    path: PathBuf,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dest: Option<PathBuf>,
+}
+
+fn unix_time(time: SystemTime) -> Option<u64> {
+    time.duration_since(SystemTime::UNIX_EPOCH).ok().map(|duration| duration.as_secs())
+}
+
+async fn get_file_uid_handler(
+    State(state): State<Arc<FileState>>,
+    Json(mut request): Json<FilePathRequest>,
+) -> Result<Json<FileUidResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let info = FileInfo::from(&request.path)?;
+    let (uid, _) = info.user_id();
+
+    Ok(Json(FileUidResponse { path: info.path().to_path_buf(), uid }))
+}
+
+async fn get_file_gid_handler(
+    State(state): State<Arc<FileState>>,
+    Json(mut request): Json<FilePathRequest>,
+) -> Result<Json<FileGidResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let info = FileInfo::from(&request.path)?;
+    let (_, gid) = info.user_id();
+
+    Ok(Json(FileGidResponse { path: info.path().to_path_buf(), gid }))
+}
+
+async fn get_file_id_handler(
+    State(state): State<Arc<FileState>>,
+    Json(mut request): Json<FilePathRequest>,
+) -> Result<Json<FileIdResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let info = FileInfo::from(&request.path)?;
+    let (uid, gid) = info.user_id();
+
+    Ok(Json(FileIdResponse { path: info.path().to_path_buf(), uid, gid }))
+}
+
+async fn get_create_time_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut request): Query<FilePathRequest>,
+) -> Result<Json<FileTimeResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let info = FileInfo::from(&request.path)?;
+
+    Ok(Json(FileTimeResponse {
+        path: info.path().to_path_buf(),
+        // 部分文件系统不支持时返回 0
+        time: info.create_time().ok().and_then(unix_time).filter(|time| *time > 0),
+    }))
+}
+
+async fn get_update_time_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut request): Query<FilePathRequest>,
+) -> Result<Json<FileTimeResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let info = FileInfo::from(&request.path)?;
+
+    Ok(Json(FileTimeResponse {
+        path: info.path().to_path_buf(),
+        time: unix_time(info.update_time()?),
+    }))
+}
+
+async fn stat_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut query): Query<FileStatQuery>,
+) -> Result<Json<FileStat>, ApiError> {
+    query.path = state.policy.check(&query.path, Access::Read)?;
+    let info = tokio::task::spawn_blocking(move || {
+        if query.follow { FileInfo::from(&query.path) } else { FileInfo::lstat(&query.path) }
+    })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(info.stat()))
+}
+
+async fn list_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut query): Query<FileListQuery>,
+) -> Result<Json<FileListResponse>, ApiError> {
+    query.path = state.policy.check(&query.path, Access::Read)?;
+    if query.depth > state.config.max_list_depth {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("depth must be at most {}", state.config.max_list_depth),
+        ));
+    }
+    let pattern = query.pattern.as_deref()
+        .map(Pattern::new)
+        .transpose()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+    let options = ListOptions {
+        depth: query.depth,
+        hidden: query.all,
+        pattern,
+        du: query.du,
+        sort: query.sort,
+        desc: query.order == SortOrder::Desc,
+        max_entries: state.config.max_list_entries,
+        offset: query.offset,
+        limit: query.limit,
+    };
+
+    let path = query.path.clone();
+    let list_state = Arc::clone(&state);
+    let listing = tokio::task::spawn_blocking(move || list_dir(path, &options, |path| list_state.policy.is_denied(path)))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(FileListResponse {
+        path: query.path,
+        total: listing.total,
+        offset: query.offset,
+        truncated: listing.truncated,
+        entries: listing.entries,
+    }))
+}
+
+// follow_handler 通过 Server-Sent Events 推送文件新增的行, 事件类型为 line、truncated、rotated、error
+async fn follow_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut query): Query<FileFollowQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    query.path = state.policy.check(&query.path, Access::Read)?;
+    let filter = query.filter.as_deref()
+        .map(Regex::new)
+        .transpose()
+        .map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+
+    if query.lines > state.config.max_follow_lines {
+        return Err(ApiError::new(
+            StatusCode::BAD_REQUEST,
+            format!("lines must be at most {}", state.config.max_follow_lines),
+        ));
+    }
+    let permit = Arc::clone(&state.followers).try_acquire_owned()
+        .map_err(|_| ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "Too many files are being followed"))?;
+
+    let max_line = usize::try_from(state.config.max_read_size).unwrap_or(usize::MAX);
+    let (open_path, lines) = (query.path.clone(), query.lines);
+    // 跟踪符号链接指向的文件, check 已经检查过它; 之后打开文件时都不再跟随符号链接
+    let (mut follower, tail) = tokio::task::spawn_blocking(move || FileFollower::open(std::fs::canonicalize(open_path)?, lines, max_line))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    let (sender, receiver) = mpsc::channel(64);
+    let path = query.path;
+
+    // 每个连接一个定时任务, 只在读取文件时占用阻塞线程, 客户端断开后 sender 关闭, 任务随之退出并释放许可
+    tokio::spawn(async move {
+        let _permit = permit;
+        let filter = filter.as_ref();
+
+        if !send_lines(&sender, filter, tail).await {
+            return;
+        }
+
+        let mut interval = tokio::time::interval_at(tokio::time::Instant::now() + FOLLOW_INTERVAL, FOLLOW_INTERVAL);
+        while !sender.is_closed() {
+            interval.tick().await;
+
+            let state = Arc::clone(&state);
+            let (returned, events) = match tokio::task::spawn_blocking(move || {
+                let events = follower.poll(&|path| state.policy.check(path, Access::Read).is_err());
+                (follower, events)
+            }).await {
+                Ok(result) => result,
+                Err(e) => {
+                    let _ = sender.send(Event::default().event("error").data(e.to_string())).await;
+                    return;
+                }
+            };
+            follower = returned;
+            let events = match events {
+                Ok(events) => events,
+                Err(e) => {
+                    let _ = sender.send(Event::default().event("error").data(e.to_string())).await;
+                    return;
+                }
+            };
+
+            let mut lines = Vec::new();
+            for event in events {
+                let notice = match event {
+                    FollowEvent::Line(line) => {
+                        lines.push(line);
+                        continue;
+                    }
+                    FollowEvent::Truncated => "truncated",
+                    FollowEvent::Rotated => "rotated",
+                };
+                if !send_lines(&sender, filter, std::mem::take(&mut lines)).await
+                    || sender.send(Event::default().event(notice).data(path.to_string_lossy())).await.is_err() {
+                    return;
+                }
+            }
+            if !send_lines(&sender, filter, lines).await {
+                return;
+            }
         }
-        Err(_e) => {
-            Json(None)
+    });
+
+    let stream = ReceiverStream::new(receiver);
+    Ok(Sse::new(tokio_stream::StreamExt::map(stream, Ok)).keep_alive(KeepAlive::default()))
+}
+
+// send_lines 推送匹配 filter 的行, 客户端已经断开时返回 false
+async fn send_lines(sender: &mpsc::Sender<Event>, filter: Option<&Regex>, lines: Vec<String>) -> bool {
+    for line in lines.into_iter().filter(|line| filter.is_none_or(|filter| filter.is_match(line))) {
+        if sender.send(Event::default().event("line").data(line)).await.is_err() {
+            return false;
         }
     }
+    true
+}
+
+async fn get_file_contents_handler(
+    State(state): State<Arc<FileState>>,
+    Json(mut request): Json<FilePathRequest>,
+) -> Result<Json<FileContentsResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let max_read_size = state.config.max_read_size;
+    let path = request.path.clone();
+    let contents = tokio::task::spawn_blocking(move || -> Result<Vec<u8>, ApiError> {
+        check_read_size(&path, max_read_size)?;
+        // /proc、/sys 下的文件大小为 0, 能通过大小检查, 读取时同样限制在 max_read_size 以内
+        let contents = get_file_contents(&path, max_read_size)?;
+        if contents.len() as u64 > max_read_size {
+            return Err(ApiError::new(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                format!("{} is larger than max_read_size {}; use a Range header", path.display(), max_read_size),
+            ));
+        }
+        Ok(contents)
+    })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    // 先检查大小再解码, 在限制处被截断的多字节字符不会被当作编码错误
+    let contents = String::from_utf8(contents).map_err(|_| ApiError::new(
+        StatusCode::UNPROCESSABLE_ENTITY,
+        format!("{} is not valid UTF-8; use /download or a Range header", request.path.display()),
+    ))?;
+
+    Ok(Json(FileContentsResponse { path: request.path, contents }))
+}
+
+async fn get_file_contents_by_line_handler(
+    State(state): State<Arc<FileState>>,
+    Json(mut request): Json<FileLinesRequest>,
+) -> Result<Json<FileLinesResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    let (path, offset, limit, max_read_size) = (request.path.clone(), request.offset, request.limit, state.config.max_read_size);
+    let page = tokio::task::spawn_blocking(move || get_file_contents_by_line(&path, offset, limit, max_read_size))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(FileLinesResponse { path: request.path, offset: request.offset, lines: page.lines }))
+}
+
+// check_read_size 拒绝一次性读取超过 max_read_size 的文件
+fn check_read_size(path: &Path, max_read_size: u64) -> Result<u64, ApiError> {
+    let metadata = std::fs::metadata(path)?;
+    if metadata.is_dir() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, format!("{} is a directory", path.display())));
+    }
+    if metadata.len() > max_read_size {
+        return Err(too_large(path, metadata.len(), max_read_size));
+    }
+    Ok(metadata.len())
 }
 
-async fn get_file_gid_handler(request: Json<FilePathRequest>) -> Json<Option<i16>> {
-    let path = &request.path;
+fn too_large(path: &Path, size: u64, max_read_size: u64) -> ApiError {
+    ApiError::new(
+        StatusCode::PAYLOAD_TOO_LARGE,
+        format!(
+            "{} is {} bytes, larger than max_read_size {}; use a Range header or format=lines",
+            path.display(), size, max_read_size,
+        ),
+    )
+}
+
+// ByteRange 是 Range 请求头解析后的结果, end 不包含在内
+enum ByteRange {
+    Full,
+    Partial { start: u64, end: u64 },
+    Unsatisfiable,
+}
+
+// 打包时跳过禁止访问的文件, 解包时遇到禁止访问的位置则失败
+async fn tar_handler(
+    State(state): State<Arc<FileState>>,
+    headers: HeaderMap,
+    Json(request): Json<ArchiveRequest>,
+) -> Result<Response, ApiError> {
+    let path = state.policy.check(&request.path, Access::Read)?;
+    let dest = state.policy.check(&request.dest, Access::Write)?;
+    let format = archive_format(request.format, &dest)?;
+    let (src, archive) = (path.clone(), dest.clone());
+    archive_response(&headers, path, dest, format.name(), move |progress| {
+        create_tar(&archive, &src, format, &|path| state.policy.is_denied(path), progress)
+    }).await
+}
+
+async fn untar_handler(
+    State(state): State<Arc<FileState>>,
+    headers: HeaderMap,
+    Json(request): Json<ArchiveRequest>,
+) -> Result<Response, ApiError> {
+    let path = state.policy.check(&request.path, Access::Read)?;
+    let dest = state.policy.check(&request.dest, Access::Write)?;
+    let format = archive_format(request.format, &path)?;
+    let (archive, target) = (path.clone(), dest.clone());
+    archive_response(&headers, path, dest, format.name(), move |progress| {
+        extract_tar(&archive, &target, format, &|path| state.policy.is_denied(path), progress)
+    }).await
+}
+
+async fn zip_handler(
+    State(state): State<Arc<FileState>>,
+    headers: HeaderMap,
+    Json(request): Json<ZipRequest>,
+) -> Result<Response, ApiError> {
+    let path = state.policy.check(&request.path, Access::Read)?;
+    let dest = state.policy.check(&request.dest, Access::Write)?;
+    let (src, archive, level) = (path.clone(), dest.clone(), request.level);
+    archive_response(&headers, path, dest, "zip", move |progress| {
+        create_zip(&src, &archive, level, &|path| state.policy.is_denied(path), progress)
+    }).await
+}
+
+async fn unzip_handler(
+    State(state): State<Arc<FileState>>,
+    headers: HeaderMap,
+    Json(mut request): Json<UnzipRequest>,
+) -> Result<Response, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Read)?;
+    if request.list {
+        let path = request.path.clone();
+        let entries = tokio::task::spawn_blocking(move || list_zip(&path))
+            .await
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+        return Ok(Json(ZipListResponse { path: request.path, entries }).into_response());
+    }
+
+    let dest = request.dest.ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, "dest is required unless list is true"))?;
+    let dest = state.policy.check(&dest, Access::Write)?;
+    let (path, target) = (request.path.clone(), dest.clone());
+    archive_response(&headers, request.path, dest, "zip", move |progress| {
+        extract_zip(&path, &target, &|path| state.policy.is_denied(path), progress)
+    }).await
+}
+
+async fn upload_status_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut request): Query<FilePathRequest>,
+) -> Result<Json<UploadResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Write)?;
+    let received = upload_received(&request.path)?;
+    Ok(Json(UploadResponse { path: request.path, received, complete: false, file: None }))
+}
+
+async fn upload_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut query): Query<UploadQuery>,
+    body: Body,
+) -> Result<Json<UploadResponse>, ApiError> {
+    query.path = state.policy.check(&query.path, Access::Write)?;
+    let received = upload_received(&query.path)?;
+    if query.offset != 0 && query.offset != received {
+        return Err(ApiError::new(
+            StatusCode::CONFLICT,
+            format!("Upload of {} has received {} bytes, got offset {}", query.path.display(), received, query.offset),
+        ));
+    }
+
+    // 先检查参数, 避免写完数据后才发现参数错误
+    let mode = query.mode.as_deref()
+        .map(|mode| u32::from_str_radix(mode, 8).ok().filter(|mode| *mode <= 0o7777)
+            .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, format!("Invalid mode {:?}", mode))))
+        .transpose()?;
+    let (uid, gid) = owner_ids(query.owner.as_deref(), query.group.as_deref())?;
+    if query.complete && query.sha256.is_none() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "sha256 is required to complete an upload"));
+    }
+
+    let temp_path = query.path.clone();
+    let file = tokio::task::spawn_blocking(move || open_upload_temp(&temp_path))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    let mut file = tokio::fs::File::from_std(file);
+    file.set_len(query.offset).await?;
+    file.seek(SeekFrom::Start(query.offset)).await?;
+
+    let mut received = query.offset;
+    let mut stream = body.into_data_stream();
+    while let Some(chunk) = tokio_stream::StreamExt::next(&mut stream).await {
+        let chunk = chunk.map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+        file.write_all(&chunk).await?;
+        received += chunk.len() as u64;
+    }
+    file.flush().await?;
+    drop(file);
+
+    let Some(sha256) = query.sha256.filter(|_| query.complete) else {
+        return Ok(Json(UploadResponse { path: query.path, received, complete: false, file: None }));
+    };
+    let path = query.path.clone();
+    let result = tokio::task::spawn_blocking(move || upload_file(&path, &sha256, mode, uid, gid))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
 
-    match init_file_info(path).await {
-        Ok(metadata) => {
-            let (_, gid) = metadata.user_id()?;
-            Json(Some(gid as i16))
+    Ok(Json(UploadResponse { path: query.path, received, complete: true, file: Some(result) }))
+}
+
+// upload_abort_handler 放弃上传, 删除临时文件
+async fn upload_abort_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut request): Query<FilePathRequest>,
+) -> Result<Json<FileActionResponse>, ApiError> {
+    request.path = state.policy.check(&request.path, Access::Write)?;
+    tokio::fs::remove_file(upload_temp_path(&request.path)?).await?;
+    Ok(Json(FileActionResponse { path: request.path, dest: None }))
+}
+
+fn archive_format(format: Option<ArchiveFormat>, archive: &Path) -> Result<ArchiveFormat, ApiError> {
+    format.or_else(|| ArchiveFormat::from_path(archive)).ok_or_else(|| ApiError::new(
+        StatusCode::BAD_REQUEST,
+        format!("Cannot detect archive format of {}, specify format", archive.display()),
+    ))
+}
+
+// archive_response 在阻塞线程中执行打包或解包。
+// 请求头 Accept 为 text/event-stream 时以 SSE 推送 progress 事件, 结束时推送 done 或 error 事件,
+// 否则完成后返回 JSON。客户端断开连接后任务仍会执行完
+async fn archive_response<F>(
+    headers: &HeaderMap,
+    path: PathBuf,
+    dest: PathBuf,
+    format: &'static str,
+    job: F,
+) -> Result<Response, ApiError>
+    where
+        F: FnOnce(&mut dyn FnMut(&ArchiveProgress)) -> io::Result<ArchiveProgress> + Send + 'static,
+{
+    let wants_events = headers.get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/event-stream"));
+
+    if !wants_events {
+        let progress = tokio::task::spawn_blocking(move || job(&mut |_| {}))
+            .await
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+        return Ok(Json(ArchiveResponse { path, dest, format, progress }).into_response());
+    }
+
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        let mut last = Instant::now();
+        let result = job(&mut |progress| {
+            if last.elapsed() >= ARCHIVE_PROGRESS_INTERVAL {
+                last = Instant::now();
+                if let Ok(event) = Event::default().event("progress").json_data(progress) {
+                    let _ = sender.blocking_send(event);
+                }
+            }
+        });
+
+        let event = match result {
+            Ok(progress) => Event::default().event("done").json_data(ArchiveResponse { path, dest, format, progress }),
+            Err(e) => Ok(Event::default().event("error").data(e.to_string())),
+        };
+        if let Ok(event) = event {
+            let _ = sender.blocking_send(event);
         }
-        Err(_e) => {
-            Json(None)
+    });
+
+    let stream = ReceiverStream::new(receiver);
+    Ok(Sse::new(tokio_stream::StreamExt::map(stream, Ok::<_, Infallible>)).into_response())
+}
+
+// parse_range 解析单个 bytes=start-end / bytes=start- / bytes=-suffix 形式的 Range,
+// 格式不正确或包含多个范围时按 RFC 9110 忽略 Range, 返回整个文件
+fn parse_range(headers: &HeaderMap, size: u64) -> ByteRange {
+    let Some(spec) = headers.get(header::RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().strip_prefix("bytes=")) else {
+        return ByteRange::Full;
+    };
+    if spec.contains(',') {
+        return ByteRange::Full;
+    }
+    let Some((start, end)) = spec.split_once('-') else {
+        return ByteRange::Full;
+    };
+
+    let (start, end) = match (start.trim(), end.trim()) {
+        ("", suffix) => match suffix.parse::<u64>() {
+            Ok(0) => return ByteRange::Unsatisfiable,
+            Ok(suffix) => (size.saturating_sub(suffix), size),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, "") => match start.parse::<u64>() {
+            Ok(start) => (start, size),
+            Err(_) => return ByteRange::Full,
+        },
+        (start, end) => match (start.parse::<u64>(), end.parse::<u64>()) {
+            (Ok(start), Ok(end)) if start <= end => (start, end.saturating_add(1).min(size)),
+            _ => return ByteRange::Full,
+        },
+    };
+
+    if start >= size {
+        return ByteRange::Unsatisfiable;
+    }
+    ByteRange::Partial { start, end }
+}
+
+// read_handler 读取文件内容, 超过 max_read_size 的部分需要通过 Range 分段读取
+async fn read_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut query): Query<FileReadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    query.path = state.policy.check(&query.path, Access::Read)?;
+    let path = query.path;
+    let metadata = tokio::fs::metadata(&path).await?;
+    if metadata.is_dir() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, format!("{} is a directory", path.display())));
+    }
+
+    if query.format == ReadFormat::Lines {
+        let (offset, limit, max) = (query.offset, query.limit, state.config.max_read_size);
+        let lines_path = path.clone();
+        let page = tokio::task::spawn_blocking(move || get_file_contents_by_line(lines_path, offset, limit, max))
+            .await
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+        return Ok(Json(FileLinePageResponse { path, page }).into_response());
+    }
+
+    let size = metadata.len();
+    let (start, end, partial) = match parse_range(&headers, size) {
+        ByteRange::Full if size > state.config.max_read_size => {
+            return Err(too_large(&path, size, state.config.max_read_size));
+        }
+        ByteRange::Full => (0, size, false),
+        // 超过 max_read_size 的范围截断, 客户端根据 Content-Range 继续读取
+        ByteRange::Partial { start, end } => (start, end.min(start + state.config.max_read_size), true),
+        ByteRange::Unsatisfiable => {
+            return Ok((
+                StatusCode::RANGE_NOT_SATISFIABLE,
+                [(header::CONTENT_RANGE, format!("bytes */{}", size))],
+            ).into_response());
         }
+    };
+
+    // /proc、/sys 下的文件大小为 0, 只能读到文件末尾为止
+    let length = if size == 0 && !partial { state.config.max_read_size } else { end - start };
+
+    if query.format == ReadFormat::Base64 {
+        let chunk_path = path.clone();
+        let data = tokio::task::spawn_blocking(move || read_range(chunk_path, start, length))
+            .await
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+        return Ok(Json(FileChunkResponse {
+            path,
+            size,
+            start,
+            length: data.len() as u64,
+            encoding: "base64",
+            data: BASE64.encode(&data),
+        }).into_response());
+    }
+
+    let mut file = tokio::fs::File::open(&path).await?;
+    file.seek(SeekFrom::Start(start)).await?;
+    let body = Body::from_stream(ReaderStream::new(file.take(length)));
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::ACCEPT_RANGES, "bytes");
+    if size > 0 || partial {
+        response = response.header(header::CONTENT_LENGTH, end - start);
+    }
+    if partial {
+        response = response
+            .status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end - 1, size));
     }
+
+    response.body(body).map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
 
-async fn get_file_id_handler(request: Json<FilePathRequest>) -> Json<Option<(i16, i16)>>{
-    let path = &request.path;
+// download_handler 下载文件, 目录被打包成 tar.gz 边打包边返回, 其中禁止访问的文件被跳过。
+// 文件的 ETag 由 inode、mtime 和大小生成, 支持 If-None-Match; 目录的内容变化不一定改变目录的 mtime, 所以没有 ETag
+// 请求 sha256 时, 文件和目录的 SHA-256 都在内容之后作为 trailer 发送
+async fn download_handler(
+    State(state): State<Arc<FileState>>,
+    Query(mut query): Query<DownloadQuery>,
+    headers: HeaderMap,
+) -> Result<Response, ApiError> {
+    query.path = state.policy.check(&query.path, Access::Read)?;
+    let path = query.path;
+    let metadata = tokio::fs::metadata(&path).await?;
+    let name = path.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, format!("Cannot download {}", path.display())))?;
+
+    if metadata.is_dir() {
+        return download_dir_response(state, path, &name, query.sha256);
+    }
+    if !metadata.is_file() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, format!("{} is not a regular file", path.display())));
+    }
 
-    match init_file_info(path).await {
-        Ok(metadata) => {
-            let (uid, gid) = metadata.user_id()?;
-            Json(Some((uid as i16, gid as i16)))
+    let etag = file_etag(&metadata);
+    if if_none_match(&headers, &etag) {
+        return Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .header(header::ETAG, etag)
+            .body(Body::empty())
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+    }
+
+    // /proc 下的文件大小为 0, 此时读到文件末尾并且不返回 Content-Length
+    let size = metadata.len();
+    let limit = if size > 0 { size } else { u64::MAX };
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, "application/octet-stream")
+        .header(header::CONTENT_DISPOSITION, content_disposition(&name))
+        .header(header::ETAG, etag);
+    if let Some(modified) = Utc.timestamp_opt(metadata.mtime(), 0).single() {
+        response = response.header(header::LAST_MODIFIED, modified.format("%a, %d %b %Y %H:%M:%S GMT").to_string());
+    }
+    let file = tokio::fs::File::open(&path).await?;
+    let stream = ReaderStream::new(file.take(limit));
+    if !query.sha256 {
+        if size > 0 {
+            response = response.header(header::CONTENT_LENGTH, size);
         }
-        Err(_e) => {
-            Json(None)
+        return response.body(Body::from_stream(stream))
+            .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()));
+    }
+
+    // SHA-256 对实际发送的字节计算, 发送完后作为 trailer 返回。trailer 需要 chunked 编码, 所以不返回 Content-Length
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::spawn(async move {
+        let mut stream = stream;
+        let mut hasher = Sha256::new();
+        while let Some(chunk) = tokio_stream::StreamExt::next(&mut stream).await {
+            match chunk {
+                Ok(chunk) => {
+                    hasher.update(&chunk);
+                    if sender.send(Ok(Frame::data(chunk))).await.is_err() {
+                        return;
+                    }
+                }
+                Err(e) => {
+                    let _ = sender.send(Err(e)).await;
+                    return;
+                }
+            }
         }
+        let _ = sender.send(Ok(Frame::trailers(checksum_trailers(hasher)))).await;
+    });
+    response = response.header(header::TRAILER, CHECKSUM_HEADER);
+    let body = Body::new(StreamBody::new(ReceiverStream::new(receiver)));
+    response.body(body).map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
+}
+
+fn checksum_trailers(hasher: Sha256) -> HeaderMap {
+    let mut trailers = HeaderMap::new();
+    if let Ok(value) = format!("{:x}", hasher.finalize()).parse() {
+        trailers.insert(CHECKSUM_HEADER, value);
     }
+    trailers
 }
 
-async fn get_create_time_handler(request: Json<FilePathRequest>) -> io::Result<SystemTime>
-{
-    let path = &request.path;
-    Ok(init_file_info(path).await?.create_time().unwrap_or(SystemTime::UNIX_EPOCH))
+// ChannelWriter 把打包的数据分块发送给响应, 同时计算 SHA-256
+struct ChannelWriter {
+    sender: mpsc::Sender<Result<Frame<Bytes>, io::Error>>,
+    hasher: Option<Sha256>,
 }
 
-async fn get_update_time_handler(request: Json<FilePathRequest>) -> io::Result<SystemTime> {
-    let path = &request.path;
-    Ok(init_file_info(path).await?.update_time().unwrap_or(SystemTime::UNIX_EPOCH))
+impl Write for ChannelWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(buf);
+        }
+        // 客户端断开后停止打包
+        self.sender.blocking_send(Ok(Frame::data(Bytes::copy_from_slice(buf))))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "Download connection closed"))?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
 }
 
-async fn get_file_contents_handler(request: Json<FilePathRequest>)
-    -> Result<Json<io::Result<String>>, io::Error>
-{
-    let path = &request.path;
-    Ok(Json(get_file_contents(path).await))
+// download_dir_response 在阻塞线程中打包目录, SHA-256 在打包完成后作为 trailer 发送,
+// 只有请求头带 TE: trailers 的客户端才能收到。打包中途出错时响应被中断, 客户端会收到不完整的 chunked 响应
+fn download_dir_response(state: Arc<FileState>, path: PathBuf, name: &str, sha256: bool) -> Result<Response, ApiError> {
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::task::spawn_blocking(move || {
+        let writer = ChannelWriter { sender: sender.clone(), hasher: sha256.then(Sha256::new) };
+        match download(&path, &|path| state.policy.is_denied(path), writer) {
+            Ok(writer) => {
+                if let Some(hasher) = writer.hasher {
+                    let _ = sender.blocking_send(Ok(Frame::trailers(checksum_trailers(hasher))));
+                }
+            }
+            Err(e) => {
+                let _ = sender.blocking_send(Err(e));
+            }
+        }
+    });
+
+    let mut response = Response::builder()
+        .header(header::CONTENT_TYPE, "application/gzip")
+        .header(header::CONTENT_DISPOSITION, content_disposition(&format!("{}.tar.gz", name)));
+    if sha256 {
+        response = response.header(header::TRAILER, CHECKSUM_HEADER);
+    }
+    let body = Body::new(StreamBody::new(ReceiverStream::new(receiver)));
+    response.body(body).map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))
 }
 
-async fn get_file_contents_by_line_handler(request: Json<FilePathRequest>)
-    -> Result<Json<io::Result<BufReader<File>>>, io::Error>
-{
-    let path = &request.path;
-    Ok(Json(get_file_contents_by_line(path).await))
+fn if_none_match(headers: &HeaderMap, etag: &str) -> bool {
+    headers.get(header::IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.split(',')
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag))
+}
+
+// header_tags 返回 If-Match、If-None-Match 中的 ETag 列表, 没有这个请求头时为 None
+fn header_tags(headers: &HeaderMap, name: header::HeaderName) -> Result<Option<Vec<String>>, ApiError> {
+    let mut tags = Vec::new();
+    for value in headers.get_all(name) {
+        let value = value.to_str().map_err(|e| ApiError::new(StatusCode::BAD_REQUEST, e.to_string()))?;
+        tags.extend(value.split(',').map(|tag| tag.trim().to_string()));
+    }
+    Ok(Some(tags).filter(|tags| !tags.is_empty()))
+}
+
+// content_disposition 生成 attachment 头, filename 中只保留可打印的 ASCII 字符, 完整的文件名放在 filename* 中
+fn content_disposition(name: &str) -> String {
+    let fallback = name.chars()
+        .map(|c| if (c.is_ascii_graphic() || c == ' ') && c != '"' && c != '\\' { c } else { '_' })
+        .collect::<String>();
+    let encoded = name.bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9'
+            | b'!' | b'#' | b'$' | b'&' | b'+' | b'-' | b'.' | b'^' | b'_' | b'`' | b'|' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect::<String>();
+    format!("attachment; filename=\"{}\"; filename*=UTF-8''{}", fallback, encoded)
+}
+
+async fn create_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<FilePathRequest>,
+) -> Result<Json<FileActionResponse>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    let path = tokio::task::spawn_blocking(move || create_file(&path).map(|()| path))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(Json(FileActionResponse { path, dest: None }))
+}
+
+async fn create_dir_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<FilePathRequest>,
+) -> Result<Json<FileActionResponse>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    let path = tokio::task::spawn_blocking(move || mkdir(&path).map(|()| path))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(Json(FileActionResponse { path, dest: None }))
+}
+
+async fn delete_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<FilePathRequest>,
+) -> Result<Json<FileActionResponse>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    let path = tokio::task::spawn_blocking(move || delete_file(&path).map(|()| path))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(Json(FileActionResponse { path, dest: None }))
+}
+
+async fn copy_file_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<FileTransferRequest>,
+) -> Result<Json<TransferResult>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Read)?;
+    let dest = state.policy.check(&request.dest, Access::Write)?;
+    let result = tokio::task::spawn_blocking(move || {
+        copy_file(&path, &dest, request.if_exists, &|path| state.policy.is_denied(path))
+    })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(Json(result))
+}
+
+async fn move_file_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<FileTransferRequest>,
+) -> Result<Json<TransferResult>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    let dest = state.policy.check(&request.dest, Access::Write)?;
+    let result = tokio::task::spawn_blocking(move || {
+        move_file(&path, &dest, request.if_exists, &|path| state.policy.is_denied(path))
+    })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    Ok(Json(result))
+}
+
+// write_handler 原子地替换文件内容, 响应头中返回新的 ETag, 下一次修改时作为 If-Match
+async fn write_handler(
+    State(state): State<Arc<FileState>>,
+    Query(query): Query<WriteQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<(HeaderMap, Json<WriteResult>), ApiError> {
+    let path = state.policy.check(&query.path, Access::Write)?;
+    let options = WriteOptions {
+        if_match: header_tags(&headers, header::IF_MATCH)?,
+        if_none_match: header_tags(&headers, header::IF_NONE_MATCH)?,
+        mtime: query.mtime,
+        sha256: query.sha256,
+        backup: query.backup,
+    };
+
+    let result = tokio::task::spawn_blocking(move || write_file(&path, &body, &options))
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+    let mut headers = HeaderMap::new();
+    if let Ok(etag) = result.etag.parse() {
+        headers.insert(header::ETAG, etag);
+    }
+    Ok((headers, Json(result)))
 }
 
-fn create_handler(request: Json<FilePathRequest>) -> Result<io::Result<()>, io::Error> {
-    let path = &request.path;
-    Ok(create_file(path))
+async fn chmod_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<ChmodRequest>,
+) -> Result<Json<PermissionResponse>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    let spec = ModeSpec::parse(&request.mode)?;
+    let target = path.clone();
+    let changed = tokio::task::spawn_blocking(move || {
+        chmod(&target, &spec, request.recursive, &|path| state.policy.is_denied(path))
+    })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(PermissionResponse { changed, file: FileInfo::from(&path)?.stat() }))
 }
 
-fn create_dir_handler(request: Json<FilePathRequest>) -> Result<io::Result<()>, io::Error> {
-    let path = &request.path;
-    Ok(mkdir(path))
+async fn chown_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<ChownRequest>,
+) -> Result<Json<PermissionResponse>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    let (uid, gid) = owner_ids(request.owner.as_deref(), request.group.as_deref())?;
+    if uid.is_none() && gid.is_none() {
+        return Err(ApiError::new(StatusCode::BAD_REQUEST, "owner or group is required"));
+    }
+    let target = path.clone();
+    let changed = tokio::task::spawn_blocking(move || {
+        chown(&target, uid, gid, request.recursive, &|path| state.policy.is_denied(path))
+    })
+        .await
+        .map_err(|e| ApiError::new(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()))??;
+
+    Ok(Json(PermissionResponse { changed, file: FileInfo::from(&path)?.stat() }))
 }
 
-fn delete_handler(request: Json<FilePathRequest>) -> Result<io::Result<()>, io::Error> {
-    let path = &request.path;
-    Ok(delete_file(path))
+async fn get_acl_handler(
+    State(state): State<Arc<FileState>>,
+    Query(query): Query<FilePathRequest>,
+) -> Result<Json<Acl>, ApiError> {
+    let path = state.policy.check(&query.path, Access::Read)?;
+    Ok(Json(get_acl(&path)?))
 }
 
-fn copy_file_handler(request: Json<FilePathRequest>) -> Result<io::Result<()>, io::Error> {
-    let path = &request.path;
-    let dest = &request.dest;
-    Ok(copy_file(path, dest))
+async fn set_acl_handler(
+    State(state): State<Arc<FileState>>,
+    Json(request): Json<AclRequest>,
+) -> Result<Json<Acl>, ApiError> {
+    let path = state.policy.check(&request.path, Access::Write)?;
+    Ok(Json(set_acl(&path, request.access.as_deref(), request.default.as_deref())?))
 }
 
-fn move_file_handler(request: Json<FilePathRequest>) -> Result<io::Result<()>, io::Error> {
-    let path = &request.path;
-    let dest = &request.dest;
-    Ok(move_file(path, dest))
+// owner_ids 通过 /etc/passwd 和 /etc/group 把用户名、组名转换为 id, 数字直接作为 id
+fn owner_ids(owner: Option<&str>, group: Option<&str>) -> Result<(Option<u32>, Option<u32>), ApiError> {
+    let uid = owner
+        .map(|owner| get_uid_by_username(owner)
+            .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, format!("Unknown user {:?}", owner))))
+        .transpose()?;
+    let gid = group
+        .map(|group| get_gid_by_groupname(group)
+            .ok_or_else(|| ApiError::new(StatusCode::BAD_REQUEST, format!("Unknown group {:?}", group))))
+        .transpose()?;
+    Ok((uid, gid))
 }
 
-fn mkdir_handler(request: Json<FilePathRequest>) -> Result<io::Result<()>, io::Error> {
-    let path = &request.path;
-    Ok(mkdir(path))
-}
\ No newline at end of file
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt};
+    use std::time::Duration;
+
+    use axum::body::{Body, Bytes};
+    use axum::http::{header, HeaderMap, Method, Request, StatusCode};
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tower::ServiceExt;
+
+    use crate::config::agent_config::AgentConfig;
+
+    use super::linux_file_action_api;
+
+    // test_config 允许写入临时目录, 默认配置不允许任何写操作
+    fn test_config() -> AgentConfig {
+        let mut config = AgentConfig::default();
+        config.file.write_roots = vec![std::env::temp_dir()];
+        config
+    }
+
+    async fn send(method: Method, uri: &str, body: Option<Value>) -> (StatusCode, Value) {
+        let request = Request::builder().method(method).uri(uri);
+        let request = match body {
+            Some(body) => request
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap(),
+            None => request.body(Body::empty()).unwrap(),
+        };
+
+        let (status, _, bytes) = send_request(&test_config(), request).await;
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    async fn send_request(config: &AgentConfig, request: Request<Body>) -> (StatusCode, HeaderMap, Bytes) {
+        let response = linux_file_action_api(config).oneshot(request).await.unwrap();
+        let status = response.status();
+        let headers = response.headers().clone();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, headers, bytes)
+    }
+
+    fn get_with_range(uri: &str, range: &str) -> Request<Body> {
+        Request::builder().uri(uri).header(header::RANGE, range).body(Body::empty()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn returns_full_uid_and_gid() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("owned.txt");
+        fs::write(&path, "").unwrap();
+        let metadata = fs::metadata(&path).unwrap();
+
+        let (status, body) = send(Method::POST, "/id", Some(json!({ "path": path }))).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["uid"], metadata.uid());
+        assert_eq!(body["gid"], metadata.gid());
+    }
+
+    #[tokio::test]
+    async fn missing_file_is_not_found_envelope() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("missing.txt");
+
+        let (status, body) = send(Method::POST, "/uid", Some(json!({ "path": path }))).await;
+
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["status"], 404);
+        assert!(body["error"]["message"].is_string());
+    }
+
+    #[tokio::test]
+    async fn update_time_reads_path_from_query() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("time.txt");
+        fs::write(&path, "").unwrap();
+        let mtime = fs::metadata(&path).unwrap().mtime();
+
+        let uri = format!("/updateTime?path={}", path.display());
+        let (status, body) = send(Method::GET, &uri, None).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["time"], mtime);
+    }
+
+    #[tokio::test]
+    async fn writes_with_if_match() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("nginx.conf");
+        fs::write(&path, "worker_processes 1;\n").unwrap();
+        let uri = format!("/write?path={}&backup=false", path.display());
+        let write = |etag: &str, body: &'static str| Request::builder()
+            .method(Method::PUT)
+            .uri(&uri)
+            .header(header::IF_MATCH, etag)
+            .body(Body::from(body))
+            .unwrap();
+
+        let request = Request::builder().uri(format!("/download?path={}", path.display())).body(Body::empty()).unwrap();
+        let (_, headers, _) = send_request(&test_config(), request).await;
+        let etag = headers[header::ETAG].to_str().unwrap().to_string();
+
+        let (status, headers, body) = send_request(&test_config(), write(&etag, "worker_processes 4;\n")).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["etag"], headers[header::ETAG].to_str().unwrap());
+        assert_eq!(body["created"], false);
+        assert_eq!(body["backup"], Value::Null);
+
+        let (status, _, body) = send_request(&test_config(), write(&etag, "worker_processes 16;\n")).await;
+        assert_eq!(status, StatusCode::PRECONDITION_FAILED);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["status"], 412);
+        assert_eq!(fs::read_to_string(&path).unwrap(), "worker_processes 4;\n");
+    }
+
+    #[tokio::test]
+    async fn changes_mode_owner_and_acl() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("perm.txt");
+        fs::write(&path, "").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o600)).unwrap();
+
+        let (status, body) = send(Method::PUT, "/chmod", Some(json!({ "path": path, "mode": "g+r,o=g" }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["changed"], 1);
+        assert_eq!(body["file"]["mode"], "0644");
+        let (status, _) = send(Method::PUT, "/chmod", Some(json!({ "path": path, "mode": "u+y" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let uid = fs::metadata(&path).unwrap().uid();
+        let (status, body) = send(Method::PUT, "/chown", Some(json!({ "path": path, "owner": uid.to_string() }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["changed"], 0);
+        let (status, body) = send(Method::PUT, "/chown", Some(json!({ "path": path, "owner": "no-such-user" }))).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"]["message"], "Unknown user \"no-such-user\"");
+
+        let (status, body) = send(Method::GET, &format!("/acl?path={}", path.display()), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["access"], json!(["user::rw-", "group::r--", "other::r--"]));
+        let acl = json!({ "path": path, "access": ["user::rw-", "group::r--"] });
+        let (status, _) = send(Method::PUT, "/acl", Some(acl)).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn stats_file_mode_and_symlink() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stat.txt");
+        fs::write(&path, "hello").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o4750)).unwrap();
+        let link = dir.path().join("stat.link");
+        std::os::unix::fs::symlink(&path, &link).unwrap();
+
+        let (status, body) = send(Method::GET, &format!("/stat?path={}", path.display()), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["file_type"], "file");
+        assert_eq!(body["size"], 5);
+        assert_eq!(body["mode"], "4750");
+        assert_eq!(body["permissions"], "-rwsr-x---");
+        assert!(body["mtime"].as_str().unwrap().contains('T'));
+
+        let (status, body) = send(Method::GET, &format!("/stat?path={}", link.display()), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["file_type"], "symlink");
+        assert_eq!(body["symlink_target"], json!(path));
+
+        let uri = format!("/stat?path={}&follow=true", link.display());
+        let (status, body) = send(Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["file_type"], "file");
+        assert_eq!(body["symlink_target"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn lists_directory_with_filters_and_sorting() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a.log"), "12345").unwrap();
+        fs::write(dir.path().join("b.txt"), "1").unwrap();
+        fs::write(dir.path().join(".hidden.log"), "").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub").join("c.log"), "1234567890").unwrap();
+        let names = |body: &Value| body["entries"].as_array().unwrap().iter()
+            .map(|entry| entry["name"].as_str().unwrap().to_string())
+            .collect::<Vec<_>>();
+
+        let (status, body) = send(Method::GET, &format!("/list?path={}", dir.path().display()), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(names(&body), vec!["a.log", "b.txt", "sub"]);
+        assert_eq!(body["entries"][0]["file_type"], "file");
+        assert_eq!(body["entries"][2]["file_type"], "directory");
+
+        let uri = format!("/list?path={}&depth=2&all=true&pattern=*.log&sort=size&order=desc", dir.path().display());
+        let (_, body) = send(Method::GET, &uri, None).await;
+        assert_eq!(names(&body), vec!["c.log", "a.log", ".hidden.log"]);
+        assert_eq!(body["entries"][0]["depth"], 2);
+
+        let uri = format!("/list?path={}&du=true&sort=size&order=desc&offset=0&limit=1", dir.path().display());
+        let (_, body) = send(Method::GET, &uri, None).await;
+        assert_eq!(body["total"], 3);
+        assert_eq!(names(&body), vec!["sub"]);
+        assert_eq!(body["entries"][0]["dir_size"], 10);
+
+        let uri = format!("/list?path={}", dir.path().join("b.txt").display());
+        let (status, _) = send(Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn pages_contents_by_line() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("lines.txt");
+        fs::write(&path, "a\nb\nc\nd\n").unwrap();
+
+        let request = json!({ "path": path, "offset": 1, "limit": 2 });
+        let (status, body) = send(Method::POST, "/contentsButBig", Some(request)).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["lines"], json!(["b", "c"]));
+        assert_eq!(body["offset"], 1);
+    }
+
+    #[tokio::test]
+    async fn reads_byte_ranges() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("range.bin");
+        fs::write(&path, b"0123456789").unwrap();
+        let uri = format!("/read?path={}", path.display());
+        let config = test_config();
+
+        let (status, headers, body) = send_request(&config, get_with_range(&uri, "bytes=2-4")).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 2-4/10");
+        assert_eq!(&body[..], b"234");
+
+        let (status, _, body) = send_request(&config, get_with_range(&uri, "bytes=-3")).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(&body[..], b"789");
+
+        let (status, headers, _) = send_request(&config, get_with_range(&uri, "bytes=20-")).await;
+        assert_eq!(status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn limits_reads_to_max_read_size() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("large.bin");
+        fs::write(&path, b"0123456789").unwrap();
+        let uri = format!("/read?path={}", path.display());
+        let mut config = test_config();
+        config.file.max_read_size = 4;
+
+        let request = Request::builder().uri(&uri).body(Body::empty()).unwrap();
+        let (status, _, body) = send_request(&config, request).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["status"], 413);
+
+        // 超过上限的 Range 被截断, 客户端根据 Content-Range 继续读取
+        let (status, headers, body) = send_request(&config, get_with_range(&uri, "bytes=3-")).await;
+        assert_eq!(status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(headers[header::CONTENT_RANGE], "bytes 3-6/10");
+        assert_eq!(&body[..], b"3456");
+    }
+
+    #[tokio::test]
+    async fn bounds_contents_of_zero_sized_proc_files() {
+        let mut config = test_config();
+        config.file.max_read_size = 16;
+
+        // /proc 下的文件大小为 0, 内容超过上限时同样返回 413
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/contents")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "path": "/proc/self/status" }).to_string()))
+            .unwrap();
+        let (status, _, body) = send_request(&config, request).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["error"]["status"], 413);
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("small.txt");
+        fs::write(&path, "0123456789abcdef").unwrap();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/contents")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "path": path }).to_string()))
+            .unwrap();
+        let (status, _, body) = send_request(&config, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["contents"], "0123456789abcdef");
+
+        // 不是 UTF-8 的文件返回 422
+        let path = dir.path().join("binary.bin");
+        fs::write(&path, [0xff, 0xfe, 0x00]).unwrap();
+        let request = Request::builder()
+            .method(Method::POST)
+            .uri("/contents")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "path": path }).to_string()))
+            .unwrap();
+        let (status, _, _) = send_request(&config, request).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn follows_file_within_line_and_connection_caps() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "one\ntwo\n").unwrap();
+        let mut config = test_config();
+        config.file.max_follow_lines = 5;
+        config.file.max_followers = 1;
+        let app = linux_file_action_api(&config);
+        let get = |lines: usize| Request::builder()
+            .uri(format!("/follow?path={}&lines={}", path.display(), lines))
+            .body(Body::empty())
+            .unwrap();
+
+        let response = app.clone().oneshot(get(6)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = app.clone().oneshot(get(1)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        let frame = body.frame().await.unwrap().unwrap().into_data().unwrap();
+        assert_eq!(&frame[..], b"event: line\ndata: two\n\n");
+
+        fs::write(&path, "one\ntwo\nthree\n").unwrap();
+        let frame = tokio::time::timeout(Duration::from_secs(5), body.frame()).await.unwrap().unwrap().unwrap();
+        assert_eq!(&frame.into_data().unwrap()[..], b"event: line\ndata: three\n\n");
+
+        // 连接数达到上限时拒绝新的连接, 断开后释放
+        let response = app.clone().oneshot(get(1)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::SERVICE_UNAVAILABLE);
+        drop(body);
+        let mut status = StatusCode::SERVICE_UNAVAILABLE;
+        for _ in 0..30 {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            status = app.clone().oneshot(get(1)).await.unwrap().status();
+            if status == StatusCode::OK {
+                break;
+            }
+        }
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn reads_binary_as_base64_and_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("mixed.bin");
+        fs::write(&path, b"one\r\n\xff\xfe\nthree\n").unwrap();
+
+        let uri = format!("/read?path={}&format=base64", path.display());
+        let (status, body) = send(Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["data"], "b25lDQr//gp0aHJlZQo=");
+        assert_eq!(body["length"], 14);
+
+        let uri = format!("/read?path={}&format=lines&offset=1&limit=1", path.display());
+        let (status, body) = send(Method::GET, &uri, None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["lines"], json!(["\u{fffd}\u{fffd}"]));
+        assert_eq!(body["next_offset"], 2);
+
+        let uri = format!("/read?path={}&format=lines&offset=2", path.display());
+        let (_, body) = send(Method::GET, &uri, None).await;
+        assert_eq!(body["lines"], json!(["three"]));
+        assert_eq!(body["next_offset"], Value::Null);
+    }
+
+    #[tokio::test]
+    async fn creates_and_deletes_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("created.txt");
+
+        let (status, _) = send(Method::PUT, "/create", Some(json!({ "path": path }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(path.is_file());
+
+        let (status, body) = send(Method::PUT, "/delete", Some(json!({ "path": path }))).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["path"], json!(path));
+        assert!(!path.exists());
+
+        let (status, body) = send(Method::PUT, "/delete", Some(json!({ "path": path }))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["error"]["status"], 404);
+
+        // 指向不存在路径的符号链接不会被跟随, 不会在写入目录之外创建文件
+        let outside = tempfile::tempdir().unwrap();
+        let link = dir.path().join("dangling");
+        std::os::unix::fs::symlink(outside.path().join("target"), &link).unwrap();
+        let mut config = AgentConfig::default();
+        config.file.write_roots = vec![dir.path().to_path_buf()];
+        let request = Request::builder()
+            .method(Method::PUT)
+            .uri("/create")
+            .header("content-type", "application/json")
+            .body(Body::from(json!({ "path": link }).to_string()))
+            .unwrap();
+        assert_eq!(send_request(&config, request).await.0, StatusCode::FORBIDDEN);
+        assert!(!outside.path().join("target").exists());
+    }
+
+    #[tokio::test]
+    async fn uploads_in_chunks_and_verifies_sha256() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.conf");
+        let base = format!("/upload?path={}", path.display());
+        let upload = |query: String, body: &'static str| async move {
+            let request = Request::builder().method(Method::PUT).uri(query).body(Body::from(body)).unwrap();
+            let (status, _, bytes) = send_request(&test_config(), request).await;
+            (status, serde_json::from_slice::<Value>(&bytes).unwrap())
+        };
+
+        let (status, body) = upload(format!("{}&offset=0", base), "hello ").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["received"], 6);
+        assert!(!path.exists());
+
+        let (status, _) = upload(format!("{}&offset=3", base), "world").await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        let (_, body) = send(Method::GET, &base, None).await;
+        assert_eq!(body["received"], 6);
+
+        let (status, _) = upload(format!("{}&offset=6&complete=true&sha256=00", base), "world").await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert!(!path.exists());
+
+        let (_, _) = upload(format!("{}&offset=0", base), "hello ").await;
+        let sha256 = "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9";
+        let uri = format!("{}&offset=6&complete=true&sha256={}&mode=0640", base, sha256);
+        let (status, body) = upload(uri, "world").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["complete"], true);
+        assert_eq!(body["file"]["size"], 11);
+        assert_eq!(body["file"]["mode"], "0640");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "hello world");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn upload_refuses_planted_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let victim = dir.path().join("passwd");
+        fs::write(&victim, "root:x:0:0").unwrap();
+        let upload = |path: std::path::PathBuf| {
+            let uri = format!("/upload?path={}&offset=0", path.display());
+            let request = Request::builder().method(Method::PUT).uri(uri).body(Body::from("evil")).unwrap();
+            async move { send_request(&test_config(), request).await.0 }
+        };
+
+        // 可预测的临时文件名被提前放置了符号链接或硬链接时拒绝续传, 不会写入链接指向的文件
+        std::os::unix::fs::symlink(&victim, dir.path().join(".a.upload")).unwrap();
+        assert_eq!(upload(dir.path().join("a")).await, StatusCode::FORBIDDEN);
+        fs::hard_link(&victim, dir.path().join(".b.upload")).unwrap();
+        assert_eq!(upload(dir.path().join("b")).await, StatusCode::FORBIDDEN);
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "root:x:0:0");
+
+        assert_eq!(upload(dir.path().join("c")).await, StatusCode::OK);
+        let mode = fs::metadata(dir.path().join(".c.upload")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[tokio::test]
+    async fn downloads_file_with_etag_and_directory_as_tar_gz() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("core dump.bin");
+        fs::write(&path, "hello world").unwrap();
+        let config = test_config();
+        let get = |uri: &str| Request::builder().uri(uri).body(Body::empty()).unwrap();
+
+        let uri = format!("/download?path={}&sha256=true", path.display()).replace(' ', "%20");
+        // SHA-256 在文件内容之后作为 trailer 返回
+        let response = linux_file_action_api(&config).oneshot(get(&uri)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers().clone();
+        assert_eq!(headers[header::TRAILER], "x-checksum-sha256");
+        assert_eq!(headers[header::CONTENT_DISPOSITION], "attachment; filename=\"core dump.bin\"; filename*=UTF-8''core%20dump.bin");
+        let collected = response.into_body().collect().await.unwrap();
+        let trailers = collected.trailers().cloned().unwrap();
+        assert_eq!(trailers["x-checksum-sha256"], "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9");
+        assert_eq!(&collected.to_bytes()[..], b"hello world");
+
+        let uri = format!("/download?path={}", path.display()).replace(' ', "%20");
+        let (status, headers, body) = send_request(&config, get(&uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(&body[..], b"hello world");
+        assert_eq!(headers[header::CONTENT_LENGTH], "11");
+
+        let etag = headers[header::ETAG].clone();
+        let request = Request::builder().uri(&uri).header(header::IF_NONE_MATCH, etag).body(Body::empty()).unwrap();
+        let (status, _, body) = send_request(&config, request).await;
+        assert_eq!(status, StatusCode::NOT_MODIFIED);
+        assert!(body.is_empty());
+
+        let uri = format!("/download?path={}", dir.path().display());
+        let (status, headers, body) = send_request(&config, get(&uri)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[header::CONTENT_TYPE], "application/gzip");
+        let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(&body[..]));
+        let names = archive.entries().unwrap()
+            .map(|entry| entry.unwrap().path().unwrap().to_string_lossy().into_owned())
+            .collect::<Vec<_>>();
+        let root = dir.path().file_name().unwrap().to_string_lossy();
+        assert!(names.contains(&format!("{}/core dump.bin", root)));
+    }
+
+    #[tokio::test]
+    async fn enforces_path_policy() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (readonly, writable, secret) = (root.join("ro"), root.join("rw"), root.join("rw/secret"));
+        fs::create_dir_all(&readonly).unwrap();
+        fs::create_dir_all(&secret).unwrap();
+        fs::write(readonly.join("a.txt"), "a").unwrap();
+        fs::write(secret.join("key"), "key").unwrap();
+        std::os::unix::fs::symlink(secret.join("key"), writable.join("link")).unwrap();
+
+        let mut config = test_config();
+        config.file.read_roots = vec![readonly.clone()];
+        config.file.write_roots = vec![writable.clone()];
+        config.file.denied_paths = vec![format!("{}/*/secret", root.display())];
+        let send = |method: Method, uri: String, body: Value| {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .header("content-type", "application/json")
+                .body(Body::from(body.to_string()))
+                .unwrap();
+            let config = config.clone();
+            async move { send_request(&config, request).await.0 }
+        };
+
+        let read = |path: &std::path::Path| format!("/stat?path={}", path.display());
+        assert_eq!(send(Method::GET, read(&readonly.join("a.txt")), Value::Null).await, StatusCode::OK);
+        assert_eq!(send(Method::GET, read(&root.join("rw/../ro/a.txt")), Value::Null).await, StatusCode::OK);
+        assert_eq!(send(Method::GET, read(&secret.join("key")), Value::Null).await, StatusCode::FORBIDDEN);
+        assert_eq!(send(Method::GET, read(&writable.join("link")), Value::Null).await, StatusCode::FORBIDDEN);
+        assert_eq!(send(Method::GET, "/stat?path=/etc/hostname".to_string(), Value::Null).await, StatusCode::FORBIDDEN);
+        assert_eq!(send(Method::GET, "/stat?path=ro/a.txt".to_string(), Value::Null).await, StatusCode::BAD_REQUEST);
+
+        let create = |path: std::path::PathBuf| json!({ "path": path });
+        assert_eq!(send(Method::PUT, "/create".to_string(), create(readonly.join("b.txt"))).await, StatusCode::FORBIDDEN);
+        assert_eq!(send(Method::PUT, "/create".to_string(), create(writable.join("b.txt"))).await, StatusCode::OK);
+        let copy = json!({ "path": readonly.join("a.txt"), "dest": secret.join("a.txt") });
+        assert_eq!(send(Method::PUT, "/copy-file".to_string(), copy).await, StatusCode::FORBIDDEN);
+        let copy = json!({ "path": readonly.join("a.txt"), "dest": writable.join("c.txt") });
+        assert_eq!(send(Method::PUT, "/copy-file".to_string(), copy.clone()).await, StatusCode::OK);
+        assert_eq!(send(Method::PUT, "/copy-file".to_string(), copy).await, StatusCode::CONFLICT);
+        let copy = json!({ "path": readonly.join("a.txt"), "dest": writable.join("c.txt"), "if_exists": "skip" });
+        assert_eq!(send(Method::PUT, "/copy-file".to_string(), copy).await, StatusCode::OK);
+        let mv = json!({ "path": writable.join("c.txt"), "dest": writable.join("d.txt") });
+        assert_eq!(send(Method::PUT, "/move-file".to_string(), mv).await, StatusCode::OK);
+
+        let request = Request::builder().uri(format!("/list?path={}", writable.display())).body(Body::empty()).unwrap();
+        let (status, _, body) = send_request(&config, request).await;
+        assert_eq!(status, StatusCode::OK);
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        let names = body["entries"].as_array().unwrap().iter().map(|entry| entry["name"].clone()).collect::<Vec<_>>();
+        assert_eq!(names, vec![json!("b.txt"), json!("d.txt"), json!("link")]);
+    }
+
+    #[tokio::test]
+    async fn list_skips_denied_subtrees_and_caps_the_walk() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("home/.ssh")).unwrap();
+        fs::write(root.join("home/.ssh/id_rsa"), "x".repeat(100)).unwrap();
+        fs::write(root.join("home/notes.txt"), "12345").unwrap();
+        for i in 0..5 {
+            fs::write(root.join(format!("f{}", i)), "").unwrap();
+        }
+
+        let mut config = test_config();
+        config.file.denied_paths = vec![format!("{}/*/.ssh", root.display())];
+        config.file.max_list_depth = 3;
+        config.file.max_list_entries = 100;
+        let list = |config: AgentConfig, query: &str| {
+            let request = Request::builder().uri(format!("/list?path={}&{}", root.display(), query)).body(Body::empty()).unwrap();
+            async move {
+                let (status, _, body) = send_request(&config, request).await;
+                (status, serde_json::from_slice::<Value>(&body).unwrap())
+            }
+        };
+
+        // 被禁止的目录既不出现在结果中, 也不计入上层目录的大小
+        let (status, body) = list(config.clone(), "depth=3&all=true&du=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["truncated"], false);
+        let entries = body["entries"].as_array().unwrap();
+        assert!(entries.iter().all(|entry| !entry["path"].as_str().unwrap().contains(".ssh")));
+        let home = entries.iter().find(|entry| entry["name"] == "home").unwrap();
+        assert_eq!(home["dir_size"], 5);
+
+        let (status, _) = list(config.clone(), "depth=4").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        config.file.max_list_entries = 3;
+        let (status, body) = list(config, "depth=3").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["truncated"], true);
+        assert_eq!(body["total"], 3);
+    }
+}
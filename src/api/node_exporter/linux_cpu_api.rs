@@ -1,7 +1,66 @@
-use axum::Router;
+use std::sync::Arc;
+
+use axum::{Json, Router};
+use axum::extract::State;
 use axum::routing::get;
+use serde::Serialize;
+
+use crate::node_exporter::cpu_utils::cpuinfo::CpuTopology;
+use crate::node_exporter::cpu_utils::cpuloadavg::get_cpu_loadavg;
+use crate::node_exporter::cpu_utils::cpustat::{CpuSampler, CpuUsage};
 
-fn cpu_stat_api() -> Router {
+pub fn cpu_stat_api() -> Router {
     Router::new()
-        .route("/cpu", get("1"))
+        .route("/usage", get(cpu_usage_handler))
+        .route("/cores", get(cpu_cores_handler))
+        .route("/loadavg", get(cpu_loadavg_handler))
+        .route("/info", get(cpu_info_handler))
+        .with_state(CpuSampler::spawn())
+}
+
+#[derive(Serialize)]
+struct LoadAvg {
+    one: f32,
+    five: f32,
+    fifteen: f32,
+}
+
+async fn cpu_usage_handler(State(sampler): State<Arc<CpuSampler>>) -> Json<Option<CpuUsage>> {
+    match sampler.usage() {
+        Ok(usage) => Json(Some(usage.total)),
+        Err(e) => {
+            eprintln!("Failed to retrieve cpu usage: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn cpu_cores_handler(State(sampler): State<Arc<CpuSampler>>) -> Json<Option<Vec<CpuUsage>>> {
+    match sampler.usage() {
+        Ok(usage) => Json(Some(usage.cores)),
+        Err(e) => {
+            eprintln!("Failed to retrieve cpu usage: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn cpu_loadavg_handler() -> Json<Option<LoadAvg>> {
+    match get_cpu_loadavg() {
+        Ok((one, five, fifteen)) => Json(Some(LoadAvg { one, five, fifteen })),
+        Err(e) => {
+            eprintln!("Failed to retrieve cpu loadavg: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn cpu_info_handler() -> Json<Option<CpuTopology>> {
+    match CpuTopology::init() {
+        Ok(topology) => Json(Some(topology)),
+        Err(e) => {
+            eprintln!("Failed to retrieve cpu info: {}", e);
+            Json(None)
+        }
+    }
 }
\ No newline at end of file
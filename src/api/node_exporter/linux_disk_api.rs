@@ -0,0 +1,51 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::{Json, Router};
+use axum::extract::{Query, State};
+use axum::routing::get;
+use serde::Deserialize;
+
+use crate::node_exporter::disk_utils::diskinfo::{DiskIo, DiskSampler, DiskStat, MountUsage};
+
+// 单次请求等待 statvfs 的最长时间
+const STATVFS_TIMEOUT: Duration = Duration::from_secs(2);
+
+pub fn disk_stats_api() -> Router {
+    Router::new()
+        .route("/mounts", get(mounts_handler))
+        .route("/stats", get(disk_stats_handler))
+        .route("/io", get(disk_io_handler))
+        .with_state(DiskSampler::spawn())
+}
+
+#[derive(Deserialize)]
+struct MountsQuery {
+    // 是否包含 proc、sysfs 等虚拟文件系统
+    #[serde(default)]
+    all: bool,
+}
+
+async fn mounts_handler(Query(query): Query<MountsQuery>) -> Json<Option<Vec<MountUsage>>> {
+    match MountUsage::filesystems_within(query.all, STATVFS_TIMEOUT).await {
+        Ok(mounts) => Json(Some(mounts)),
+        Err(e) => {
+            eprintln!("Failed to retrieve mounts: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn disk_stats_handler() -> Json<Option<Vec<DiskStat>>> {
+    match DiskStat::devices() {
+        Ok(stats) => Json(Some(stats)),
+        Err(e) => {
+            eprintln!("Failed to retrieve disk stats: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn disk_io_handler(State(sampler): State<Arc<DiskSampler>>) -> Json<Vec<DiskIo>> {
+    Json(sampler.io())
+}
\ No newline at end of file
@@ -1,6 +1,7 @@
 use std::convert::Infallible;
 use std::error::Error;
 use axum::{Json, Router, routing::{get, post}};
+use serde::Serialize;
 
 use crate::node_exporter::mem_utils::meminfo::MemInfo;
 
@@ -12,6 +13,7 @@ pub fn memory_stats_api() -> Router {
         .route("/clearCache", post("TODO"))
 }
 
+#[derive(Serialize)]
 struct MemoryStats {
     meminfo: MemInfo,
     used: f64
@@ -0,0 +1,54 @@
+use std::sync::Arc;
+
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::IntoResponse;
+use axum::Router;
+use axum::routing::get;
+
+use crate::config::agent_config::AgentConfig;
+use crate::node_exporter::collector::{Registry, TEXT_FORMAT_CONTENT_TYPE};
+use crate::node_exporter::cpu_utils::cpuloadavg::LoadAvgCollector;
+use crate::node_exporter::cpu_utils::cpustat::CpuCollector;
+use crate::node_exporter::disk_utils::diskinfo::DiskCollector;
+use crate::node_exporter::file_utils::fileinfo::DirSizeCollector;
+use crate::node_exporter::mem_utils::meminfo::MemInfoCollector;
+use crate::node_exporter::net_utils::netinfo::NetCollector;
+use crate::node_exporter::net_utils::socket::SocketCollector;
+use crate::node_exporter::proc_utils::process::ProcessCollector;
+
+pub fn metrics_api(config: &AgentConfig) -> Router {
+    // 注册所有 node_exporter 数据源
+    let registry = Registry::new()
+        .register(MemInfoCollector)
+        .register(LoadAvgCollector)
+        .register(CpuCollector)
+        .register(DiskCollector)
+        .register(NetCollector)
+        .register(SocketCollector)
+        .register(ProcessCollector)
+        .register(DirSizeCollector::new(config.metrics.dir_size_paths.clone()));
+
+    Router::new()
+        .route("/metrics", get(metrics_handler))
+        .with_state(Arc::new(registry))
+}
+
+async fn metrics_handler(State(registry): State<Arc<Registry>>) -> impl IntoResponse {
+    // 采集过程会读取大量 /proc 文件和遍历目录, 放到阻塞线程池中执行
+    match tokio::task::spawn_blocking(move || registry.gather()).await {
+        Ok(body) => (
+            StatusCode::OK,
+            [(header::CONTENT_TYPE, TEXT_FORMAT_CONTENT_TYPE)],
+            body,
+        ),
+        Err(e) => {
+            eprintln!("Failed to gather metrics: {}", e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                [(header::CONTENT_TYPE, TEXT_FORMAT_CONTENT_TYPE)],
+                String::new(),
+            )
+        }
+    }
+}
\ No newline at end of file
@@ -0,0 +1,133 @@
+use std::io;
+use std::sync::Arc;
+
+use axum::{Json, Router};
+use axum::extract::{Query, State};
+use axum::routing::get;
+use serde::Deserialize;
+
+use crate::node_exporter::net_utils::netinfo::{NetInterface, NetRate, NetSampler};
+use crate::node_exporter::net_utils::socket::{Socket, SocketSummary};
+
+pub fn network_stats_api() -> Router {
+    Router::new()
+        .route("/interfaces", get(interfaces_handler))
+        .route("/rates", get(rates_handler))
+        .route("/sockets", get(sockets_handler))
+        .route("/sockets/summary", get(sockets_summary_handler))
+        .with_state(NetSampler::spawn())
+}
+
+async fn interfaces_handler() -> Json<Option<Vec<NetInterface>>> {
+    match NetInterface::interfaces() {
+        Ok(interfaces) => Json(Some(interfaces)),
+        Err(e) => {
+            eprintln!("Failed to retrieve network interfaces: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn rates_handler(State(sampler): State<Arc<NetSampler>>) -> Json<Vec<NetRate>> {
+    Json(sampler.rates())
+}
+
+#[derive(Deserialize)]
+struct SocketQuery {
+    // 精确匹配协议, 多个协议用逗号分隔, 如 tcp,tcp6
+    protocol: Option<String>,
+    // 连接状态, 如 ESTABLISHED, 不区分大小写
+    state: Option<String>,
+    // 本地或远端端口
+    port: Option<u16>,
+    local_port: Option<u16>,
+    remote_port: Option<u16>,
+    pid: Option<u32>,
+    uid: Option<u32>,
+}
+
+impl SocketQuery {
+    fn matches(&self, socket: &Socket) -> bool {
+        self.protocol.as_ref().is_none_or(|protocol| protocol.split(',').any(|protocol| protocol.trim() == socket.protocol))
+            && self.state.as_ref().is_none_or(|state| socket.state.eq_ignore_ascii_case(state))
+            && self.port.is_none_or(|port| socket.local_port == port || socket.remote_port == port)
+            && self.local_port.is_none_or(|port| socket.local_port == port)
+            && self.remote_port.is_none_or(|port| socket.remote_port == port)
+            && self.pid.is_none_or(|pid| socket.pid == Some(pid))
+            && self.uid.is_none_or(|uid| socket.uid == uid)
+    }
+}
+
+// read_sockets 在阻塞线程池中读取 socket 表, 关联进程需要遍历所有进程的 fd
+async fn read_sockets(with_pid: bool) -> Result<Vec<Socket>, io::Error> {
+    tokio::task::spawn_blocking(move || Socket::sockets(with_pid))
+        .await
+        .map_err(io::Error::other)?
+}
+
+async fn sockets_handler(Query(query): Query<SocketQuery>) -> Json<Option<Vec<Socket>>> {
+    match read_sockets(true).await {
+        Ok(sockets) => Json(Some(sockets.into_iter().filter(|socket| query.matches(socket)).collect())),
+        Err(e) => {
+            eprintln!("Failed to retrieve sockets: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn sockets_summary_handler() -> Json<Option<SocketSummary>> {
+    match read_sockets(false).await {
+        Ok(sockets) => Json(Some(SocketSummary::from(&sockets))),
+        Err(e) => {
+            eprintln!("Failed to retrieve sockets: {}", e);
+            Json(None)
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+
+    fn socket(protocol: &'static str) -> Socket {
+        Socket {
+            protocol,
+            local_addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            local_port: 22,
+            remote_addr: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            remote_port: 0,
+            state: "LISTEN",
+            tx_queue: 0,
+            rx_queue: 0,
+            uid: 0,
+            inode: 1,
+            pid: None,
+        }
+    }
+
+    fn query(protocol: &str) -> SocketQuery {
+        SocketQuery {
+            protocol: Some(protocol.to_string()),
+            state: None,
+            port: None,
+            local_port: None,
+            remote_port: None,
+            pid: None,
+            uid: None,
+        }
+    }
+
+    #[test]
+    fn protocol_filter_is_exact() {
+        assert!(query("tcp").matches(&socket("tcp")));
+        assert!(!query("tcp").matches(&socket("tcp6")));
+        assert!(!query("t").matches(&socket("tcp")));
+        assert!(query("tcp6").matches(&socket("tcp6")));
+
+        let both = query("tcp, tcp6");
+        assert!(both.matches(&socket("tcp")));
+        assert!(both.matches(&socket("tcp6")));
+        assert!(!both.matches(&socket("udp")));
+    }
+}
@@ -1,16 +1,189 @@
-use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
 use axum::{Json, Router};
-use axum::routing::get;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, post};
+use serde::{Deserialize, Serialize};
+
+use crate::api::api_error::ApiError;
+use crate::hand::node::process_control::{kill_tree, KillTreeResult, parse_signal, renice, send_signal};
+
 use crate::node_exporter::proc_utils::process::ProcessStatus;
+use crate::node_exporter::proc_utils::procdetail::ProcessDetail;
+use crate::node_exporter::proc_utils::proctop::{ProcessSampler, ProcessUsage, SortBy};
+use crate::node_exporter::proc_utils::proctree::{ProcessNode, render_tree};
 
 
-pub async fn process_api() -> Router {
+pub fn process_api() -> Router {
     Router::new()
         .route("/proc-status", get(processes_handler))
+        .route("/top", get(top_handler))
+        .route("/tree", get(tree_handler))
+        .route("/:pid", get(detail_handler))
+        .route("/:pid/signal", post(signal_handler))
+        .route("/:pid/renice", post(renice_handler))
+        .route("/:pid/kill-tree", post(kill_tree_handler))
+        .with_state(ProcessSampler::spawn())
+}
+
+#[derive(Deserialize)]
+struct TopQuery {
+    // 排序依据, cpu 或 mem
+    #[serde(default)]
+    by: SortBy,
+    // 返回的进程数量
+    #[serde(default = "default_top_n")]
+    n: usize,
+}
+
+fn default_top_n() -> usize {
+    20
+}
+
+#[derive(Deserialize)]
+struct TreeQuery {
+    // 只返回以该进程为根的子树
+    pid: Option<u32>,
+    // json 或 text
+    #[serde(default)]
+    format: TreeFormat,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "lowercase")]
+enum TreeFormat {
+    #[default]
+    Json,
+    Text,
+}
+
+#[derive(Deserialize)]
+struct DetailQuery {
+    // 是否返回环境变量的值, 默认只返回变量名
+    #[serde(default)]
+    show_environ: bool,
+}
+
+#[derive(Deserialize)]
+struct SignalRequest {
+    // 信号名或编号, 如 SIGTERM、term、15
+    signal: String,
+    // 是否发送给进程所在的整个进程组
+    #[serde(default)]
+    group: bool,
+}
+
+#[derive(Serialize)]
+struct SignalResponse {
+    pid: u32,
+    signal: String,
+    group: bool,
+}
+
+#[derive(Deserialize)]
+struct ReniceRequest {
+    nice: i32,
+}
+
+#[derive(Serialize)]
+struct ReniceResponse {
+    pid: u32,
+    nice: i32,
+    // 实际修改了的线程
+    threads: Vec<u32>,
+}
+
+#[derive(Deserialize)]
+struct KillTreeRequest {
+    // 发送 SIGTERM 后等待的秒数, 超时后发送 SIGKILL, 最大为 300
+    #[serde(default = "default_grace_secs")]
+    grace_secs: u64,
+}
+
+fn default_grace_secs() -> u64 {
+    5
+}
+
+async fn processes_handler() -> Json<Option<Vec<ProcessStatus>>> {
+    match ProcessStatus::processes() {
+        Ok(processes) => Json(Some(processes)),
+        Err(e) => {
+            eprintln!("Failed to retrieve processes: {}", e);
+            Json(None)
+        }
+    }
+}
+
+async fn top_handler(
+    State(sampler): State<Arc<ProcessSampler>>,
+    Query(query): Query<TopQuery>,
+) -> Json<Vec<ProcessUsage>> {
+    Json(sampler.top(query.by, query.n))
+}
+
+async fn tree_handler(
+    State(sampler): State<Arc<ProcessSampler>>,
+    Query(query): Query<TreeQuery>,
+) -> Result<Response, ApiError> {
+    let tree = ProcessNode::build(&sampler.usage(), query.pid);
+    if let Some(pid) = query.pid.filter(|_| tree.is_empty()) {
+        return Err(ApiError::new(StatusCode::NOT_FOUND, format!("process {} not found", pid)));
+    }
+
+    Ok(match query.format {
+        TreeFormat::Json => Json(tree).into_response(),
+        TreeFormat::Text => (
+            [(header::CONTENT_TYPE, "text/plain; charset=utf-8")],
+            render_tree(&tree),
+        ).into_response(),
+    })
+}
+
+async fn detail_handler(
+    Path(pid): Path<u32>,
+    Query(query): Query<DetailQuery>,
+) -> Result<Json<ProcessDetail>, ApiError> {
+    let detail = ProcessDetail::of(pid, query.show_environ)?;
+
+    Ok(Json(detail))
+}
+
+async fn signal_handler(
+    Path(pid): Path<u32>,
+    Json(request): Json<SignalRequest>,
+) -> Result<Json<SignalResponse>, ApiError> {
+    let signal = parse_signal(&request.signal)?;
+    send_signal(pid, signal, request.group)?;
+
+    Ok(Json(SignalResponse {
+        pid,
+        signal: signal.as_str().to_string(),
+        group: request.group,
+    }))
+}
+
+async fn renice_handler(
+    Path(pid): Path<u32>,
+    Json(request): Json<ReniceRequest>,
+) -> Result<Json<ReniceResponse>, ApiError> {
+    let threads = renice(pid, request.nice)?;
+
+    Ok(Json(ReniceResponse {
+        pid,
+        nice: request.nice,
+        threads,
+    }))
 }
 
-async fn processes_handler() -> Result<Json<Vec<ProcessStatus>>, axum::Error> {
-    let processes = ProcessStatus::processes()?;
+async fn kill_tree_handler(
+    Path(pid): Path<u32>,
+    request: Option<Json<KillTreeRequest>>,
+) -> Result<Json<KillTreeResult>, ApiError> {
+    let grace_secs = request.map_or(default_grace_secs(), |Json(request)| request.grace_secs);
+    let result = kill_tree(pid, Duration::from_secs(grace_secs)).await?;
 
-    Ok(Json(processes))
+    Ok(Json(result))
 }
\ No newline at end of file
@@ -0,0 +1,191 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use axum::http::StatusCode;
+use glob::{MatchOptions, Pattern};
+
+use crate::api::api_error::ApiError;
+use crate::config::agent_config::FileConfig;
+
+// * 和 ? 不匹配 /, 与 shell 相同
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+// PathPolicy 限制文件接口可以访问的路径, 所有文件接口在调用 file_operation 之前都要经过 check
+#[derive(Debug, Clone)]
+pub struct PathPolicy {
+    read_roots: Vec<PathBuf>,
+    write_roots: Vec<PathBuf>,
+    denied: Vec<Pattern>,
+}
+
+impl PathPolicy {
+    // new 根据配置创建, 根目录本身是符号链接时按链接指向的目录比较
+    pub fn new(config: &FileConfig) -> Self {
+        let canonical = |roots: &[PathBuf]| roots.iter()
+            .map(|root| fs::canonicalize(root).unwrap_or_else(|_| root.clone()))
+            .collect();
+
+        Self {
+            read_roots: canonical(&config.read_roots),
+            write_roots: canonical(&config.write_roots),
+            // validate 已经检查过通配符的格式
+            denied: config.denied_patterns().filter_map(|pattern| Pattern::new(pattern).ok()).collect(),
+        }
+    }
+
+    // check 解析路径中的符号链接后检查是否允许访问, 返回解析后的路径, 之后的操作都应使用它。
+    // 最后一级不跟随符号链接, 这样 stat、删除等操作的仍是链接本身; 但链接指向的文件也必须允许访问
+    pub fn check(&self, path: &Path, access: Access) -> Result<PathBuf, ApiError> {
+        if !path.is_absolute() {
+            return Err(ApiError::new(StatusCode::BAD_REQUEST, format!("{} is not an absolute path", path.display())));
+        }
+
+        let resolved = resolve(path)?;
+        self.allowed(&resolved, access)?;
+        match fs::canonicalize(&resolved) {
+            Ok(target) if target != resolved => self.allowed(&target, access)?,
+            Ok(_) => {}
+            // 指向不存在的路径的符号链接无法检查目标, 创建文件时会跟随它写到允许的目录之外
+            Err(_) if fs::symlink_metadata(&resolved).is_ok_and(|metadata| metadata.file_type().is_symlink()) => {
+                return Err(ApiError::new(
+                    StatusCode::FORBIDDEN,
+                    format!("{} is a symlink to a path that cannot be resolved", resolved.display()),
+                ));
+            }
+            Err(_) => {}
+        }
+        Ok(resolved)
+    }
+
+    // is_denied 判断路径或它的上级目录是否在禁止列表中, 用于过滤目录列表、打包和解包中的文件
+    pub fn is_denied(&self, path: &Path) -> bool {
+        path.ancestors().any(|ancestor| self.denied.iter().any(|pattern| pattern.matches_path_with(ancestor, MATCH_OPTIONS)))
+    }
+
+    fn allowed(&self, path: &Path, access: Access) -> Result<(), ApiError> {
+        let mut roots = self.write_roots.iter()
+            .chain(self.read_roots.iter().filter(|_| access == Access::Read));
+        if !roots.any(|root| path.starts_with(root)) {
+            let kind = if access == Access::Read { "readable" } else { "writable" };
+            return Err(ApiError::new(StatusCode::FORBIDDEN, format!("{} is outside of the {} roots", path.display(), kind)));
+        }
+        if self.is_denied(path) {
+            return Err(ApiError::new(StatusCode::FORBIDDEN, format!("Access to {} is denied", path.display())));
+        }
+        Ok(())
+    }
+}
+
+// resolve 解析上级目录中的符号链接和 .., 保留最后一级
+fn resolve(path: &Path) -> io::Result<PathBuf> {
+    match (path.parent(), path.file_name()) {
+        (Some(parent), Some(name)) => Ok(canonicalize_existing(parent)?.join(name)),
+        // / 或以 .. 结尾的路径
+        _ => fs::canonicalize(path),
+    }
+}
+
+// canonicalize_existing 解析路径中已经存在的部分, 不存在的部分原样拼接在后面
+fn canonicalize_existing(path: &Path) -> io::Result<PathBuf> {
+    match fs::canonicalize(path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => match (path.parent(), path.file_name()) {
+            (Some(parent), Some(name)) => Ok(canonicalize_existing(parent)?.join(name)),
+            _ => Err(e),
+        },
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::symlink;
+
+    use axum::http::StatusCode;
+
+    use super::*;
+
+    fn policy(read_roots: &[&Path], write_roots: &[&Path], denied_paths: &[String]) -> PathPolicy {
+        PathPolicy::new(&FileConfig {
+            read_roots: read_roots.iter().map(|root| root.to_path_buf()).collect(),
+            write_roots: write_roots.iter().map(|root| root.to_path_buf()).collect(),
+            denied_paths: denied_paths.to_vec(),
+            ..FileConfig::default()
+        })
+    }
+
+    fn status(result: Result<PathBuf, ApiError>) -> StatusCode {
+        match result {
+            Ok(_) => StatusCode::OK,
+            Err(e) => e.status,
+        }
+    }
+
+    #[test]
+    fn separates_read_and_write_roots() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (readonly, writable) = (root.join("ro"), root.join("rw"));
+        fs::create_dir_all(&readonly).unwrap();
+        fs::create_dir_all(&writable).unwrap();
+        let policy = policy(&[&readonly], &[&writable], &[]);
+
+        assert_eq!(status(policy.check(&readonly.join("a"), Access::Read)), StatusCode::OK);
+        assert_eq!(status(policy.check(&readonly.join("a"), Access::Write)), StatusCode::FORBIDDEN);
+        assert_eq!(status(policy.check(&writable.join("a"), Access::Write)), StatusCode::OK);
+        assert_eq!(status(policy.check(&writable.join("a"), Access::Read)), StatusCode::OK);
+        assert_eq!(status(policy.check(&root.join("other"), Access::Read)), StatusCode::FORBIDDEN);
+        assert_eq!(status(policy.check(&writable.join("../ro/a"), Access::Write)), StatusCode::FORBIDDEN);
+        assert_eq!(status(policy.check(Path::new("rw/a"), Access::Read)), StatusCode::BAD_REQUEST);
+    }
+
+    #[test]
+    fn denies_globs_and_their_contents() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        fs::create_dir_all(root.join("app/secret")).unwrap();
+        let policy = policy(&[], &[&root], &[format!("{}/*/secret", root.display())]);
+
+        assert_eq!(status(policy.check(&root.join("app/secret"), Access::Read)), StatusCode::FORBIDDEN);
+        assert_eq!(status(policy.check(&root.join("app/secret/key"), Access::Read)), StatusCode::FORBIDDEN);
+        // * 不匹配 /
+        assert_eq!(status(policy.check(&root.join("app/sub/secret"), Access::Read)), StatusCode::OK);
+        assert!(policy.is_denied(&root.join("app/secret/a/b")));
+        // 配置的禁止路径不会替换内置的列表
+        assert!(policy.is_denied(Path::new("/etc/shadow")));
+    }
+
+    #[test]
+    fn checks_symlink_targets() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let (writable, outside) = (root.join("rw"), root.join("outside"));
+        fs::create_dir_all(&writable).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        fs::write(outside.join("file"), "").unwrap();
+        symlink(outside.join("file"), writable.join("escape")).unwrap();
+        symlink(&outside, writable.join("dir")).unwrap();
+        symlink(outside.join("missing"), writable.join("dangling")).unwrap();
+        symlink(writable.join("a"), writable.join("inside")).unwrap();
+        fs::write(writable.join("a"), "").unwrap();
+        let policy = policy(&[], &[&writable], &[]);
+
+        // 最后一级是指向允许范围之外的符号链接, 或者上级目录经过这样的链接
+        assert_eq!(status(policy.check(&writable.join("escape"), Access::Write)), StatusCode::FORBIDDEN);
+        assert_eq!(status(policy.check(&writable.join("dir/file"), Access::Read)), StatusCode::FORBIDDEN);
+        // 指向不存在路径的符号链接无法检查目标
+        assert_eq!(status(policy.check(&writable.join("dangling"), Access::Write)), StatusCode::FORBIDDEN);
+        assert_eq!(policy.check(&writable.join("inside"), Access::Write).unwrap(), writable.join("inside"));
+    }
+}
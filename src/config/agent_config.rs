@@ -0,0 +1,564 @@
+//! wiseye_agent 的配置文件默认位于 config/wiseye_agent.toml, 内容大致如下:
+//! [wiseye_agent]
+//! client_addr = "127.0.0.1"
+//! client_port = "4200"
+//!
+//! [mysql]
+//! dsn = "mysql://localhost/database"
+//!
+//! [metrics]
+//! dir_size_paths = ["/var/log"]
+//!
+//! [file]
+//! max_read_size = 67108864
+//! read_roots = ["/"]
+//! write_roots = ["/tmp", "/data"]
+//! denied_paths = ["/data/*/secret"]
+//! max_follow_lines = 1000
+//! max_followers = 16
+//! max_list_depth = 8
+//! max_list_entries = 100000
+//!
+//! write_roots 默认为空, 即不允许任何写操作; denied_paths 是在内置禁止列表 BUILTIN_DENIED_PATHS 之外追加的路径。
+//!
+//! 加载顺序: 命令行 --config 指定的路径 > 环境变量 WISEYE_AGENT_CONFIG > 默认路径,
+//! 读取文件后再用 WISEYE_AGENT_* 环境变量覆盖对应字段, 最后统一校验。
+//! 带账号密码的 dsn 不要写进配置文件, 用 WISEYE_AGENT_MYSQL_DSN 传入, 没有配置 dsn 时不连接 MySQL。
+
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Deserializer};
+
+// 默认配置文件路径
+pub const DEFAULT_CONFIG_PATH: &str = "config/wiseye_agent.toml";
+
+// 无论 denied_paths 如何配置都禁止访问的路径
+pub const BUILTIN_DENIED_PATHS: &[&str] = &["/etc/shadow", "/etc/shadow-", "/etc/gshadow", "/etc/gshadow-", "/root/.ssh", "/home/*/.ssh"];
+
+// 环境变量名称
+const ENV_CONFIG_PATH: &str = "WISEYE_AGENT_CONFIG";
+const ENV_CLIENT_ADDR: &str = "WISEYE_AGENT_CLIENT_ADDR";
+const ENV_CLIENT_PORT: &str = "WISEYE_AGENT_CLIENT_PORT";
+const ENV_MYSQL_DSN: &str = "WISEYE_AGENT_MYSQL_DSN";
+
+// AgentConfig 是整个 agent 的配置, 每个子系统从这里读取自己的配置段
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AgentConfig {
+    // 监听地址和端口
+    #[serde(default)]
+    pub wiseye_agent: ServerConfig,
+    // MySQL 连接信息
+    #[serde(default)]
+    pub mysql: MysqlConfig,
+    // /metrics 的采集配置
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    // 文件接口的配置
+    #[serde(default)]
+    pub file: FileConfig,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ServerConfig {
+    #[serde(default = "default_client_addr")]
+    pub client_addr: String,
+    // 兼容 client_port = "4200" 和 client_port = 4200 两种写法
+    #[serde(default = "default_client_port", deserialize_with = "deserialize_port")]
+    pub client_port: u16,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MysqlConfig {
+    #[serde(default)]
+    pub dsn: Option<String>,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    // 需要导出 node_directory_size_bytes 的目录
+    #[serde(default)]
+    pub dir_size_paths: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    // 单次读取文件返回的最大字节数, 更大的文件需要用 Range 或按行分页读取
+    #[serde(default = "default_max_read_size")]
+    pub max_read_size: u64,
+    // 只读的目录, 其中的子目录同样可以读取
+    #[serde(default = "default_read_roots")]
+    pub read_roots: Vec<PathBuf>,
+    // 可以读写的目录, 默认为空
+    #[serde(default)]
+    pub write_roots: Vec<PathBuf>,
+    // 禁止访问的路径, 支持 * ? [] 通配符 (* 不匹配 /), 目录被禁止时其中的文件同样被禁止。
+    // 与 BUILTIN_DENIED_PATHS 合并使用, 不会替换内置的列表
+    #[serde(default)]
+    pub denied_paths: Vec<String>,
+    // /file/follow 开始时最多返回的行数
+    #[serde(default = "default_max_follow_lines")]
+    pub max_follow_lines: usize,
+    // 同时跟踪文件的连接数上限
+    #[serde(default = "default_max_followers")]
+    pub max_followers: usize,
+    // /file/list 递归的最大层数
+    #[serde(default = "default_max_list_depth")]
+    pub max_list_depth: usize,
+    // /file/list 最多遍历的项数, 超过时返回不完整的结果
+    #[serde(default = "default_max_list_entries")]
+    pub max_list_entries: usize,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            client_addr: default_client_addr(),
+            client_port: default_client_port(),
+        }
+    }
+}
+
+impl FileConfig {
+    // denied_patterns 返回内置和配置的禁止路径
+    pub fn denied_patterns(&self) -> impl Iterator<Item = &str> {
+        BUILTIN_DENIED_PATHS.iter().copied().chain(self.denied_paths.iter().map(String::as_str))
+    }
+}
+
+impl Default for FileConfig {
+    fn default() -> Self {
+        Self {
+            max_read_size: default_max_read_size(),
+            read_roots: default_read_roots(),
+            write_roots: Vec::new(),
+            denied_paths: Vec::new(),
+            max_follow_lines: default_max_follow_lines(),
+            max_followers: default_max_followers(),
+            max_list_depth: default_max_list_depth(),
+            max_list_entries: default_max_list_entries(),
+        }
+    }
+}
+
+fn default_client_addr() -> String {
+    "0.0.0.0".to_string()
+}
+
+fn default_client_port() -> u16 {
+    4201
+}
+
+fn default_max_read_size() -> u64 {
+    64 * 1024 * 1024
+}
+
+fn default_read_roots() -> Vec<PathBuf> {
+    vec![PathBuf::from("/")]
+}
+
+fn default_max_follow_lines() -> usize {
+    1000
+}
+
+fn default_max_followers() -> usize {
+    16
+}
+
+fn default_max_list_depth() -> usize {
+    8
+}
+
+fn default_max_list_entries() -> usize {
+    100_000
+}
+
+fn deserialize_port<'de, D>(deserializer: D) -> Result<u16, D::Error>
+    where
+        D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        Number(i64),
+        Text(String),
+    }
+
+    match Port::deserialize(deserializer)? {
+        Port::Number(port) => u16::try_from(port)
+            .map_err(|_| serde::de::Error::custom(format!("port {} is out of range 1-65535", port))),
+        Port::Text(port) => parse_port(&port).map_err(serde::de::Error::custom),
+    }
+}
+
+fn parse_port(value: &str) -> Result<u16, String> {
+    value.trim().parse::<u16>()
+        .map_err(|_| format!("invalid port {:?}, expected a number in 1-65535", value))
+}
+
+// ConfigError 描述加载配置时可能出现的错误
+#[derive(Debug)]
+pub enum ConfigError {
+    // 读取配置文件失败
+    Io { path: PathBuf, source: io::Error },
+    // 配置文件格式错误
+    Parse { path: PathBuf, source: toml::de::Error },
+    // 命令行参数错误
+    Args(String),
+    // 环境变量的值无效
+    Env { name: &'static str, message: String },
+    // 字段校验失败
+    Invalid { field: &'static str, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { path, source } =>
+                write!(f, "failed to read config file {}: {}", path.display(), source),
+            ConfigError::Parse { path, source } =>
+                write!(f, "failed to parse config file {}: {}", path.display(), source),
+            ConfigError::Args(message) => write!(f, "invalid arguments: {}", message),
+            ConfigError::Env { name, message } => write!(f, "invalid env {}: {}", name, message),
+            ConfigError::Invalid { field, message } => write!(f, "invalid config {}: {}", field, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ConfigError::Io { source, .. } => Some(source),
+            ConfigError::Parse { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl AgentConfig {
+    // load 根据命令行参数和环境变量加载配置
+    pub fn load<I>(args: I) -> Result<Self, ConfigError>
+        where
+            I: IntoIterator<Item = String>,
+    {
+        let cli_path = config_path_from_args(args)?;
+        let env_path = env::var_os(ENV_CONFIG_PATH).map(PathBuf::from);
+
+        // 显式指定的配置文件必须存在, 默认路径不存在时使用默认配置
+        let mut config = match cli_path.or(env_path) {
+            Some(path) => Self::from_file(&path)?,
+            None if Path::new(DEFAULT_CONFIG_PATH).exists() => Self::from_file(DEFAULT_CONFIG_PATH)?,
+            None => Self::default(),
+        };
+
+        config.apply_env()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    // from_file 从 toml 文件中解析配置
+    pub fn from_file<P>(path: P) -> Result<Self, ConfigError>
+        where
+            P: AsRef<Path>,
+    {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|source| ConfigError::Io { path: path.to_path_buf(), source })?;
+        toml::from_str(&content)
+            .map_err(|source| ConfigError::Parse { path: path.to_path_buf(), source })
+    }
+
+    // apply_env 用 WISEYE_AGENT_* 环境变量覆盖配置文件中的值
+    fn apply_env(&mut self) -> Result<(), ConfigError> {
+        if let Some(addr) = read_env(ENV_CLIENT_ADDR)? {
+            self.wiseye_agent.client_addr = addr;
+        }
+        if let Some(port) = read_env(ENV_CLIENT_PORT)? {
+            self.wiseye_agent.client_port = parse_port(&port)
+                .map_err(|message| ConfigError::Env { name: ENV_CLIENT_PORT, message })?;
+        }
+        if let Some(dsn) = read_env(ENV_MYSQL_DSN)? {
+            self.mysql.dsn = Some(dsn);
+        }
+        Ok(())
+    }
+
+    // validate 检查各字段的取值是否合法
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        self.wiseye_agent.client_addr.parse::<IpAddr>()
+            .map_err(|_| ConfigError::Invalid {
+                field: "wiseye_agent.client_addr",
+                message: format!("{:?} is not a valid IP address", self.wiseye_agent.client_addr),
+            })?;
+
+        if self.wiseye_agent.client_port == 0 {
+            return Err(ConfigError::Invalid {
+                field: "wiseye_agent.client_port",
+                message: "port must be in 1-65535".to_string(),
+            });
+        }
+
+        if self.mysql.dsn.as_ref().is_some_and(|dsn| !dsn.starts_with("mysql://")) {
+            return Err(ConfigError::Invalid {
+                field: "mysql.dsn",
+                message: "dsn must start with mysql://".to_string(),
+            });
+        }
+
+        if let Some(path) = self.metrics.dir_size_paths.iter().find(|path| !path.is_absolute()) {
+            return Err(ConfigError::Invalid {
+                field: "metrics.dir_size_paths",
+                message: format!("{} is not an absolute path", path.display()),
+            });
+        }
+
+        if self.file.max_read_size == 0 {
+            return Err(ConfigError::Invalid {
+                field: "file.max_read_size",
+                message: "max_read_size must be greater than 0".to_string(),
+            });
+        }
+
+        if self.file.max_list_entries == 0 {
+            return Err(ConfigError::Invalid {
+                field: "file.max_list_entries",
+                message: "max_list_entries must be greater than 0".to_string(),
+            });
+        }
+
+        for (field, roots) in [("file.read_roots", &self.file.read_roots), ("file.write_roots", &self.file.write_roots)] {
+            if let Some(path) = roots.iter().find(|path| !path.is_absolute()) {
+                return Err(ConfigError::Invalid {
+                    field,
+                    message: format!("{} is not an absolute path", path.display()),
+                });
+            }
+        }
+
+        for pattern in &self.file.denied_paths {
+            if !pattern.starts_with('/') {
+                return Err(ConfigError::Invalid {
+                    field: "file.denied_paths",
+                    message: format!("{:?} is not an absolute path", pattern),
+                });
+            }
+            glob::Pattern::new(pattern).map_err(|e| ConfigError::Invalid {
+                field: "file.denied_paths",
+                message: format!("{:?}: {}", pattern, e),
+            })?;
+        }
+
+        Ok(())
+    }
+
+    // listen_addr 返回 agent 监听的地址
+    pub fn listen_addr(&self) -> SocketAddr {
+        // validate 已经保证 client_addr 是合法的 IP 地址
+        let ip = self.wiseye_agent.client_addr.parse::<IpAddr>()
+            .unwrap_or(IpAddr::from([0, 0, 0, 0]));
+        SocketAddr::new(ip, self.wiseye_agent.client_port)
+    }
+}
+
+// config_path_from_args 从命令行参数中解析 --config <path> / --config=<path> / -c <path>
+fn config_path_from_args<I>(args: I) -> Result<Option<PathBuf>, ConfigError>
+    where
+        I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+    let mut path = None;
+
+    while let Some(arg) = args.next() {
+        if arg == "--config" || arg == "-c" {
+            match args.next() {
+                Some(value) => path = Some(PathBuf::from(value)),
+                None => return Err(ConfigError::Args(format!("{} requires a path", arg))),
+            }
+        } else if let Some(value) = arg.strip_prefix("--config=") {
+            path = Some(PathBuf::from(value));
+        } else {
+            return Err(ConfigError::Args(format!("unknown argument {:?}", arg)));
+        }
+    }
+
+    Ok(path)
+}
+
+// read_env 读取环境变量, 未设置或为空时返回 None
+fn read_env(name: &'static str) -> Result<Option<String>, ConfigError> {
+    match env::var(name) {
+        Ok(value) if value.trim().is_empty() => Ok(None),
+        Ok(value) => Ok(Some(value)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(env::VarError::NotUnicode(_)) => Err(ConfigError::Env {
+            name,
+            message: "value is not valid unicode".to_string(),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use super::*;
+
+    // 环境变量是进程级的, 读写环境变量的用例需要串行执行
+    static ENV_LOCK: Mutex<()> = Mutex::new(());
+
+    const ENV_NAMES: [&str; 4] = [ENV_CONFIG_PATH, ENV_CLIENT_ADDR, ENV_CLIENT_PORT, ENV_MYSQL_DSN];
+
+    fn args(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    fn parse(content: &str) -> Result<AgentConfig, toml::de::Error> {
+        toml::from_str(content)
+    }
+
+    fn clear_env() {
+        for name in ENV_NAMES {
+            env::remove_var(name);
+        }
+    }
+
+    #[test]
+    fn parses_config_path_arguments() {
+        assert_eq!(config_path_from_args(args(&[])).unwrap(), None);
+        assert_eq!(config_path_from_args(args(&["--config", "/etc/a.toml"])).unwrap(), Some(PathBuf::from("/etc/a.toml")));
+        assert_eq!(config_path_from_args(args(&["-c", "b.toml"])).unwrap(), Some(PathBuf::from("b.toml")));
+        assert_eq!(config_path_from_args(args(&["--config=c.toml"])).unwrap(), Some(PathBuf::from("c.toml")));
+        // 重复指定时以最后一个为准
+        assert_eq!(config_path_from_args(args(&["-c", "a.toml", "--config=b.toml"])).unwrap(), Some(PathBuf::from("b.toml")));
+
+        assert!(matches!(config_path_from_args(args(&["--config"])), Err(ConfigError::Args(_))));
+        assert!(matches!(config_path_from_args(args(&["-c"])), Err(ConfigError::Args(_))));
+        assert!(matches!(config_path_from_args(args(&["--verbose"])), Err(ConfigError::Args(_))));
+    }
+
+    #[test]
+    fn parses_port_as_number_or_string() {
+        assert_eq!(parse("[wiseye_agent]\nclient_port = 4300").unwrap().wiseye_agent.client_port, 4300);
+        assert_eq!(parse("[wiseye_agent]\nclient_port = \"4301\"").unwrap().wiseye_agent.client_port, 4301);
+        assert!(parse("[wiseye_agent]\nclient_port = 70000").is_err());
+        assert!(parse("[wiseye_agent]\nclient_port = \"http\"").is_err());
+    }
+
+    #[test]
+    fn rejects_unknown_fields() {
+        assert!(parse("[wiseye_agent]\nclient_adr = \"127.0.0.1\"").is_err());
+        assert!(parse("[mysql]\nurl = \"mysql://localhost/db\"").is_err());
+        assert!(parse("[file]\nwrite_root = [\"/tmp\"]").is_err());
+        assert!(parse("[auth]\ntoken = \"secret\"").is_err());
+    }
+
+    #[test]
+    fn defaults_have_no_mysql_credentials() {
+        let config = parse("").unwrap();
+        assert_eq!(config.mysql.dsn, None);
+        assert_eq!(config.listen_addr(), "0.0.0.0:4201".parse().unwrap());
+        config.validate().unwrap();
+    }
+
+    #[test]
+    fn denied_paths_extend_the_builtin_list() {
+        let config = parse("[file]\ndenied_paths = [\"/data/*/secret\"]").unwrap();
+        assert!(config.file.write_roots.is_empty());
+        let patterns = config.file.denied_patterns().collect::<Vec<_>>();
+        assert!(patterns.contains(&"/etc/shadow"));
+        assert!(patterns.contains(&"/root/.ssh"));
+        assert!(patterns.contains(&"/data/*/secret"));
+    }
+
+    #[test]
+    fn validate_reports_the_invalid_field() {
+        let invalid_field = |content: &str| match parse(content).unwrap().validate() {
+            Err(ConfigError::Invalid { field, .. }) => field,
+            other => panic!("expected invalid config, got {:?}", other),
+        };
+
+        assert_eq!(invalid_field("[wiseye_agent]\nclient_addr = \"localhost\""), "wiseye_agent.client_addr");
+        assert_eq!(invalid_field("[wiseye_agent]\nclient_port = 0"), "wiseye_agent.client_port");
+        assert_eq!(invalid_field("[mysql]\ndsn = \"postgres://localhost/db\""), "mysql.dsn");
+        assert_eq!(invalid_field("[metrics]\ndir_size_paths = [\"var/log\"]"), "metrics.dir_size_paths");
+        assert_eq!(invalid_field("[file]\nmax_read_size = 0"), "file.max_read_size");
+        assert_eq!(invalid_field("[file]\nmax_list_entries = 0"), "file.max_list_entries");
+        assert_eq!(invalid_field("[file]\nread_roots = [\"data\"]"), "file.read_roots");
+        assert_eq!(invalid_field("[file]\nwrite_roots = [\"tmp\"]"), "file.write_roots");
+        assert_eq!(invalid_field("[file]\ndenied_paths = [\"*.key\"]"), "file.denied_paths");
+        assert_eq!(invalid_field("[file]\ndenied_paths = [\"/data/[\"]"), "file.denied_paths");
+    }
+
+    #[test]
+    fn env_overrides_config_file() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        clear_env();
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("agent.toml");
+        fs::write(&path, "[wiseye_agent]\nclient_addr = \"127.0.0.1\"\nclient_port = \"4200\"\n").unwrap();
+
+        // 只通过 WISEYE_AGENT_CONFIG 指定配置文件
+        env::set_var(ENV_CONFIG_PATH, &path);
+        let config = AgentConfig::load(args(&[])).unwrap();
+        assert_eq!(config.listen_addr(), "127.0.0.1:4200".parse().unwrap());
+        assert_eq!(config.mysql.dsn, None);
+
+        env::set_var(ENV_CLIENT_ADDR, "10.0.0.1");
+        env::set_var(ENV_CLIENT_PORT, "4211");
+        env::set_var(ENV_MYSQL_DSN, "mysql://agent:secret@db/metrics");
+        let config = AgentConfig::load(args(&[])).unwrap();
+        assert_eq!(config.listen_addr(), "10.0.0.1:4211".parse().unwrap());
+        assert_eq!(config.mysql.dsn.as_deref(), Some("mysql://agent:secret@db/metrics"));
+
+        // 空值视为未设置
+        env::set_var(ENV_CLIENT_ADDR, " ");
+        assert_eq!(AgentConfig::load(args(&[])).unwrap().wiseye_agent.client_addr, "127.0.0.1");
+
+        env::set_var(ENV_CLIENT_PORT, "port");
+        let result = AgentConfig::load(args(&[]));
+        assert!(matches!(result, Err(ConfigError::Env { name: ENV_CLIENT_PORT, .. })));
+
+        env::set_var(ENV_CLIENT_PORT, "0");
+        let result = AgentConfig::load(args(&[]));
+        assert!(matches!(result, Err(ConfigError::Invalid { field: "wiseye_agent.client_port", .. })));
+
+        clear_env();
+    }
+
+    #[test]
+    fn command_line_path_takes_precedence() {
+        let _guard = ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        clear_env();
+
+        let dir = tempfile::tempdir().unwrap();
+        let env_path = dir.path().join("env.toml");
+        let cli_path = dir.path().join("cli.toml");
+        fs::write(&env_path, "[wiseye_agent]\nclient_port = 4400\n").unwrap();
+        fs::write(&cli_path, "[wiseye_agent]\nclient_port = 4401\n").unwrap();
+
+        env::set_var(ENV_CONFIG_PATH, &env_path);
+        let arg = format!("--config={}", cli_path.display());
+        assert_eq!(AgentConfig::load(args(&[&arg])).unwrap().wiseye_agent.client_port, 4401);
+
+        // 显式指定的配置文件不存在时报错, 而不是退回默认配置
+        let missing = dir.path().join("missing.toml");
+        let result = AgentConfig::load(args(&["-c", missing.to_str().unwrap()]));
+        assert!(matches!(result, Err(ConfigError::Io { .. })));
+
+        fs::write(&cli_path, "[wiseye_agent\n").unwrap();
+        let result = AgentConfig::load(args(&["-c", cli_path.to_str().unwrap()]));
+        assert!(matches!(result, Err(ConfigError::Parse { .. })));
+
+        clear_env();
+    }
+}
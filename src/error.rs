@@ -0,0 +1,17 @@
+//! hand 层和 api 层共用的错误类型。
+//! hand 层的函数都返回 io::Error, io::ErrorKind 无法表达的错误包装在 io::Error 中返回,
+//! ApiError 根据包装的类型选择 HTTP 状态码。
+
+use std::fmt;
+
+// PreconditionFailed 表示写入前目标文件已经被修改, 接口返回 412
+#[derive(Debug)]
+pub struct PreconditionFailed(pub String);
+
+impl fmt::Display for PreconditionFailed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for PreconditionFailed {}
@@ -1,98 +1,1525 @@
 use std::{fs, io};
-use std::env::current_dir;
+use std::cell::Cell;
 use std::fs::{create_dir, File};
-use std::path::Path;
+use std::io::{Read, Write};
+use std::os::unix::fs::{FileTypeExt, MetadataExt, OpenOptionsExt, PermissionsExt};
+use std::path::{Component, Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use chrono::{Local, NaiveDateTime, TimeZone};
+use flate2::Compression;
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use nix::sys::stat::{utimensat, UtimensatFlags};
+use nix::sys::time::TimeSpec;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tar::{Archive, Builder, EntryType};
+use walkdir::WalkDir;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+use zip::write::SimpleFileOptions;
+
+use crate::error::PreconditionFailed;
 
 // delete_file 删除指定的文件
 pub fn delete_file<P>(path: P) -> io::Result<()>
     where
         P: AsRef<Path>
 {
-/*    if path.exists() {
-        fs::remove_file(path)
+    fs::remove_file(path)
+}
+
+// create_file 创建空文件, 文件已存在时不做修改。
+// 用 create_new 和 O_NOFOLLOW 创建, 路径是符号链接 (包括指向不存在的文件) 时不会在链接指向的位置创建文件
+pub fn create_file<P>(path: P) -> io::Result<()>
+    where
+        P: AsRef<Path>,
+{
+    match File::options().write(true).create_new(true).custom_flags(libc::O_NOFOLLOW).open(path) {
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        result => result.map(|_| ()),
+    }
+}
+
+// IfExists 决定目标已存在时复制和移动的行为
+#[derive(Debug, Clone, Copy, PartialEq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum IfExists {
+    // 返回 AlreadyExists, 不做任何修改
+    #[default]
+    Fail,
+    // 保留已存在的文件, 复制目录时只复制目标中还没有的文件
+    Skip,
+    // 替换已存在的文件, 目录合并到已存在的目录中
+    Overwrite,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferAction {
+    Copied,
+    Moved,
+    Skipped,
+}
+
+// TransferResult 记录复制或移动实际完成的操作
+#[derive(Debug, Clone, Serialize)]
+pub struct TransferResult {
+    pub path: PathBuf,
+    pub dest: PathBuf,
+    // 移动时如果有文件没能复制, 源文件会保留, action 为 copied
+    pub action: TransferAction,
+    // 源和目标不在同一个文件系统, 移动改为复制后删除
+    pub cross_device: bool,
+    pub files: u64,
+    pub dirs: u64,
+    pub symlinks: u64,
+    pub bytes: u64,
+    // 被替换的已存在文件数
+    pub overwritten: u64,
+    // 因目标已存在或文件类型不支持而没有复制的文件
+    pub skipped: Vec<PathBuf>,
+}
+
+impl TransferResult {
+    fn new(path: &Path, dest: &Path, action: TransferAction) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            dest: dest.to_path_buf(),
+            action,
+            cross_device: false,
+            files: 0,
+            dirs: 0,
+            symlinks: 0,
+            bytes: 0,
+            overwritten: 0,
+            skipped: Vec::new(),
+        }
+    }
+
+    fn record(&mut self, metadata: &fs::Metadata) {
+        let file_type = metadata.file_type();
+        if file_type.is_dir() {
+            self.dirs += 1;
+        } else if file_type.is_symlink() {
+            self.symlinks += 1;
+        } else {
+            self.files += 1;
+            self.bytes += metadata.len();
+        }
+    }
+}
+
+// copy_file 复制文件或递归复制目录, 保留权限、时间, 以 root 运行时还保留属主。
+// 符号链接复制链接本身, 设备文件、管道等不复制, 记录在 skipped 中。denied 中的文件不复制
+pub fn copy_file(src: &Path, dest: &Path, if_exists: IfExists, denied: &dyn Fn(&Path) -> bool) -> io::Result<TransferResult> {
+    let metadata = fs::symlink_metadata(src)?;
+    if metadata.is_dir() && dest.starts_with(src) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot copy {} into itself", src.display())));
+    }
+
+    let mut result = TransferResult::new(src, dest, TransferAction::Copied);
+    if let Ok(existing) = fs::symlink_metadata(dest) {
+        match if_exists {
+            IfExists::Fail => return Err(already_exists(dest)),
+            IfExists::Skip if !(metadata.is_dir() && existing.is_dir()) => {
+                result.action = TransferAction::Skipped;
+                result.skipped.push(dest.to_path_buf());
+                return Ok(result);
+            }
+            _ => {}
+        }
+    }
+
+    copy_tree(src, dest, if_exists, denied, &mut result)?;
+    Ok(result)
+}
+
+// move_file 移动并改名文件或目录, 优先使用 rename。
+// 跨文件系统或者要合并到非空目录时, 先复制并 fsync, 全部复制成功后再删除源文件
+pub fn move_file(src: &Path, dest: &Path, if_exists: IfExists, denied: &dyn Fn(&Path) -> bool) -> io::Result<TransferResult> {
+    let metadata = fs::symlink_metadata(src)?;
+    if metadata.is_dir() && dest.starts_with(src) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot move {} into itself", src.display())));
+    }
+
+    let mut result = TransferResult::new(src, dest, TransferAction::Moved);
+    let existing = fs::symlink_metadata(dest).ok();
+    if let Some(existing) = &existing {
+        match if_exists {
+            IfExists::Fail => return Err(already_exists(dest)),
+            IfExists::Skip => {
+                result.action = TransferAction::Skipped;
+                result.skipped.push(dest.to_path_buf());
+                return Ok(result);
+            }
+            IfExists::Overwrite if existing.is_dir() && !metadata.is_dir() => return Err(overwrite_dir(dest)),
+            IfExists::Overwrite => {}
+        }
+    }
+
+    // rename 会把目录中的文件一起移走, 其中有禁止访问的文件时拒绝移动
+    for entry in WalkDir::new(src) {
+        let entry = entry?;
+        let target = transfer_target(src, dest, entry.path());
+        if denied(entry.path()) || denied(&target) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Refusing to move {}: access to {} is denied", src.display(), entry.path().display()),
+            ));
+        }
+        result.record(&entry.metadata()?);
+    }
+
+    // rename 不能用目录替换文件
+    let replaced_file = existing.as_ref().is_some_and(|existing| !existing.is_dir()) && metadata.is_dir();
+    if replaced_file {
+        fs::remove_file(dest)?;
+    }
+    match fs::rename(src, dest) {
+        Ok(()) => {
+            result.overwritten = existing.is_some() as u64;
+            sync_parent(dest)?;
+            return Ok(result);
+        }
+        Err(e) if matches!(e.raw_os_error(), Some(libc::EXDEV) | Some(libc::ENOTEMPTY) | Some(libc::EEXIST)) => {
+            result = TransferResult::new(src, dest, TransferAction::Moved);
+            result.cross_device = e.raw_os_error() == Some(libc::EXDEV);
+            result.overwritten = replaced_file as u64;
+        }
+        Err(e) => return Err(e),
+    }
+
+    copy_tree(src, dest, if_exists, denied, &mut result)?;
+    if !result.skipped.is_empty() {
+        result.action = TransferAction::Copied;
+        return Ok(result);
+    }
+    if metadata.is_dir() {
+        fs::remove_dir_all(src)?;
     } else {
-        Err(io::Error::new(io::ErrorKind::NotFound, "File not found"))
-    }*/
+        fs::remove_file(src)?;
+    }
+    sync_parent(src)?;
+    Ok(result)
+}
 
-    let path_ref = path.as_ref();
+// copy_tree 复制 src 到 dest, 调用前已经处理过 dest 本身的冲突。
+// 目录的权限和时间在其中的文件复制完之后再设置, 否则只读目录无法写入, 写入文件也会改变目录的修改时间
+fn copy_tree(
+    src: &Path,
+    dest: &Path,
+    if_exists: IfExists,
+    denied: &dyn Fn(&Path) -> bool,
+    result: &mut TransferResult,
+) -> io::Result<()> {
+    let mut created_dirs = Vec::new();
+    let mut walker = WalkDir::new(src).into_iter().filter_entry(|entry| !denied(entry.path()));
+    while let Some(entry) = walker.next() {
+        let entry = entry?;
+        let target = transfer_target(src, dest, entry.path());
+        if denied(&target) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Refusing to copy {}: access to {} is denied", entry.path().display(), target.display()),
+            ));
+        }
 
-    // 先检查文件是否存在
-    if let Ok(file) = File::open(path_ref) {
-        // 存在的话，删除文件
-        fs::remove_file(path_ref)?;
-    } else if let Err(e) = fs::remove_file(path_ref) {
-        // 如果文件不存在且删除操作也失败，则返回相应的错误
-        if e.kind() != io::ErrorKind::NotFound {
-            return Err(e);
+        let metadata = entry.metadata()?;
+        let file_type = metadata.file_type();
+        if !(file_type.is_dir() || file_type.is_file() || file_type.is_symlink()) {
+            result.skipped.push(entry.path().to_path_buf());
+            continue;
         }
+
+        if let Ok(existing) = fs::symlink_metadata(&target) {
+            match if_exists {
+                _ if file_type.is_dir() && existing.is_dir() => continue,
+                IfExists::Fail => return Err(already_exists(&target)),
+                IfExists::Skip => {
+                    result.skipped.push(target);
+                    if file_type.is_dir() {
+                        walker.skip_current_dir();
+                    }
+                    continue;
+                }
+                IfExists::Overwrite if existing.is_dir() => return Err(overwrite_dir(&target)),
+                IfExists::Overwrite => {
+                    // 普通文件通过 rename 原子地替换, 其他类型需要先删除
+                    if !file_type.is_file() {
+                        fs::remove_file(&target)?;
+                    }
+                    result.overwritten += 1;
+                }
+            }
+        }
+
+        if file_type.is_dir() {
+            fs::create_dir(&target)?;
+            created_dirs.push((target, metadata));
+            result.dirs += 1;
+        } else if file_type.is_symlink() {
+            std::os::unix::fs::symlink(fs::read_link(entry.path())?, &target)?;
+            preserve_metadata(&target, &metadata)?;
+            result.symlinks += 1;
+        } else {
+            copy_regular(entry.path(), &target, &metadata)?;
+            result.files += 1;
+            result.bytes += metadata.len();
+        }
+    }
+
+    for (dir, metadata) in created_dirs.iter().rev() {
+        preserve_metadata(dir, metadata)?;
+        File::open(dir)?.sync_all()?;
+    }
+    sync_parent(dest)
+}
+
+// copy_regular 先写入同一目录下的临时文件, 设置好属性并落盘后再 rename, 失败时不会留下不完整的文件。
+// 临时文件名带有随机数并且用 create_new 创建, 不会写入提前放置的链接; 设置属性前权限为 0600, 复制中途其他用户无法读取
+fn copy_regular(src: &Path, dest: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    let mut reader = File::open(src)?;
+    let temp = unique_temp_path(dest, "copy")?;
+    let mut file = File::options().write(true).create_new(true).mode(0o600).open(&temp)?;
+    let copied = (|| {
+        io::copy(&mut reader, &mut file)?;
+        preserve_metadata(&temp, metadata)?;
+        file.sync_all()?;
+        fs::rename(&temp, dest)
+    })();
+    if copied.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    copied
+}
+
+// preserve_metadata 设置属主、权限和访问、修改时间, 符号链接只设置链接本身
+fn preserve_metadata(path: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    // 只有 root 才能把属主改成其他用户, 先改属主, chown 会清除 setuid 位
+    if unsafe { libc::geteuid() } == 0 {
+        std::os::unix::fs::lchown(path, Some(metadata.uid()), Some(metadata.gid()))?;
+    }
+    if !metadata.file_type().is_symlink() {
+        fs::set_permissions(path, fs::Permissions::from_mode(metadata.mode() & 0o7777))?;
     }
+    utimensat(
+        None,
+        path,
+        &TimeSpec::new(metadata.atime(), metadata.atime_nsec()),
+        &TimeSpec::new(metadata.mtime(), metadata.mtime_nsec()),
+        UtimensatFlags::NoFollowSymlink,
+    )?;
     Ok(())
 }
 
-// create_file 在当前路径下创建文件
-pub fn create_file<P>(filename: P) -> io::Result<()>
+// transfer_target 返回 src 中的文件复制到 dest 后的路径
+fn transfer_target(src: &Path, dest: &Path, path: &Path) -> PathBuf {
+    match path.strip_prefix(src) {
+        Ok(relative) if !relative.as_os_str().is_empty() => dest.join(relative),
+        _ => dest.to_path_buf(),
+    }
+}
+
+// sync_parent 把 path 所在目录落盘, rename、创建和删除文件都需要
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+        Some(parent) => File::open(parent)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+fn already_exists(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, format!("{} already exists", path.display()))
+}
+
+fn overwrite_dir(path: &Path) -> io::Error {
+    io::Error::new(io::ErrorKind::AlreadyExists, format!("Cannot overwrite directory {} with a non-directory", path.display()))
+}
+
+pub fn mkdir<P>(path: P) -> io::Result<()>
     where
         P: AsRef<Path>,
 {
-    let pwd = current_dir()?;
-    let file_path = pwd.join(filename);
-    if !file_path.exists() {
-        File::create(file_path)?;
+    if !path.as_ref().exists() {
+        create_dir(path)?;
     }
     Ok(())
 }
 
-// copy_file 复制文件
-pub fn copy_file(src: &Path, dest: &Path) -> io::Result<()> {
-/*    if !dest.exists() {
-        fs::copy(src, dest)?;
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArchiveFormat {
+    Tar,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+    #[serde(rename = "tar.zst")]
+    TarZst,
+}
+
+impl ArchiveFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Tar => "tar",
+            Self::TarGz => "tar.gz",
+            Self::TarZst => "tar.zst",
+        }
+    }
+
+    // from_path 根据扩展名判断压缩格式
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(Self::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(Self::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+// ArchiveProgress 记录打包或解包的进度。
+// 打包时 bytes 为已写入的文件大小, total_bytes 为所有文件的总大小;
+// 解包时两者都按压缩包本身计算, 即已读取的字节数和压缩包大小
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ArchiveProgress {
+    pub entries: u64,
+    pub bytes: u64,
+    pub total_bytes: u64,
+}
+
+// ArchiveWriter 在 tar 外面套一层压缩, 结束时需要调用 finish 写入压缩格式的结尾
+enum ArchiveWriter {
+    Tar(File),
+    TarGz(GzEncoder<File>),
+    TarZst(zstd::Encoder<'static, File>),
+}
+
+impl ArchiveWriter {
+    fn new(file: File, format: ArchiveFormat) -> io::Result<Self> {
+        Ok(match format {
+            ArchiveFormat::Tar => Self::Tar(file),
+            ArchiveFormat::TarGz => Self::TarGz(GzEncoder::new(file, Compression::default())),
+            ArchiveFormat::TarZst => Self::TarZst(zstd::Encoder::new(file, 0)?),
+        })
     }
-    Ok(())*/
 
-    if !dest.exists() || !dest.is_file() {
-        fs::copy(src, dest)?;
+    fn finish(self) -> io::Result<File> {
+        match self {
+            Self::Tar(file) => Ok(file),
+            Self::TarGz(encoder) => encoder.finish(),
+            Self::TarZst(encoder) => encoder.finish(),
+        }
     }
-    Ok(())
 }
 
-// move_file 移动并改名文件
-pub fn move_file(src: &Path, dest: &Path) -> io::Result<()> {
-    if !dest.exists() {
-        fs::rename(src, dest)?;
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            Self::Tar(file) => file.write(buf),
+            Self::TarGz(encoder) => encoder.write(buf),
+            Self::TarZst(encoder) => encoder.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match self {
+            Self::Tar(file) => file.flush(),
+            Self::TarGz(encoder) => encoder.flush(),
+            Self::TarZst(encoder) => encoder.flush(),
+        }
     }
-    Ok(())
 }
 
-pub fn mkdir<P>(path: P) -> io::Result<()>
+// CountingReader 统计从压缩包中读取的字节数, 用于计算解包进度
+struct CountingReader<R> {
+    inner: R,
+    count: Rc<Cell<u64>>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.inner.read(buf)?;
+        self.count.set(self.count.get() + read as u64);
+        Ok(read)
+    }
+}
+
+// create_tar 把 file_path 打包到 tar_path, 包内的路径以 file_path 的文件名开头, 与 tar -C 上级目录 相同。
+// 符号链接按链接本身打包, socket 和 denied 返回 true 的文件被跳过。tar_path 已存在时返回 AlreadyExists
+pub fn create_tar(
+    tar_path: &Path,
+    file_path: &Path,
+    format: ArchiveFormat,
+    denied: &dyn Fn(&Path) -> bool,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    create_archive(tar_path, file_path, denied, |file, source| write_tar(file, source, format, progress))
+}
+
+// ArchiveSource 是要打包的文件, 压缩包本身在被打包的目录中时已经被排除
+struct ArchiveSource<'a> {
+    root: &'a Path,
+    // 包内路径的第一级, 即 root 的文件名
+    name: PathBuf,
+    entries: Vec<walkdir::DirEntry>,
+    // 所有普通文件的总大小
+    total_bytes: u64,
+}
+
+impl ArchiveSource<'_> {
+    // name_of 返回文件在包内的路径
+    fn name_of(&self, entry: &walkdir::DirEntry) -> PathBuf {
+        self.name.join(entry.path().strip_prefix(self.root).unwrap_or(entry.path()))
+    }
+}
+
+// create_archive 创建压缩包文件并交给 write 写入, 失败时删除写了一半的压缩包
+fn create_archive<F>(archive_path: &Path, file_path: &Path, denied: &dyn Fn(&Path) -> bool, write: F) -> io::Result<ArchiveProgress>
     where
-        P: AsRef<Path>,
+        F: FnOnce(File, &ArchiveSource) -> io::Result<ArchiveProgress>,
 {
-    if !path.exists() {
-        create_dir(path)?;
+    fs::symlink_metadata(file_path)?;
+
+    let file = File::create_new(archive_path)?;
+    let result = archive_source(Some(archive_path), file_path, denied).and_then(|source| write(file, &source));
+    if result.is_err() {
+        let _ = fs::remove_file(archive_path);
+    }
+    result
+}
+
+// archive_source 遍历要打包的文件, archive_path 为正在写入的压缩包, 在被打包的目录中时跳过它
+fn archive_source<'a>(
+    archive_path: Option<&Path>,
+    file_path: &'a Path,
+    denied: &dyn Fn(&Path) -> bool,
+) -> io::Result<ArchiveSource<'a>> {
+    let name = file_path.file_name()
+        .map(PathBuf::from)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Cannot archive {}", file_path.display())))?;
+    let archive_id = archive_path
+        .map(|path| fs::metadata(path).map(|metadata| (metadata.dev(), metadata.ino())))
+        .transpose()?;
+    let entries = WalkDir::new(file_path)
+        .into_iter()
+        .filter_entry(|entry| !denied(entry.path()) && archive_id.is_none_or(|archive_id| {
+            entry.metadata().map_or(true, |metadata| (metadata.dev(), metadata.ino()) != archive_id)
+        }))
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(io::Error::from)?;
+
+    let total_bytes = entries.iter()
+        .filter_map(|entry| entry.metadata().ok())
+        .filter(|metadata| metadata.is_file())
+        .map(|metadata| metadata.len())
+        .sum();
+    Ok(ArchiveSource { root: file_path, name, entries, total_bytes })
+}
+
+fn write_tar(
+    file: File,
+    source: &ArchiveSource,
+    format: ArchiveFormat,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    let mut builder = Builder::new(ArchiveWriter::new(file, format)?);
+    let state = append_entries(&mut builder, source, progress)?;
+
+    builder.into_inner()?.finish()?.sync_all()?;
+    Ok(state)
+}
+
+// download 把目录打包成 tar.gz 写入 writer, 用于边打包边下载
+pub fn download<W>(dir_path: &Path, denied: &dyn Fn(&Path) -> bool, writer: W) -> io::Result<W>
+    where
+        W: Write,
+{
+    let source = archive_source(None, dir_path, denied)?;
+    let mut builder = Builder::new(GzEncoder::new(writer, Compression::default()));
+    append_entries(&mut builder, &source, &mut |_| {})?;
+
+    builder.into_inner()?.finish()
+}
+
+fn append_entries<W: Write>(
+    builder: &mut Builder<W>,
+    source: &ArchiveSource,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    let mut state = ArchiveProgress { total_bytes: source.total_bytes, ..ArchiveProgress::default() };
+
+    builder.follow_symlinks(false);
+    for entry in &source.entries {
+        let metadata = entry.metadata().map_err(io::Error::from)?;
+        if metadata.file_type().is_socket() {
+            continue;
+        }
+        builder.append_path_with_name(entry.path(), source.name_of(entry))?;
+
+        state.entries += 1;
+        if metadata.is_file() {
+            state.bytes += metadata.len();
+        }
+        progress(&state);
+    }
+
+    Ok(state)
+}
+
+// extract_tar 把 tar_path 解包到 dest_path, dest_path 不存在时自动创建。
+// 包含 .. 或绝对路径的条目、指向 dest_path 之外的符号链接和硬链接都会使解包失败,
+// 解出的位置或硬链接的目标使 denied 返回 true 时同样失败。
+// 出错前已经解出的文件不会被删除。以 root 运行时保留文件的权限位和属主
+pub fn extract_tar(
+    tar_path: &Path,
+    dest_path: &Path,
+    format: ArchiveFormat,
+    denied: &dyn Fn(&Path) -> bool,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    let file = File::open(tar_path)?;
+    let mut state = ArchiveProgress { total_bytes: file.metadata()?.len(), ..ArchiveProgress::default() };
+
+    let count = Rc::new(Cell::new(0));
+    let reader = CountingReader { inner: file, count: count.clone() };
+    let reader: Box<dyn Read> = match format {
+        ArchiveFormat::Tar => Box::new(reader),
+        ArchiveFormat::TarGz => Box::new(GzDecoder::new(reader)),
+        ArchiveFormat::TarZst => Box::new(zstd::Decoder::new(reader)?),
+    };
+
+    fs::create_dir_all(dest_path)?;
+    let is_root = unsafe { libc::geteuid() } == 0;
+    let mut archive = Archive::new(reader);
+    archive.set_preserve_permissions(is_root);
+    archive.set_preserve_ownerships(is_root);
+
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let relative = safe_relative_path(&path)?;
+        check_denied(&path, &dest_path.join(&relative), denied)?;
+
+        match entry.header().entry_type() {
+            EntryType::Symlink => {
+                let target = entry.link_name()?.unwrap_or_default().into_owned();
+                check_symlink(dest_path, &relative, &target, &path)?;
+            }
+            EntryType::Link => {
+                let target = entry.link_name()?.unwrap_or_default().into_owned();
+                check_denied(&path, &dest_path.join(safe_relative_path(&target)?), denied)?;
+            }
+            _ => {}
+        }
+
+        // unpack_in 还会检查已经解出的符号链接, 不会通过它们写到 dest_path 之外
+        if !entry.unpack_in(dest_path)? {
+            return Err(unsafe_entry(&path, "outside of destination"));
+        }
+
+        state.entries += 1;
+        state.bytes = count.get();
+        progress(&state);
+    }
+
+    // 读完 tar 结尾的填充和压缩格式的结尾, gzip 在这里校验 CRC
+    io::copy(&mut archive.into_inner(), &mut io::sink())?;
+    state.bytes = count.get();
+    Ok(state)
+}
+
+// safe_relative_path 确认压缩包中的路径是不含 .. 的相对路径
+fn safe_relative_path(path: &Path) -> io::Result<PathBuf> {
+    let mut relative = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            _ => return Err(unsafe_entry(path, "absolute path or parent directory")),
+        }
+    }
+    Ok(relative)
+}
+
+// check_symlink 对照已经解出的内容解析符号链接 relative -> link, 确认它不会指向 dest 之外。
+// 经过已有的符号链接或者尚不存在的路径之后不允许再出现 .., 因为它们的指向可能被后面的项改变,
+// 例如 d -> . 之后的 d/l -> .. 按字面不超出 dest, 实际却指向 dest 的上级目录。
+// relative 的上级目录在解出时会被创建为普通目录, 不存在时按目录处理
+fn check_symlink(dest: &Path, relative: &Path, link: &Path, path: &Path) -> io::Result<()> {
+    let outside = || unsafe_entry(path, &format!("symlink points outside: {}", link.display()));
+    if link.is_absolute() {
+        return Err(outside());
+    }
+
+    let mut current = dest.to_path_buf();
+    let mut depth = 0;
+    // 已经经过了符号链接, 之后的路径无法确定
+    let mut unresolved = false;
+    for part in relative.parent().unwrap_or(Path::new("")).iter() {
+        current.push(part);
+        depth += 1;
+        if !unresolved && fs::symlink_metadata(&current).is_ok_and(|metadata| metadata.file_type().is_symlink()) {
+            unresolved = true;
+        }
+    }
+    for component in link.components() {
+        match component {
+            Component::Normal(part) => {
+                current.push(part);
+                depth += 1;
+                if !unresolved {
+                    unresolved = match fs::symlink_metadata(&current) {
+                        Ok(metadata) => metadata.file_type().is_symlink(),
+                        Err(e) if e.kind() == io::ErrorKind::NotFound => true,
+                        Err(e) => return Err(e),
+                    };
+                }
+            }
+            Component::CurDir => {}
+            Component::ParentDir if depth > 0 && !unresolved => {
+                current.pop();
+                depth -= 1;
+            }
+            _ => return Err(outside()),
+        }
+    }
+    Ok(())
+}
+
+fn check_denied(path: &Path, target: &Path, denied: &dyn Fn(&Path) -> bool) -> io::Result<()> {
+    if denied(target) {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Refusing to extract {}: access to {} is denied", path.display(), target.display()),
+        ));
+    }
+    Ok(())
+}
+
+fn unsafe_entry(path: &Path, reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Refusing to extract {}: {}", path.display(), reason))
+}
+
+// ZipEntryInfo 是 zip 包中的一项, 用于只列出内容而不解压
+#[derive(Debug, Clone, Serialize)]
+pub struct ZipEntryInfo {
+    pub name: String,
+    pub size: u64,
+    pub compressed_size: u64,
+    pub is_dir: bool,
+    pub is_symlink: bool,
+    // 不是在 unix 下创建的 zip 包没有权限位
+    pub mode: Option<String>,
+    // zip 包中的时间不带时区
+    pub mtime: Option<String>,
+    // 路径包含 .. 或者是绝对路径, 解压时会被拒绝
+    pub unsafe_path: bool,
+}
+
+// create_zip 把 src_path 打包到 dest_path, 包内路径与 create_tar 相同。
+// level 为 0 时只存储不压缩, 1~9 为 deflate 的压缩级别, 不指定时使用默认级别
+pub fn create_zip(
+    src_path: &Path,
+    dest_path: &Path,
+    level: Option<u32>,
+    denied: &dyn Fn(&Path) -> bool,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    let options = match level {
+        None => SimpleFileOptions::default().compression_method(CompressionMethod::Deflated),
+        Some(0) => SimpleFileOptions::default().compression_method(CompressionMethod::Stored),
+        Some(level @ 1..=9) => SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .compression_level(Some(i64::from(level))),
+        Some(level) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid compression level {}, expected 0-9", level)));
+        }
+    };
+
+    create_archive(dest_path, src_path, denied, |file, source| write_zip(file, source, options, progress))
+}
+
+fn write_zip(
+    file: File,
+    source: &ArchiveSource,
+    options: SimpleFileOptions,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    let mut state = ArchiveProgress { total_bytes: source.total_bytes, ..ArchiveProgress::default() };
+
+    let mut writer = ZipWriter::new(file);
+    for entry in &source.entries {
+        let metadata = entry.metadata().map_err(io::Error::from)?;
+        // zip 中的路径总是使用 /
+        let name = source.name_of(entry).to_string_lossy().into_owned();
+        let modified = Local.timestamp_opt(metadata.mtime(), 0).single()
+            .and_then(|time| zip::DateTime::try_from(time.naive_local()).ok());
+        let mut options = options
+            .unix_permissions(metadata.mode() & 0o7777)
+            .large_file(metadata.len() >= u64::from(u32::MAX));
+        if let Some(modified) = modified {
+            options = options.last_modified_time(modified);
+        }
+
+        if metadata.is_dir() {
+            writer.add_directory(name, options)?;
+        } else if metadata.is_symlink() {
+            writer.add_symlink(name, fs::read_link(entry.path())?.to_string_lossy(), options)?;
+        } else if metadata.is_file() {
+            writer.start_file(name, options)?;
+            io::copy(&mut File::open(entry.path())?, &mut writer)?;
+            state.bytes += metadata.len();
+        } else {
+            // 设备文件、fifo、socket 无法放入 zip
+            continue;
+        }
+
+        state.entries += 1;
+        progress(&state);
+    }
+
+    writer.finish()?.sync_all()?;
+    Ok(state)
+}
+
+// list_zip 列出 zip 包中的内容
+pub fn list_zip(src_path: &Path) -> io::Result<Vec<ZipEntryInfo>> {
+    let mut archive = ZipArchive::new(File::open(src_path)?)?;
+
+    let mut entries = Vec::with_capacity(archive.len());
+    for index in 0..archive.len() {
+        let file = archive.by_index_raw(index)?;
+        entries.push(ZipEntryInfo {
+            name: file.name().to_string(),
+            size: file.size(),
+            compressed_size: file.compressed_size(),
+            is_dir: file.is_dir(),
+            is_symlink: file.is_symlink(),
+            mode: file.unix_mode().map(|mode| format!("{:04o}", mode & 0o7777)),
+            mtime: file.last_modified()
+                .and_then(|time| NaiveDateTime::try_from(time).ok())
+                .map(|time| time.format("%Y-%m-%dT%H:%M:%S").to_string()),
+            unsafe_path: safe_relative_path(Path::new(file.name())).is_err(),
+        });
+    }
+    Ok(entries)
+}
+
+// extract_zip 把 src_path 解压到 dest_path, 对路径和符号链接的检查与 extract_tar 相同。
+// zip 中没有属主, 解压出的文件都属于 agent 的用户, 与 unzip 一样去掉 setuid/setgid/sticky 位。
+// 进度按压缩后的大小计算
+pub fn extract_zip(
+    src_path: &Path,
+    dest_path: &Path,
+    denied: &dyn Fn(&Path) -> bool,
+    progress: &mut dyn FnMut(&ArchiveProgress),
+) -> io::Result<ArchiveProgress> {
+    let mut archive = ZipArchive::new(File::open(src_path)?)?;
+    let mut state = ArchiveProgress::default();
+    for index in 0..archive.len() {
+        state.total_bytes += archive.by_index_raw(index)?.compressed_size();
+    }
+
+    fs::create_dir_all(dest_path)?;
+    let root = dest_path.canonicalize()?;
+
+    for index in 0..archive.len() {
+        let mut file = archive.by_index(index)?;
+        let path = PathBuf::from(file.name());
+        let relative = safe_relative_path(&path)?;
+        let target = root.join(&relative);
+        check_denied(&path, &target, denied)?;
+
+        // 上级目录中不能有指向 dest_path 之外的符号链接
+        let parent = target.parent().unwrap_or(&root);
+        fs::create_dir_all(parent)?;
+        if !parent.canonicalize()?.starts_with(&root) {
+            return Err(unsafe_entry(&path, "outside of destination"));
+        }
+        // 与 tar 一样先删除已存在的文件, 避免通过已有的符号链接写到别处
+        if fs::symlink_metadata(&target).is_ok_and(|metadata| !metadata.is_dir()) {
+            fs::remove_file(&target)?;
+        }
+
+        if file.is_dir() {
+            fs::create_dir_all(&target)?;
+        } else if file.is_symlink() {
+            let mut link = String::new();
+            file.read_to_string(&mut link)?;
+            let link = PathBuf::from(link);
+            check_symlink(&root, &relative, &link, &path)?;
+            std::os::unix::fs::symlink(&link, &target)?;
+        } else {
+            io::copy(&mut file, &mut File::create(&target)?)?;
+        }
+
+        if let Some(mode) = file.unix_mode().filter(|_| !file.is_symlink()) {
+            fs::set_permissions(&target, fs::Permissions::from_mode(mode & 0o777))?;
+        }
+
+        state.entries += 1;
+        state.bytes += file.compressed_size();
+        progress(&state);
+    }
+
+    Ok(state)
+}
+
+// UploadResult 是完成上传后的文件信息
+#[derive(Debug, Clone, Serialize)]
+pub struct UploadResult {
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+}
+
+// upload_temp_path 返回上传过程中使用的临时文件, 与目标文件在同一目录下, 保证最后的 rename 是原子的
+pub fn upload_temp_path(path: &Path) -> io::Result<PathBuf> {
+    temp_path(path, "upload")
+}
+
+// temp_path 返回与 path 在同一目录下的隐藏临时文件 .<name>.<suffix>
+fn temp_path(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid path {}", path.display())))?;
+    let mut temp = std::ffi::OsString::from(".");
+    temp.push(name);
+    temp.push(".");
+    temp.push(suffix);
+    Ok(path.with_file_name(temp))
+}
+
+static TEMP_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+// unique_temp_path 返回 .<name>.<pid>.<序号>.<随机数>.<suffix>, 同一进程中并发的请求不会使用同一个临时文件,
+// 随机数使其他用户无法预先猜到文件名并提前创建
+fn unique_temp_path(path: &Path, suffix: &str) -> io::Result<PathBuf> {
+    let sequence = TEMP_SEQUENCE.fetch_add(1, Ordering::Relaxed);
+    let mut random = [0u8; 8];
+    if unsafe { libc::getrandom(random.as_mut_ptr().cast(), random.len(), 0) } != random.len() as isize {
+        return Err(io::Error::last_os_error());
+    }
+    let random = u64::from_ne_bytes(random);
+    temp_path(path, &format!("{}.{}.{:016x}.{}", std::process::id(), sequence, random, suffix))
+}
+
+// upload_received 返回已经收到的字节数, 用于断点续传
+pub fn upload_received(path: &Path) -> io::Result<u64> {
+    let temp = upload_temp_path(path)?;
+    match fs::symlink_metadata(&temp) {
+        Ok(metadata) => {
+            check_upload_temp(&temp, &metadata)?;
+            Ok(metadata.len())
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e),
+    }
+}
+
+// open_upload_temp 打开或创建上传的临时文件用于续传。临时文件名是可预测的,
+// 不跟随符号链接, 新建时权限为 0600, 已存在时必须是 agent 自己创建的普通文件
+pub fn open_upload_temp(path: &Path) -> io::Result<File> {
+    let temp = upload_temp_path(path)?;
+    if let Ok(metadata) = fs::symlink_metadata(&temp) {
+        check_upload_temp(&temp, &metadata)?;
+    }
+
+    let file = File::options()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .custom_flags(libc::O_NOFOLLOW)
+        .open(&temp)
+        .map_err(|e| match e.raw_os_error() {
+            Some(libc::ELOOP) => not_regular_upload(&temp),
+            _ => e,
+        })?;
+    // lstat 和 open 之间文件可能被替换, 再检查一次打开的文件
+    check_upload_temp(&temp, &file.metadata()?)?;
+    Ok(file)
+}
+
+// check_upload_temp 确认临时文件是 agent 自己的普通文件, 没有指向其他文件的硬链接
+fn check_upload_temp(temp: &Path, metadata: &fs::Metadata) -> io::Result<()> {
+    if !metadata.file_type().is_file() || metadata.nlink() > 1 || metadata.uid() != unsafe { libc::geteuid() } {
+        return Err(not_regular_upload(temp));
     }
     Ok(())
 }
 
-fn create_tar(tar_path: &str, file_path: &str) {
-    // TODO
+fn not_regular_upload(temp: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("Upload temp file {} is not a regular file owned by the agent", temp.display()),
+    )
+}
+
+// upload_file 校验临时文件的 SHA-256, 设置权限和属主后 rename 到 path。
+// 没有指定 mode 时沿用已存在的目标文件的权限, 以 root 运行时属主也是如此。校验失败时删除临时文件
+pub fn upload_file(
+    path: &Path,
+    sha256: &str,
+    mode: Option<u32>,
+    uid: Option<u32>,
+    gid: Option<u32>,
+) -> io::Result<UploadResult> {
+    let temp = upload_temp_path(path)?;
+    let mut file = File::options().read(true).custom_flags(libc::O_NOFOLLOW).open(&temp)
+        .map_err(|e| match e.raw_os_error() {
+            Some(libc::ELOOP) => not_regular_upload(&temp),
+            _ => e,
+        })?;
+    let opened = file.metadata()?;
+    check_upload_temp(&temp, &opened)?;
+
+    let (size, digest) = sha256_of(&mut file)?;
+    if !digest.eq_ignore_ascii_case(sha256.trim()) {
+        fs::remove_file(&temp)?;
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("SHA-256 mismatch for {}: expected {}, got {}", path.display(), sha256.trim(), digest),
+        ));
+    }
+
+    let existing = fs::metadata(path).ok();
+    let mode = mode.or_else(|| existing.as_ref().map(|metadata| metadata.mode() & 0o7777));
+    // 只有 root 才能把属主改成其他用户
+    let owner = existing.as_ref().filter(|_| unsafe { libc::geteuid() } == 0);
+    let uid = uid.or_else(|| owner.map(|metadata| metadata.uid()));
+    let gid = gid.or_else(|| owner.map(|metadata| metadata.gid()));
+    // 通过打开的文件修改, 不经过路径; 先改属主, chown 会清除 setuid 位
+    if uid.is_some() || gid.is_some() {
+        std::os::unix::fs::fchown(&file, uid, gid)?;
+    }
+    if let Some(mode) = mode {
+        file.set_permissions(fs::Permissions::from_mode(mode))?;
+    }
+
+    file.sync_all()?;
+    // rename 操作的是路径, 确认路径仍然是校验过的那个普通文件
+    let current = fs::symlink_metadata(&temp)?;
+    if !current.file_type().is_file() || (current.dev(), current.ino()) != (opened.dev(), opened.ino()) {
+        return Err(not_regular_upload(&temp));
+    }
+    fs::rename(&temp, path)?;
+    // rename 本身也需要落盘
+    sync_parent(path)?;
+
+    let metadata = fs::metadata(path)?;
+    Ok(UploadResult {
+        path: path.to_path_buf(),
+        size,
+        sha256: digest,
+        mode: format!("{:04o}", metadata.mode() & 0o7777),
+        uid: metadata.uid(),
+        gid: metadata.gid(),
+    })
+}
+
+// sha256_of 读取全部内容, 返回字节数和十六进制的 SHA-256
+pub fn sha256_of<R: Read>(reader: &mut R) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(reader, &mut hasher)?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+// file_etag 由 inode、修改时间和大小生成文件的 ETag, 下载和写入使用同一种格式。
+// 文件系统的时间精度有限, 原子写入和大多数编辑器保存时会换一个 inode, 所以加上 inode
+pub fn file_etag(metadata: &fs::Metadata) -> String {
+    format!("\"{:x}-{:x}-{:x}-{:x}\"", metadata.ino(), metadata.mtime(), metadata.mtime_nsec(), metadata.len())
+}
+
+// WriteOptions 是 write_file 的参数, 条件都为空时直接覆盖
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    // 与 If-Match 相同, 当前文件的 ETag 必须是其中之一, * 表示文件必须存在
+    pub if_match: Option<Vec<String>>,
+    // 与 If-None-Match 相同, 当前文件的 ETag 不能是其中之一, * 表示文件必须不存在
+    pub if_none_match: Option<Vec<String>>,
+    // 当前文件的修改时间, Unix 时间戳
+    pub mtime: Option<i64>,
+    // 当前文件内容的 SHA-256
+    pub sha256: Option<String>,
+    // 是否把修改前的文件保留为 <name>.<时间>.bak
+    pub backup: bool,
+}
+
+impl WriteOptions {
+    // check 检查当前文件是否满足条件, metadata 为空表示文件不存在
+    fn check(&self, path: &Path, metadata: Option<&fs::Metadata>) -> io::Result<()> {
+        let failed = |reason: String| Err(io::Error::other(PreconditionFailed(format!("{} {}", path.display(), reason))));
+        let matches = |tags: &[String], etag: &str| tags.iter()
+            .map(|tag| tag.trim())
+            .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag);
+
+        let Some(metadata) = metadata else {
+            if self.if_match.is_some() || self.mtime.is_some() || self.sha256.is_some() {
+                return failed("does not exist".to_string());
+            }
+            return Ok(());
+        };
+
+        let etag = file_etag(metadata);
+        if self.if_none_match.as_ref().is_some_and(|tags| matches(tags, &etag)) {
+            return failed(format!("already exists with ETag {}", etag));
+        }
+        if self.if_match.as_ref().is_some_and(|tags| !matches(tags, &etag)) {
+            return failed(format!("has been modified, current ETag is {}", etag));
+        }
+        if self.mtime.is_some_and(|mtime| mtime != metadata.mtime()) {
+            return failed(format!("has been modified, current mtime is {}", metadata.mtime()));
+        }
+        if let Some(sha256) = &self.sha256 {
+            let (_, digest) = sha256_of(&mut File::open(path)?)?;
+            if !digest.eq_ignore_ascii_case(sha256.trim()) {
+                return failed(format!("has been modified, current SHA-256 is {}", digest));
+            }
+        }
+        Ok(())
+    }
 }
 
-fn extract_tar(tar_path: &str, dest_path: &str)  {
-    // TODO
+// WriteResult 是写入后的文件信息
+#[derive(Debug, Clone, Serialize)]
+pub struct WriteResult {
+    // path 是符号链接时为链接指向的文件
+    pub path: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+    pub etag: String,
+    // 文件原来不存在时为 true
+    pub created: bool,
+    // 修改前的文件, 没有备份时为空
+    pub backup: Option<PathBuf>,
 }
 
-fn create_zip(src_path: &Path, dest_path: &Path) {
-    // TODO
+// 同一进程中的写入在检查条件和 rename 之间互斥, 避免两个请求基于同一版本修改时都成功
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+// write_file 原子地写入文件: 内容先写入同一目录下的临时文件并 fsync, 再检查条件、备份, 最后 rename 替换。
+// 已存在的文件保留原来的权限, 以 root 运行时也保留属主。任何一步失败时原文件都不会被修改
+pub fn write_file(path: &Path, content: &[u8], options: &WriteOptions) -> io::Result<WriteResult> {
+    // 写入符号链接指向的文件, 而不是把链接替换成普通文件
+    let path = match fs::canonicalize(path) {
+        Ok(target) => target,
+        Err(e) if e.kind() == io::ErrorKind::NotFound && fs::symlink_metadata(path).is_ok() => {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a dangling symlink", path.display())));
+        }
+        Err(e) if e.kind() == io::ErrorKind::NotFound => path.to_path_buf(),
+        Err(e) => return Err(e),
+    };
+    let existing = || match fs::metadata(&path) {
+        Ok(metadata) if !metadata.is_file() => {
+            Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a regular file", path.display())))
+        }
+        Ok(metadata) => Ok(Some(metadata)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    };
+    // 先检查一次, 不满足条件时不用写临时文件
+    options.check(&path, existing()?.as_ref())?;
+
+    let temp = unique_temp_path(&path, "write")?;
+    let written = (|| {
+        // 写入过程中临时文件的权限为 0600, 替换 0600 的文件时其他用户也无法读到新内容
+        let mut file = File::options().write(true).create_new(true).mode(0o600).open(&temp)?;
+        file.write_all(content)?;
+
+        let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        let existing = existing()?;
+        options.check(&path, existing.as_ref())?;
+
+        let mut backup = None;
+        if let Some(metadata) = &existing {
+            // 只有 root 才能把属主改成其他用户, 先改属主, chown 会清除 setuid 位
+            if unsafe { libc::geteuid() } == 0 {
+                std::os::unix::fs::fchown(&file, Some(metadata.uid()), Some(metadata.gid()))?;
+            }
+            file.set_permissions(fs::Permissions::from_mode(metadata.mode() & 0o7777))?;
+            if options.backup {
+                backup = Some(backup_file(&path, metadata)?);
+            }
+        } else {
+            // 新文件与直接创建时一样使用 0666 去掉 umask 后的权限
+            file.set_permissions(fs::Permissions::from_mode(0o666 & !process_umask()))?;
+        }
+
+        file.sync_all()?;
+        fs::rename(&temp, &path)?;
+        sync_parent(&path)?;
+        let metadata = file.metadata()?;
+        Ok(WriteResult {
+            path: path.clone(),
+            size: metadata.len(),
+            sha256: format!("{:x}", Sha256::digest(content)),
+            etag: file_etag(&metadata),
+            created: existing.is_none(),
+            backup,
+        })
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
 }
 
-fn extract_zip(src_path: &Path, dest_path: &Path){
-    // TODO
+// process_umask 从 /proc/self/status 读取当前的 umask, 调用 umask 读取会短暂修改它, 影响其他线程创建的文件
+fn process_umask() -> u32 {
+    fs::read_to_string("/proc/self/status")
+        .ok()
+        .and_then(|status| status.lines()
+            .find_map(|line| line.strip_prefix("Umask:"))
+            .and_then(|umask| u32::from_str_radix(umask.trim(), 8).ok()))
+        .unwrap_or(0o022)
 }
 
-fn upload_file(filepath: &Path, remote_path: &str) {
-    // TODO
+// backup_file 把当前文件保留为 <name>.<时间>.bak, 优先使用硬链接, 原文件被 rename 替换后备份就是修改前的内容
+fn backup_file(path: &Path, metadata: &fs::Metadata) -> io::Result<PathBuf> {
+    let name = path.file_name()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid path {}", path.display())))?;
+    let timestamp = Local::now().format("%Y%m%d%H%M%S");
+
+    let mut attempt = 0;
+    loop {
+        let mut backup = name.to_os_string();
+        match attempt {
+            0 => backup.push(format!(".{}.bak", timestamp)),
+            _ => backup.push(format!(".{}-{}.bak", timestamp, attempt)),
+        }
+        let backup = path.with_file_name(backup);
+        match fs::hard_link(path, &backup) {
+            Ok(()) => return Ok(backup),
+            // 同一秒内多次写入
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => attempt += 1,
+            // 文件系统不支持硬链接等情况下改为复制
+            Err(_) if !backup.exists() => {
+                copy_regular(path, &backup, metadata)?;
+                return Ok(backup);
+            }
+            Err(e) => return Err(e),
+        }
+    }
 }
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, File};
+    use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+    use std::path::Path;
+
+    use std::io::Write;
+
+    use tar::{Builder, EntryType, Header};
+    use zip::ZipWriter;
+    use zip::write::SimpleFileOptions;
+
+    use super::{ArchiveFormat, copy_file, create_tar, create_zip, extract_tar, extract_zip, IfExists, list_zip, move_file, TransferAction, unique_temp_path, write_file, WriteOptions};
+
+    // raw_tar 直接写入头部的 name 和 linkname 字段, 绕过 tar::Builder 对路径的检查
+    fn raw_tar(path: &Path, name: &str, entry_type: EntryType, link: &str) {
+        let mut header = Header::new_old();
+        header.as_old_mut().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.as_old_mut().linkname[..link.len()].copy_from_slice(link.as_bytes());
+        header.set_entry_type(entry_type);
+        header.set_mode(0o644);
+        let data = if entry_type == EntryType::Regular { &b"evil"[..] } else { &b""[..] };
+        header.set_size(data.len() as u64);
+        header.set_cksum();
+
+        let mut builder = Builder::new(File::create(path).unwrap());
+        builder.append(&header, data).unwrap();
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn archives_round_trip_in_every_format() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("data");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "hello").unwrap();
+        fs::write(src.join("sub").join("b.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(src.join("sub").join("b.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("../a.txt", src.join("sub").join("link")).unwrap();
+
+        for (name, format) in [("out.tar", ArchiveFormat::Tar), ("out.tgz", ArchiveFormat::TarGz), ("out.tar.zst", ArchiveFormat::TarZst)] {
+            assert_eq!(ArchiveFormat::from_path(Path::new(name)), Some(format));
+            let archive = dir.path().join(name);
+            let mut reports = 0;
+            let created = create_tar(&archive, &src, format, &|_| false, &mut |_| reports += 1).unwrap();
+            assert_eq!(created.entries, 5);
+            assert_eq!(created.bytes, created.total_bytes);
+            assert_eq!(reports, 5);
+            assert!(create_tar(&archive, &src, format, &|_| false, &mut |_| {}).is_err());
+
+            let dest = dir.path().join(format!("{}.d", name));
+            let extracted = extract_tar(&archive, &dest, format, &|_| false, &mut |_| {}).unwrap();
+            assert_eq!(extracted.entries, 5);
+            assert_eq!(extracted.bytes, fs::metadata(&archive).unwrap().len());
+            assert_eq!(fs::read_to_string(dest.join("data/sub/link")).unwrap(), "hello");
+            let mode = fs::metadata(dest.join("data/sub/b.sh")).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o755);
+        }
+    }
+
+    #[test]
+    fn extraction_rejects_paths_outside_destination() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        let cases = [
+            ("../evil.txt", EntryType::Regular, ""),
+            ("/tmp/evil.txt", EntryType::Regular, ""),
+            ("link", EntryType::Symlink, "/etc"),
+            ("sub/link", EntryType::Symlink, "../../outside"),
+            ("hard", EntryType::Link, "../outside"),
+        ];
+
+        for (name, entry_type, link) in cases {
+            let archive = dir.path().join("evil.tar");
+            raw_tar(&archive, name, entry_type, link);
+            let e = extract_tar(&archive, &dest, ArchiveFormat::Tar, &|_| false, &mut |_| {}).unwrap_err();
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidData, "{}", name);
+        }
+        assert!(!dir.path().join("evil.txt").exists());
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+    }
 
-fn download() {
-    // TODO
-}
\ No newline at end of file
+    #[test]
+    fn extraction_rejects_chained_symlinks() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        let archive = dir.path().join("chain.tar");
+        let mut builder = Builder::new(File::create(&archive).unwrap());
+        for (name, link) in [("d", "."), ("d/l", "..")] {
+            let mut header = Header::new_gnu();
+            header.set_entry_type(EntryType::Symlink);
+            header.set_size(0);
+            header.set_mode(0o777);
+            header.set_uid(0);
+            header.set_gid(0);
+            header.set_mtime(0);
+            builder.append_link(&mut header, name, link).unwrap();
+        }
+        builder.finish().unwrap();
+
+        // d/l 按字面是 dest 本身, 但 d 指向 dest, 实际是 dest 的上级目录
+        let e = extract_tar(&archive, &dest, ArchiveFormat::Tar, &|_| false, &mut |_| {}).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(fs::symlink_metadata(dest.join("l")).is_err());
+    }
+
+    #[test]
+    fn zip_round_trip_and_listing() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("data");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "hello hello hello hello").unwrap();
+        fs::write(src.join("sub").join("b.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(src.join("sub").join("b.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+        symlink("../a.txt", src.join("sub").join("link")).unwrap();
+
+        let archive = dir.path().join("out.zip");
+        assert!(create_zip(&src, &archive, Some(10), &|_| false, &mut |_| {}).is_err());
+        let created = create_zip(&src, &archive, Some(9), &|_| false, &mut |_| {}).unwrap();
+        assert_eq!(created.entries, 5);
+        assert_eq!(created.bytes, created.total_bytes);
+
+        let entries = list_zip(&archive).unwrap();
+        let script = entries.iter().find(|entry| entry.name == "data/sub/b.sh").unwrap();
+        assert_eq!(script.mode.as_deref(), Some("0755"));
+        assert!(entries.iter().any(|entry| entry.name == "data/sub/link" && entry.is_symlink));
+        assert!(entries.iter().all(|entry| !entry.unsafe_path));
+
+        let dest = dir.path().join("dest");
+        let extracted = extract_zip(&archive, &dest, &|_| false, &mut |_| {}).unwrap();
+        assert_eq!(extracted.entries, 5);
+        assert_eq!(fs::read_to_string(dest.join("data/sub/link")).unwrap(), "hello hello hello hello");
+        let mode = fs::metadata(dest.join("data/sub/b.sh")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o755);
+    }
+
+    #[test]
+    fn unzip_drops_setuid_bits() {
+        let dir = tempfile::tempdir().unwrap();
+        let archive = dir.path().join("suid.zip");
+        let mut writer = zip::ZipWriter::new(fs::File::create(&archive).unwrap());
+        let options = zip::write::SimpleFileOptions::default().unix_permissions(0o6755);
+        writer.start_file("suid", options).unwrap();
+        writer.write_all(b"#!/bin/sh\n").unwrap();
+        writer.finish().unwrap();
+
+        let dest = dir.path().join("dest");
+        extract_zip(&archive, &dest, &|_| false, &mut |_| {}).unwrap();
+        let mode = fs::metadata(dest.join("suid")).unwrap().permissions().mode();
+        assert_eq!(mode & 0o7777, 0o755);
+    }
+
+    #[test]
+    fn unzip_rejects_zip_slip() {
+        let dir = tempfile::tempdir().unwrap();
+        let dest = dir.path().join("dest");
+        let options = SimpleFileOptions::default();
+
+        for name in ["../evil.txt", "/tmp/evil.txt", "a/../../evil.txt"] {
+            let archive = dir.path().join("evil.zip");
+            let mut writer = ZipWriter::new(File::create(&archive).unwrap());
+            writer.start_file(name, options).unwrap();
+            writer.write_all(b"evil").unwrap();
+            writer.finish().unwrap();
+
+            assert!(list_zip(&archive).unwrap()[0].unsafe_path);
+            let e = extract_zip(&archive, &dest, &|_| false, &mut |_| {}).unwrap_err();
+            assert_eq!(e.kind(), std::io::ErrorKind::InvalidData, "{}", name);
+        }
+
+        let archive = dir.path().join("link.zip");
+        let mut writer = ZipWriter::new(File::create(&archive).unwrap());
+        writer.add_symlink("link", "../outside", options).unwrap();
+        writer.finish().unwrap();
+        assert!(extract_zip(&archive, &dest, &|_| false, &mut |_| {}).is_err());
+        assert!(!dir.path().join("evil.txt").exists());
+        assert_eq!(fs::read_dir(&dest).unwrap().count(), 0);
+
+        // d/l 按字面是 dest 本身, 但 d 指向 dest, 实际是 dest 的上级目录
+        let archive = dir.path().join("chain.zip");
+        let mut writer = ZipWriter::new(File::create(&archive).unwrap());
+        writer.add_symlink("d", ".", options).unwrap();
+        writer.add_symlink("d/l", "..", options).unwrap();
+        writer.finish().unwrap();
+        let e = extract_zip(&archive, &dest, &|_| false, &mut |_| {}).unwrap_err();
+        assert_eq!(e.kind(), std::io::ErrorKind::InvalidData);
+        assert!(fs::symlink_metadata(dest.join("l")).is_err());
+    }
+
+    #[test]
+    fn copy_and_move_follow_if_exists() {
+        let dir = tempfile::tempdir().unwrap();
+        let src = dir.path().join("src");
+        fs::create_dir_all(src.join("sub")).unwrap();
+        fs::write(src.join("a.txt"), "new").unwrap();
+        fs::write(src.join("sub").join("b.sh"), "#!/bin/sh\n").unwrap();
+        fs::set_permissions(src.join("sub").join("b.sh"), fs::Permissions::from_mode(0o751)).unwrap();
+        symlink("../a.txt", src.join("sub").join("link")).unwrap();
+        fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o500)).unwrap();
+
+        let copy = dir.path().join("copy");
+        let copied = copy_file(&src, &copy, IfExists::Fail, &|_| false).unwrap();
+        assert_eq!((copied.files, copied.dirs, copied.symlinks, copied.bytes), (2, 2, 1, 13));
+        assert_eq!(fs::read_link(copy.join("sub/link")).unwrap(), Path::new("../a.txt"));
+        for name in ["sub", "sub/b.sh"] {
+            let (original, copied) = (fs::metadata(src.join(name)).unwrap(), fs::metadata(copy.join(name)).unwrap());
+            assert_eq!(original.mode(), copied.mode());
+            assert_eq!((original.mtime(), original.mtime_nsec()), (copied.mtime(), copied.mtime_nsec()));
+        }
+        assert!(copy_file(&src, &src.join("sub/inner"), IfExists::Overwrite, &|_| false).is_err());
+
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "old").unwrap();
+        let err = copy_file(&src.join("a.txt"), &file, IfExists::Fail, &|_| false).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::AlreadyExists);
+        let skipped = copy_file(&src.join("a.txt"), &file, IfExists::Skip, &|_| false).unwrap();
+        assert_eq!(skipped.action, TransferAction::Skipped);
+        assert_eq!(fs::read_to_string(&file).unwrap(), "old");
+        let overwritten = copy_file(&src.join("a.txt"), &file, IfExists::Overwrite, &|_| false).unwrap();
+        assert_eq!((overwritten.files, overwritten.overwritten), (1, 1));
+        assert_eq!(fs::read_to_string(&file).unwrap(), "new");
+
+        // 合并到已存在的目录: skip 只补上缺少的文件, denied 中的文件不复制
+        fs::write(copy.join("a.txt"), "kept").unwrap();
+        fs::remove_file(copy.join("sub/link")).unwrap();
+        let merged = copy_file(&src, &copy, IfExists::Skip, &|path| path.ends_with("b.sh")).unwrap();
+        assert_eq!(merged.skipped, vec![copy.join("a.txt")]);
+        assert_eq!((merged.files, merged.symlinks), (0, 1));
+        assert_eq!(fs::read_to_string(copy.join("a.txt")).unwrap(), "kept");
+
+        let moved = move_file(&copy, &dir.path().join("moved"), IfExists::Fail, &|_| false).unwrap();
+        assert_eq!((moved.action, moved.cross_device, moved.files), (TransferAction::Moved, false, 2));
+        assert!(!copy.exists());
+        assert!(move_file(&dir.path().join("moved"), &src, IfExists::Skip, &|_| false).unwrap().skipped.len() == 1);
+
+        // 目标是非空目录时 rename 失败, 改为合并后删除源目录
+        fs::set_permissions(src.join("sub"), fs::Permissions::from_mode(0o700)).unwrap();
+        fs::write(src.join("a.txt"), "replaced").unwrap();
+        let merged = move_file(&src, &dir.path().join("moved"), IfExists::Overwrite, &|_| false).unwrap();
+        assert_eq!((merged.action, merged.overwritten), (TransferAction::Moved, 3));
+        assert!(!src.exists());
+        assert_eq!(fs::read_to_string(dir.path().join("moved/a.txt")).unwrap(), "replaced");
+
+        let denied = move_file(&dir.path().join("moved"), &src, IfExists::Fail, &|path| path.ends_with("b.sh")).unwrap_err();
+        assert_eq!(denied.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    fn copy_does_not_follow_planted_temp_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (src, dest, victim) = (dir.path().join("secret"), dir.path().join("copy"), dir.path().join("victim"));
+        fs::write(&src, "secret").unwrap();
+        fs::set_permissions(&src, fs::Permissions::from_mode(0o600)).unwrap();
+        fs::write(&victim, "victim").unwrap();
+        // 旧的可预测临时文件名上放置的符号链接不会被写入
+        symlink(&victim, dir.path().join(".copy.copy")).unwrap();
+
+        copy_file(&src, &dest, IfExists::Fail, &|_| false).unwrap();
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "secret");
+        assert_eq!(fs::metadata(&dest).unwrap().permissions().mode() & 0o777, 0o600);
+        assert_eq!(fs::read_to_string(&victim).unwrap(), "victim");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 4);
+    }
+
+    #[test]
+    fn temp_names_are_not_predictable() {
+        let path = Path::new("/data/app.conf");
+        let (a, b) = (unique_temp_path(path, "copy").unwrap(), unique_temp_path(path, "copy").unwrap());
+        assert_ne!(a, b);
+        assert!(a.file_name().unwrap().to_string_lossy().starts_with(".app.conf."));
+        // 去掉序号后仍然不同
+        let strip = |path: &Path| path.file_name().unwrap().to_string_lossy().split('.').nth(5).unwrap().to_string();
+        assert_ne!(strip(&a), strip(&b));
+    }
+
+    #[test]
+    fn write_file_checks_preconditions_and_keeps_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("my.cnf");
+        let options = WriteOptions { if_none_match: Some(vec!["*".to_string()]), ..Default::default() };
+        let created = write_file(&path, b"[mysqld]\n", &options).unwrap();
+        assert!(created.created && created.backup.is_none());
+        // 新文件的权限与直接创建时相同
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o666 & !super::process_umask());
+        assert!(write_file(&path, b"again", &options).is_err());
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o640)).unwrap();
+
+        // 通过符号链接写入时替换的是链接指向的文件
+        let link = dir.path().join("link.cnf");
+        symlink(&path, &link).unwrap();
+        let etag = super::file_etag(&fs::metadata(&path).unwrap());
+        let options = WriteOptions { if_match: Some(vec![etag.clone()]), backup: true, ..Default::default() };
+        let written = write_file(&link, b"[mysqld]\nport=3307\n", &options).unwrap();
+        assert_eq!(written.path, path.canonicalize().unwrap());
+        assert!(fs::symlink_metadata(&link).unwrap().file_type().is_symlink());
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o640);
+        let backup = written.backup.unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), "[mysqld]\n");
+        assert!(backup.file_name().unwrap().to_str().unwrap().ends_with(".bak"));
+
+        // 基于旧版本的修改被拒绝, 文件内容不变, 也不会留下临时文件
+        let err = write_file(&path, b"stale", &options).unwrap_err();
+        assert!(err.get_ref().unwrap().is::<crate::error::PreconditionFailed>());
+        let options = WriteOptions { sha256: Some(created.sha256), ..Default::default() };
+        assert!(write_file(&path, b"stale", &options).is_err());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[mysqld]\nport=3307\n");
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        let options = WriteOptions { sha256: Some(written.sha256), backup: true, ..Default::default() };
+        let again = write_file(&path, b"[mysqld]\n", &options).unwrap();
+        assert_ne!(again.backup.unwrap(), backup);
+    }
+}
@@ -0,0 +1,544 @@
+use std::ffi::CString;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+
+use nix::fcntl::AtFlags;
+use nix::sys::stat::{fchmodat, FchmodatFlags, Mode};
+use nix::unistd::{fchownat, Gid, Uid};
+use serde::Serialize;
+use walkdir::WalkDir;
+
+use crate::hand::node::user::{get_gid_by_groupname, get_groupname_by_gid, get_uid_by_username, get_username_by_uid};
+
+// 每一类用户对应的 setuid/setgid/sticky 位和 rwx 所在的位移
+const CLASSES: [(char, u32, u32); 3] = [('u', 0o4000, 6), ('g', 0o2000, 3), ('o', 0o1000, 0)];
+
+// ModeSpec 是 chmod 的权限参数, 八进制如 0755, 或符号形式如 u+x,go-w、a=rX、g=u。
+// 符号形式省略用户类别时等同于 a, 与 chmod 命令不同, 不受 umask 影响
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModeSpec {
+    Octal(u32),
+    Symbolic(Vec<ModeClause>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModeClause {
+    // u、g、o 的组合
+    who: String,
+    // 操作符 +、-、= 和对应的权限字母
+    actions: Vec<(char, String)>,
+}
+
+impl ModeSpec {
+    pub fn parse(spec: &str) -> io::Result<Self> {
+        let invalid = || io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid mode {:?}", spec));
+
+        if !spec.is_empty() && spec.len() <= 4 && spec.chars().all(|c| c.is_digit(8)) {
+            return u32::from_str_radix(spec, 8).map(ModeSpec::Octal).map_err(|_| invalid());
+        }
+
+        let mut clauses = Vec::new();
+        for clause in spec.split(',') {
+            let split = clause.find(['+', '-', '=']).ok_or_else(invalid)?;
+            let (who, mut rest) = clause.split_at(split);
+            if !who.chars().all(|c| "ugoa".contains(c)) {
+                return Err(invalid());
+            }
+            let who = if who.is_empty() || who.contains('a') { "ugo".to_string() } else { who.to_string() };
+
+            let mut actions = Vec::new();
+            while let Some(op) = rest.chars().next() {
+                let perms = &rest[1..];
+                let end = perms.find(['+', '-', '=']).unwrap_or(perms.len());
+                let perms = &perms[..end];
+                // 权限字母只能是 rwxXst, 或者一个 u、g、o 表示复制该类用户的权限
+                let copy = perms.len() == 1 && "ugo".contains(perms);
+                if !copy && !perms.chars().all(|c| "rwxXst".contains(c)) {
+                    return Err(invalid());
+                }
+                actions.push((op, perms.to_string()));
+                rest = &rest[1 + end..];
+            }
+            clauses.push(ModeClause { who, actions });
+        }
+        Ok(ModeSpec::Symbolic(clauses))
+    }
+
+    // apply 返回修改后的权限位, mode 为修改前的权限位
+    pub fn apply(&self, mode: u32, is_dir: bool) -> u32 {
+        let clauses = match self {
+            ModeSpec::Octal(mode) => return *mode,
+            ModeSpec::Symbolic(clauses) => clauses,
+        };
+
+        let mut mode = mode & 0o7777;
+        for clause in clauses {
+            for (op, perms) in &clause.actions {
+                let mut bits = 0;
+                let mut affected = 0;
+                for &(class, special, shift) in CLASSES.iter().filter(|(class, _, _)| clause.who.contains(*class)) {
+                    affected |= 0o7 << shift;
+                    // = 会清除 setuid/setgid 位, sticky 位只有 o 类包含
+                    affected |= special;
+                    let rwx = match CLASSES.iter().find(|(source, _, _)| perms.starts_with(*source)) {
+                        Some(&(_, _, source)) => (mode >> source) & 0o7,
+                        None => perms.chars().fold(0, |rwx, c| match c {
+                            'r' => rwx | 0o4,
+                            'w' => rwx | 0o2,
+                            'x' => rwx | 0o1,
+                            // X 只给目录和已经有执行权限的文件加上执行权限
+                            'X' if is_dir || mode & 0o111 != 0 => rwx | 0o1,
+                            _ => rwx,
+                        }),
+                    };
+                    bits |= rwx << shift;
+                    if (perms.contains('s') && class != 'o') || (perms.contains('t') && class == 'o') {
+                        bits |= special;
+                    }
+                }
+                mode = match op {
+                    '+' => mode | bits,
+                    '-' => mode & !bits,
+                    _ => (mode & !affected) | bits,
+                };
+            }
+        }
+        mode
+    }
+}
+
+// chmod 修改权限, 返回实际修改的文件数。
+// recursive 时递归修改目录中的文件, 其中的符号链接和 denied 中的文件不修改
+pub fn chmod(path: &Path, spec: &ModeSpec, recursive: bool, denied: &dyn Fn(&Path) -> bool) -> io::Result<u64> {
+    let mut changed = 0;
+    for target in targets(path, recursive, denied) {
+        let (target, follow) = target?;
+        if chmod_entry(&target, spec, follow)? {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+// chmod_entry 先用 O_PATH 打开文件, 再通过 /proc/self/fd 修改打开的这个文件。
+// follow 为 false 时不跟随符号链接, 遍历之后文件被换成符号链接时跳过, 不会修改链接指向的文件。
+// O_PATH 打开的 fd 不能直接 fchmod, 但不需要文件的读权限, 打开 FIFO 和设备文件也没有副作用
+fn chmod_entry(path: &Path, spec: &ModeSpec, follow: bool) -> io::Result<bool> {
+    let flags = if follow { libc::O_PATH } else { libc::O_PATH | libc::O_NOFOLLOW };
+    let file = fs::File::options().read(true).custom_flags(flags).open(path)?;
+    let metadata = file.metadata()?;
+    if metadata.file_type().is_symlink() {
+        return Ok(false);
+    }
+    let mode = spec.apply(metadata.mode(), metadata.is_dir());
+    if mode == metadata.mode() & 0o7777 {
+        return Ok(false);
+    }
+    let fd_path = PathBuf::from(format!("/proc/self/fd/{}", file.as_raw_fd()));
+    fchmodat(None, &fd_path, Mode::from_bits_truncate(mode), FchmodatFlags::FollowSymlink)?;
+    Ok(true)
+}
+
+// chown 修改属主和属组, 为 None 的不修改, 返回实际修改的文件数。递归的规则与 chmod 相同
+pub fn chown(
+    path: &Path,
+    uid: Option<u32>,
+    gid: Option<u32>,
+    recursive: bool,
+    denied: &dyn Fn(&Path) -> bool,
+) -> io::Result<u64> {
+    let mut changed = 0;
+    for target in targets(path, recursive, denied) {
+        let (target, follow) = target?;
+        if chown_entry(&target, uid, gid, follow)? {
+            changed += 1;
+        }
+    }
+    Ok(changed)
+}
+
+// chown_entry 在 follow 为 false 时使用 AT_SYMLINK_NOFOLLOW, 文件在遍历之后被换成符号链接时只会修改链接本身
+fn chown_entry(path: &Path, uid: Option<u32>, gid: Option<u32>, follow: bool) -> io::Result<bool> {
+    let (metadata, flags) = if follow {
+        (fs::metadata(path)?, AtFlags::empty())
+    } else {
+        (fs::symlink_metadata(path)?, AtFlags::AT_SYMLINK_NOFOLLOW)
+    };
+    if metadata.file_type().is_symlink() {
+        return Ok(false);
+    }
+    if uid.is_none_or(|uid| uid == metadata.uid()) && gid.is_none_or(|gid| gid == metadata.gid()) {
+        return Ok(false);
+    }
+    fchownat(None, path, uid.map(Uid::from_raw), gid.map(Gid::from_raw), flags)?;
+    Ok(true)
+}
+
+// targets 返回要修改的文件以及是否跟随符号链接。
+// path 本身是符号链接时与命令一样修改链接指向的文件, 目录中的文件不跟随符号链接
+fn targets<'a>(
+    path: &Path,
+    recursive: bool,
+    denied: &'a dyn Fn(&Path) -> bool,
+) -> impl Iterator<Item = io::Result<(PathBuf, bool)>> + 'a {
+    WalkDir::new(path)
+        .max_depth(if recursive { usize::MAX } else { 0 })
+        .into_iter()
+        .filter_entry(move |entry| entry.depth() == 0 || !denied(entry.path()))
+        .filter_map(|entry| match entry {
+            Err(e) => Some(Err(e.into())),
+            Ok(entry) if entry.depth() > 0 && entry.path_is_symlink() => None,
+            Ok(entry) => {
+                let follow = entry.depth() == 0;
+                Some(Ok((entry.into_path(), follow)))
+            }
+        })
+}
+
+const ACL_ACCESS: &str = "system.posix_acl_access";
+const ACL_DEFAULT: &str = "system.posix_acl_default";
+// 内核中 posix_acl_xattr_header 的版本号, 以及 user::、group::、mask::、other:: 使用的 id
+const ACL_VERSION: u32 = 2;
+const ACL_UNDEFINED_ID: u32 = u32::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum AclTag {
+    UserObj = 0x01,
+    User = 0x02,
+    GroupObj = 0x04,
+    Group = 0x08,
+    Mask = 0x10,
+    Other = 0x20,
+}
+
+// AclEntry 是 ACL 中的一项, 文本形式与 getfacl 相同, 如 user::rw-、user:nginx:r-x、mask::r-x
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct AclEntry {
+    tag: AclTag,
+    id: u32,
+    perm: u16,
+}
+
+impl AclEntry {
+    fn parse(text: &str) -> io::Result<Self> {
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid ACL entry {:?}: {}", text, reason));
+
+        let fields = text.trim().split(':').collect::<Vec<_>>();
+        let (tag, qualifier, perm) = match fields[..] {
+            [tag, qualifier, perm] => (tag, qualifier, perm),
+            // mask 和 other 可以省略中间的空字段
+            [tag @ ("m" | "mask" | "o" | "other"), perm] => (tag, "", perm),
+            _ => return Err(invalid("expected tag:qualifier:perms")),
+        };
+
+        let (tag, id) = match (tag, qualifier) {
+            ("u" | "user", "") => (AclTag::UserObj, ACL_UNDEFINED_ID),
+            ("u" | "user", name) => (AclTag::User, get_uid_by_username(name).ok_or_else(|| invalid("unknown user"))?),
+            ("g" | "group", "") => (AclTag::GroupObj, ACL_UNDEFINED_ID),
+            ("g" | "group", name) => (AclTag::Group, get_gid_by_groupname(name).ok_or_else(|| invalid("unknown group"))?),
+            ("m" | "mask", "") => (AclTag::Mask, ACL_UNDEFINED_ID),
+            ("o" | "other", "") => (AclTag::Other, ACL_UNDEFINED_ID),
+            _ => return Err(invalid("unknown tag")),
+        };
+
+        // 权限为 rwx 的组合, - 占位, 或者一个八进制数字
+        let perm = match perm.parse::<u16>() {
+            Ok(perm) if perm <= 7 => perm,
+            _ => perm.chars().try_fold(0, |perm, c| match c {
+                'r' => Ok(perm | 4),
+                'w' => Ok(perm | 2),
+                'x' => Ok(perm | 1),
+                '-' => Ok(perm),
+                _ => Err(invalid("invalid permissions")),
+            })?,
+        };
+        Ok(Self { tag, id, perm })
+    }
+
+    fn to_text(self) -> String {
+        let (tag, qualifier) = match self.tag {
+            AclTag::UserObj => ("user", String::new()),
+            AclTag::User => ("user", get_username_by_uid(self.id).unwrap_or_else(|| self.id.to_string())),
+            AclTag::GroupObj => ("group", String::new()),
+            AclTag::Group => ("group", get_groupname_by_gid(self.id).unwrap_or_else(|| self.id.to_string())),
+            AclTag::Mask => ("mask", String::new()),
+            AclTag::Other => ("other", String::new()),
+        };
+        let bit = |mask: u16, c: char| if self.perm & mask != 0 { c } else { '-' };
+        format!("{}:{}:{}{}{}", tag, qualifier, bit(4, 'r'), bit(2, 'w'), bit(1, 'x'))
+    }
+}
+
+// Acl 是文件的访问 ACL 和目录的默认 ACL, 每一项为 getfacl 的文本形式
+#[derive(Debug, Clone, Serialize)]
+pub struct Acl {
+    pub path: PathBuf,
+    // 没有扩展 ACL 时由权限位生成 user::、group::、other:: 三项
+    pub access: Vec<String>,
+    // 只有目录才有, 没有时为空
+    pub default: Vec<String>,
+}
+
+// get_acl 读取 ACL
+pub fn get_acl(path: &Path) -> io::Result<Acl> {
+    let metadata = fs::metadata(path)?;
+    let access = match get_xattr(path, ACL_ACCESS)? {
+        Some(value) => decode_acl(&value)?,
+        None => {
+            let mode = metadata.mode();
+            let entry = |tag, shift: u32| AclEntry { tag, id: ACL_UNDEFINED_ID, perm: ((mode >> shift) & 0o7) as u16 };
+            vec![entry(AclTag::UserObj, 6), entry(AclTag::GroupObj, 3), entry(AclTag::Other, 0)]
+        }
+    };
+    let default = match metadata.is_dir() {
+        true => get_xattr(path, ACL_DEFAULT)?.map(|value| decode_acl(&value)).transpose()?.unwrap_or_default(),
+        false => Vec::new(),
+    };
+
+    Ok(Acl {
+        path: path.to_path_buf(),
+        access: access.into_iter().map(AclEntry::to_text).collect(),
+        default: default.into_iter().map(AclEntry::to_text).collect(),
+    })
+}
+
+// set_acl 替换访问 ACL 或默认 ACL, 为 None 的不修改, 为空时删除扩展 ACL。
+// 有 user:name: 或 group:name: 项而没有 mask 时, 与 setfacl 一样自动计算 mask。
+// 内核会根据访问 ACL 同步修改权限位
+pub fn set_acl(path: &Path, access: Option<&[String]>, default: Option<&[String]>) -> io::Result<Acl> {
+    let access = access.map(build_acl).transpose()?;
+    let default = default.map(build_acl).transpose()?;
+    if default.as_ref().is_some_and(|default| !default.is_empty()) && !fs::metadata(path)?.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "Default ACL can only be set on directories"));
+    }
+
+    for (name, entries) in [(ACL_ACCESS, access), (ACL_DEFAULT, default)] {
+        match entries {
+            Some(entries) if entries.is_empty() => remove_xattr(path, name)?,
+            Some(entries) => set_xattr(path, name, &encode_acl(&entries))?,
+            None => {}
+        }
+    }
+    get_acl(path)
+}
+
+// build_acl 解析文本形式的 ACL, 排序并检查是否完整
+fn build_acl(texts: &[String]) -> io::Result<Vec<AclEntry>> {
+    let mut entries = texts.iter().map(|text| AclEntry::parse(text)).collect::<io::Result<Vec<_>>>()?;
+    if entries.is_empty() {
+        return Ok(entries);
+    }
+    entries.sort_by_key(|entry| (entry.tag, entry.id));
+
+    let invalid = |reason: String| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid ACL: {}", reason));
+    if let Some(pair) = entries.windows(2).find(|pair| pair[0].tag == pair[1].tag && pair[0].id == pair[1].id) {
+        return Err(invalid(format!("duplicate entry {}", pair[0].to_text())));
+    }
+    for (tag, name) in [(AclTag::UserObj, "user::"), (AclTag::GroupObj, "group::"), (AclTag::Other, "other::")] {
+        if !entries.iter().any(|entry| entry.tag == tag) {
+            return Err(invalid(format!("missing {} entry", name)));
+        }
+    }
+
+    let named = entries.iter().any(|entry| matches!(entry.tag, AclTag::User | AclTag::Group));
+    if named && !entries.iter().any(|entry| entry.tag == AclTag::Mask) {
+        let perm = entries.iter()
+            .filter(|entry| matches!(entry.tag, AclTag::User | AclTag::GroupObj | AclTag::Group))
+            .fold(0, |perm, entry| perm | entry.perm);
+        entries.push(AclEntry { tag: AclTag::Mask, id: ACL_UNDEFINED_ID, perm });
+        entries.sort_by_key(|entry| (entry.tag, entry.id));
+    }
+    Ok(entries)
+}
+
+// xattr 中的 ACL 是 4 字节的版本号, 之后每项为 2 字节 tag、2 字节权限、4 字节 id, 均为小端
+fn encode_acl(entries: &[AclEntry]) -> Vec<u8> {
+    let mut value = ACL_VERSION.to_le_bytes().to_vec();
+    for entry in entries {
+        value.extend_from_slice(&(entry.tag as u16).to_le_bytes());
+        value.extend_from_slice(&entry.perm.to_le_bytes());
+        value.extend_from_slice(&entry.id.to_le_bytes());
+    }
+    value
+}
+
+fn decode_acl(value: &[u8]) -> io::Result<Vec<AclEntry>> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "Invalid ACL xattr");
+    if value.len() < 4 || !(value.len() - 4).is_multiple_of(8) || value[..4] != ACL_VERSION.to_le_bytes() {
+        return Err(invalid());
+    }
+
+    value[4..].chunks_exact(8)
+        .map(|chunk| {
+            let tag = match u16::from_le_bytes([chunk[0], chunk[1]]) {
+                0x01 => AclTag::UserObj,
+                0x02 => AclTag::User,
+                0x04 => AclTag::GroupObj,
+                0x08 => AclTag::Group,
+                0x10 => AclTag::Mask,
+                0x20 => AclTag::Other,
+                _ => return Err(invalid()),
+            };
+            let perm = u16::from_le_bytes([chunk[2], chunk[3]]);
+            let id = u32::from_le_bytes([chunk[4], chunk[5], chunk[6], chunk[7]]);
+            Ok(AclEntry { tag, id, perm })
+        })
+        .collect()
+}
+
+fn c_string(value: &[u8]) -> io::Result<CString> {
+    CString::new(value).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))
+}
+
+// get_xattr 读取扩展属性, 不存在时返回 None
+fn get_xattr(path: &Path, name: &str) -> io::Result<Option<Vec<u8>>> {
+    let (path, name) = (c_string(path.as_os_str().as_bytes())?, c_string(name.as_bytes())?);
+    loop {
+        let size = unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), std::ptr::null_mut(), 0) };
+        let mut value = vec![0u8; size.max(0) as usize];
+        let read = match size {
+            0.. => unsafe { libc::getxattr(path.as_ptr(), name.as_ptr(), value.as_mut_ptr().cast(), value.len()) },
+            _ => size,
+        };
+        if read >= 0 {
+            value.truncate(read as usize);
+            return Ok(Some(value));
+        }
+        let e = io::Error::last_os_error();
+        match e.raw_os_error() {
+            Some(libc::ENODATA) => return Ok(None),
+            // 两次调用之间属性变大了, 重新获取大小
+            Some(libc::ERANGE) => continue,
+            _ => return Err(e),
+        }
+    }
+}
+
+fn set_xattr(path: &Path, name: &str, value: &[u8]) -> io::Result<()> {
+    let (path, name) = (c_string(path.as_os_str().as_bytes())?, c_string(name.as_bytes())?);
+    if unsafe { libc::setxattr(path.as_ptr(), name.as_ptr(), value.as_ptr().cast(), value.len(), 0) } < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+// remove_xattr 删除扩展属性, 不存在时不报错
+fn remove_xattr(path: &Path, name: &str) -> io::Result<()> {
+    let (path, name) = (c_string(path.as_os_str().as_bytes())?, c_string(name.as_bytes())?);
+    if unsafe { libc::removexattr(path.as_ptr(), name.as_ptr()) } < 0 {
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::ENODATA) {
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::os::unix::fs::{MetadataExt, PermissionsExt, symlink};
+
+    use super::{chmod, chmod_entry, chown, chown_entry, get_acl, ModeSpec, set_acl};
+
+    #[test]
+    fn symbolic_modes_match_chmod() {
+        let apply = |spec: &str, mode: u32, is_dir: bool| ModeSpec::parse(spec).unwrap().apply(mode, is_dir);
+        assert_eq!(apply("0750", 0o644, false), 0o750);
+        assert_eq!(apply("u+x,go-w", 0o666, false), 0o744);
+        assert_eq!(apply("a=rX", 0o600, true), 0o555);
+        assert_eq!(apply("a=rX", 0o600, false), 0o444);
+        assert_eq!(apply("+X", 0o644, false), 0o644);
+        assert_eq!(apply("g=u,o=", 0o751, false), 0o770);
+        assert_eq!(apply("u+s,+t", 0o755, true), 0o5755);
+        assert_eq!(apply("u=rw", 0o4755, false), 0o655);
+        assert_eq!(apply("u+r-w", 0o200, false), 0o400);
+        for invalid in ["", "9", "07777777", "u+q", "z+x", "u", "u+x,,g+w"] {
+            assert!(ModeSpec::parse(invalid).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn chmod_and_chown_recursively() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("data");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("sub").join("secret"), "b").unwrap();
+        fs::write(dir.path().join("outside"), "c").unwrap();
+        fs::set_permissions(dir.path().join("outside"), fs::Permissions::from_mode(0o600)).unwrap();
+        symlink("../../outside", root.join("sub").join("link")).unwrap();
+        for (path, mode) in [("a.txt", 0o600), ("sub/secret", 0o700), ("sub", 0o700), ("", 0o700)] {
+            fs::set_permissions(root.join(path), fs::Permissions::from_mode(mode)).unwrap();
+        }
+
+        let spec = ModeSpec::parse("go=rX").unwrap();
+        let changed = chmod(&root, &spec, true, &|path| path.ends_with("secret")).unwrap();
+        let mode = |path: &str| fs::metadata(root.join(path)).unwrap().mode() & 0o7777;
+        assert_eq!(changed, 3);
+        assert_eq!((mode(""), mode("sub"), mode("a.txt"), mode("sub/secret")), (0o755, 0o755, 0o644, 0o700));
+        assert_eq!(fs::metadata(dir.path().join("outside")).unwrap().mode() & 0o777, 0o600);
+        assert_eq!(chmod(&root, &spec, true, &|_| false).unwrap(), 1);
+        assert_eq!(chmod(&root, &spec, false, &|_| false).unwrap(), 0);
+
+        let uid = fs::metadata(&root).unwrap().uid();
+        assert_eq!(chown(&root, Some(uid), None, true, &|_| false).unwrap(), 0);
+        if unsafe { libc::geteuid() } == 0 {
+            assert_eq!(chown(&root.join("a.txt"), Some(1234), Some(1234), false, &|_| false).unwrap(), 1);
+            assert_eq!(fs::metadata(root.join("a.txt")).unwrap().gid(), 1234);
+        }
+    }
+
+    #[test]
+    fn entries_replaced_by_symlinks_are_not_followed() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = dir.path().join("passwd");
+        fs::write(&target, "root:x:0:0").unwrap();
+        fs::set_permissions(&target, fs::Permissions::from_mode(0o600)).unwrap();
+        // 模拟遍历之后文件被换成了符号链接
+        let link = dir.path().join("link");
+        symlink(&target, &link).unwrap();
+
+        let spec = ModeSpec::parse("0666").unwrap();
+        assert!(!chmod_entry(&link, &spec, false).unwrap());
+        assert_eq!(fs::metadata(&target).unwrap().mode() & 0o7777, 0o600);
+        let uid = fs::metadata(&target).unwrap().uid();
+        assert!(!chown_entry(&link, Some(uid + 1), Some(uid + 1), false).unwrap());
+        assert_eq!(fs::metadata(&target).unwrap().uid(), uid);
+
+        // 直接指定的路径是符号链接时修改链接指向的文件
+        assert!(chmod_entry(&link, &spec, true).unwrap());
+        assert_eq!(fs::metadata(&target).unwrap().mode() & 0o7777, 0o666);
+    }
+
+    #[test]
+    fn acl_round_trip() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("a.txt");
+        fs::write(&file, "a").unwrap();
+        fs::set_permissions(&file, fs::Permissions::from_mode(0o640)).unwrap();
+
+        assert_eq!(get_acl(&file).unwrap().access, vec!["user::rw-", "group::r--", "other::---"]);
+        let access = ["u::rw", "group:4242:r-x", "o::-", "g::r"].map(String::from);
+        let acl = match set_acl(&file, Some(&access), None) {
+            Ok(acl) => acl,
+            // 文件系统不支持 ACL
+            Err(e) if e.kind() == std::io::ErrorKind::Unsupported => return,
+            Err(e) => panic!("{}", e),
+        };
+        assert_eq!(acl.access, vec!["user::rw-", "group::r--", "group:4242:r-x", "mask::r-x", "other::---"]);
+        // 组权限位显示的是 mask
+        assert_eq!(fs::metadata(&file).unwrap().mode() & 0o777, 0o650);
+
+        assert!(set_acl(&file, None, Some(&access)).is_err());
+        assert!(set_acl(&file, Some(&["user::rw-".to_string()]), None).is_err());
+        assert!(set_acl(&file, Some(&["u::rw".to_string(), "u::r".to_string()]), None).is_err());
+
+        set_acl(&file, Some(&[]), None).unwrap();
+        assert_eq!(get_acl(&file).unwrap().access, vec!["user::rw-", "group::r-x", "other::---"]);
+    }
+}
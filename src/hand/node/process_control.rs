@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::time::Duration;
+
+use nix::sys::signal::{kill, killpg, Signal};
+use nix::unistd::Pid;
+use serde::Serialize;
+
+use crate::node_exporter::proc_utils::process::{ProcessStatus, ProcStat};
+
+// kill-tree 发送 SIGTERM 后检查进程是否退出的间隔
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+// kill-tree 最长的等待时间, 请求会一直等到进程退出或超时
+pub const MAX_GRACE: Duration = Duration::from_secs(300);
+
+// KillTreeResult 记录 kill-tree 实际做了什么
+#[derive(Debug, Serialize)]
+pub struct KillTreeResult {
+    // 目标进程及其所有子孙进程
+    pub pids: Vec<u32>,
+    // 收到 SIGTERM 后在等待时间内退出的进程
+    pub terminated: Vec<u32>,
+    // 超时后发送了 SIGKILL 的进程
+    pub killed: Vec<u32>,
+    // 发送信号失败的进程, 如没有权限
+    pub failed: Vec<KillTreeFailure>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct KillTreeFailure {
+    pub pid: u32,
+    pub signal: &'static str,
+    pub error: String,
+}
+
+// Target 是 pid 和进程启动时间, 用于发送信号前确认 pid 没有被复用
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Target {
+    pid: u32,
+    starttime: u64,
+}
+
+// parse_signal 解析 "SIGTERM"、"term"、"15" 等写法
+pub fn parse_signal(value: &str) -> io::Result<Signal> {
+    let value = value.trim();
+
+    let signal = match value.parse::<i32>() {
+        Ok(number) => Signal::try_from(number).ok(),
+        Err(_) => {
+            let name = value.to_ascii_uppercase();
+            let name = if name.starts_with("SIG") { name } else { format!("SIG{}", name) };
+            name.parse::<Signal>().ok()
+        }
+    };
+
+    signal.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, format!("Unknown signal {:?}", value)))
+}
+
+// check_target 拒绝对 1 号进程和 agent 自身进行操作
+pub fn check_target(pid: u32) -> io::Result<()> {
+    if pid == 0 || pid == 1 || pid == std::process::id() {
+        return Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!("Refusing to act on PID {}", pid),
+        ));
+    }
+    Ok(())
+}
+
+// send_signal 向进程发送信号, group 为 true 时发送给进程所在的整个进程组
+pub fn send_signal(pid: u32, signal: Signal, group: bool) -> io::Result<()> {
+    check_target(pid)?;
+
+    if group {
+        let pgrp = ProcStat::read(pid)?.pgrp;
+        let own_pgrp = ProcStat::read(std::process::id())?.pgrp;
+        // 进程组里包含 agent 自己时同样拒绝
+        if pgrp <= 1 || pgrp == own_pgrp {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                format!("Refusing to signal process group {}", pgrp),
+            ));
+        }
+        killpg(Pid::from_raw(pgrp), signal)?;
+    } else {
+        kill(to_pid(pid)?, signal)?;
+    }
+
+    Ok(())
+}
+
+// renice 修改进程所有线程的 nice 值, linux 下 setpriority 只作用于单个线程
+pub fn renice(pid: u32, nice: i32) -> io::Result<Vec<u32>> {
+    check_target(pid)?;
+
+    if !(-20..=19).contains(&nice) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "nice must be in -20..19"));
+    }
+
+    let mut tids = std::fs::read_dir(format!("/proc/{}/task", pid))?
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()))
+        .collect::<Vec<_>>();
+    tids.sort_unstable();
+
+    for tid in &tids {
+        let result = unsafe { libc::setpriority(libc::PRIO_PROCESS, *tid as libc::id_t, nice) };
+        if result != 0 {
+            let e = io::Error::last_os_error();
+            // 线程可能已经退出
+            if e.raw_os_error() != Some(libc::ESRCH) {
+                return Err(e);
+            }
+        }
+    }
+
+    Ok(tids)
+}
+
+// descendants 返回 pid 及其所有子孙进程, 父进程在前
+fn descendants(pid: u32) -> io::Result<Vec<Target>> {
+    let mut children: HashMap<u32, Vec<Target>> = HashMap::new();
+    for child in ProcessStatus::get_pids()? {
+        if let Ok(stat) = ProcStat::read(child) {
+            children.entry(stat.ppid).or_default().push(Target { pid: child, starttime: stat.starttime });
+        }
+    }
+
+    // 确认目标进程存在
+    let stat = ProcStat::read(pid).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, format!("No such process {}", pid)),
+        _ => e,
+    })?;
+
+    let mut result = vec![Target { pid, starttime: stat.starttime }];
+    let mut visited = HashSet::from([pid]);
+    let mut index = 0;
+    while index < result.len() {
+        if let Some(list) = children.get(&result[index].pid) {
+            for child in list {
+                if visited.insert(child.pid) {
+                    result.push(*child);
+                }
+            }
+        }
+        index += 1;
+    }
+
+    Ok(result)
+}
+
+// kill_tree 先向整棵进程树发送 SIGTERM, 等待 grace 后对仍未退出的进程发送 SIGKILL,
+// 单个进程发送失败不影响其他进程, 失败原因记录在 failed 中
+pub async fn kill_tree(pid: u32, grace: Duration) -> io::Result<KillTreeResult> {
+    if grace > MAX_GRACE {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("grace_secs must not exceed {}", MAX_GRACE.as_secs()),
+        ));
+    }
+    let targets = descendants(pid)?;
+    for target in &targets {
+        check_target(target.pid)?;
+    }
+
+    let mut failed = Vec::new();
+    let mut pending = Vec::new();
+    for target in &targets {
+        match signal_alive(*target, Signal::SIGTERM) {
+            Ok(()) => pending.push(*target),
+            Err(e) => failed.push(KillTreeFailure { pid: target.pid, signal: Signal::SIGTERM.as_str(), error: e.to_string() }),
+        }
+    }
+
+    let now = tokio::time::Instant::now();
+    let deadline = now.checked_add(grace).unwrap_or(now);
+    while pending.iter().any(|target| is_alive(*target)) && tokio::time::Instant::now() < deadline {
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+
+    let mut terminated = Vec::new();
+    let mut killed = Vec::new();
+    for target in pending {
+        if !is_alive(target) {
+            terminated.push(target.pid);
+            continue;
+        }
+        match signal_alive(target, Signal::SIGKILL) {
+            Ok(()) => killed.push(target.pid),
+            Err(e) => failed.push(KillTreeFailure { pid: target.pid, signal: Signal::SIGKILL.as_str(), error: e.to_string() }),
+        }
+    }
+
+    Ok(KillTreeResult {
+        pids: targets.iter().map(|target| target.pid).collect(),
+        terminated,
+        killed,
+        failed,
+    })
+}
+
+// signal_alive 在确认 pid 仍是同一个进程后发送信号, 进程已经退出或 pid 已被复用时忽略
+fn signal_alive(target: Target, signal: Signal) -> io::Result<()> {
+    if !is_same_process(target) {
+        return Ok(());
+    }
+    match kill(to_pid(target.pid)?, signal) {
+        Ok(()) | Err(nix::errno::Errno::ESRCH) => Ok(()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+// is_alive 判断进程是否仍在运行, 僵尸进程和 pid 被复用都视为已经退出
+fn is_alive(target: Target) -> bool {
+    ProcStat::read(target.pid)
+        .is_ok_and(|stat| stat.starttime == target.starttime && stat.state != 'Z' && stat.state != 'X')
+}
+
+fn is_same_process(target: Target) -> bool {
+    ProcStat::read(target.pid).is_ok_and(|stat| stat.starttime == target.starttime)
+}
+
+fn to_pid(pid: u32) -> io::Result<Pid> {
+    i32::try_from(pid)
+        .map(Pid::from_raw)
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, format!("Invalid PID {}", pid)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::{Command, Stdio};
+
+    use super::*;
+
+    #[test]
+    fn parses_signal_names_and_numbers() {
+        assert_eq!(parse_signal("SIGTERM").unwrap(), Signal::SIGTERM);
+        assert_eq!(parse_signal("term").unwrap(), Signal::SIGTERM);
+        assert_eq!(parse_signal(" Kill ").unwrap(), Signal::SIGKILL);
+        assert_eq!(parse_signal("15").unwrap(), Signal::SIGTERM);
+        assert_eq!(parse_signal("sigusr1").unwrap(), Signal::SIGUSR1);
+
+        for value in ["", "FOO", "SIG", "0", "-9", "999"] {
+            assert_eq!(parse_signal(value).unwrap_err().kind(), io::ErrorKind::InvalidInput, "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn refuses_init_and_self() {
+        for pid in [0, 1, std::process::id()] {
+            assert_eq!(check_target(pid).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+            assert_eq!(send_signal(pid, Signal::SIGCONT, false).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+            assert_eq!(renice(pid, 0).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        }
+        assert!(check_target(std::process::id() + 1).is_ok());
+    }
+
+    #[test]
+    fn reused_pid_is_not_signalled() {
+        let stat = ProcStat::read(std::process::id()).unwrap();
+        let stale = Target { pid: std::process::id(), starttime: stat.starttime + 1 };
+
+        assert!(!is_same_process(stale));
+        assert!(!is_alive(stale));
+        // 启动时间不一致时不会真正发送信号
+        signal_alive(stale, Signal::SIGKILL).unwrap();
+    }
+
+    #[tokio::test]
+    async fn refuses_grace_above_the_limit() {
+        for grace in [MAX_GRACE + Duration::from_secs(1), Duration::from_secs(u64::MAX)] {
+            let err = kill_tree(std::process::id() + 1, grace).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+        }
+    }
+
+    #[tokio::test]
+    async fn kills_tree_and_escalates_to_sigkill() {
+        // sleep 在 trap 之前启动, 收到 SIGTERM 即退出; 忽略 SIGTERM 并阻塞在 read 上的 shell 只能被 SIGKILL 结束
+        let mut child = Command::new("sh")
+            .args(["-c", "sleep 30 & trap '' TERM; read line"])
+            .stdin(Stdio::piped())
+            .spawn()
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let result = kill_tree(child.id(), Duration::from_millis(300)).await.unwrap();
+        child.wait().unwrap();
+
+        assert_eq!(result.pids.len(), 2);
+        assert_eq!(result.pids[0], child.id());
+        assert_eq!(result.killed, [child.id()]);
+        assert_eq!(result.terminated.len(), 1);
+        assert!(result.failed.is_empty());
+    }
+}
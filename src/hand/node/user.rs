@@ -1,13 +1,16 @@
+use std::collections::HashMap;
 use std::fs::File;
 use std::process::Command;
 use std::io;
 use std::io::BufRead;
 
+#[allow(dead_code)]
 struct UserAdd {
     username: String,
     args: Vec<String>,
 }
 
+#[allow(dead_code)]
 impl UserAdd {
     fn new(username: &str, args: &[&str]) -> Self {
         Self {
@@ -28,7 +31,7 @@ impl UserAdd {
         if status.success() {
             Ok(())
         } else {
-            Err(io::Error::new(io::ErrorKind::Other, "Failed to create user"))
+            Err(io::Error::other("Failed to create user"))
         }
     }
 }
@@ -39,15 +42,13 @@ pub fn get_username_by_uid(uid: u32) -> Option<String> {
     if let Ok(passwd_file) = File::open("/etc/passwd") {
         let reader = io::BufReader::new(passwd_file);
 
-        for line in reader.lines() {
-            if let Ok(entry) = line {
-                let fields: Vec<&str> = entry.split(':').collect();
+        for entry in reader.lines().map_while(Result::ok) {
+            let fields: Vec<&str> = entry.split(':').collect();
 
-                if fields.len() >= 3 {
-                    if let Ok(parsed_uid) = fields[2].parse::<u32>() {
-                        if parsed_uid == uid {
-                            return Some(fields[0].to_string()); // Return the username
-                        }
+            if fields.len() >= 3 {
+                if let Ok(parsed_uid) = fields[2].parse::<u32>() {
+                    if parsed_uid == uid {
+                        return Some(fields[0].to_string()); // Return the username
                     }
                 }
             }
@@ -55,4 +56,93 @@ pub fn get_username_by_uid(uid: u32) -> Option<String> {
     }
 
     None
-}
\ No newline at end of file
+}
+
+// get_uid_by_username 从 /etc/passwd 中查找用户名对应的 uid, 纯数字时直接作为 uid
+pub fn get_uid_by_username(name: &str) -> Option<u32> {
+    name.parse::<u32>().ok().or_else(|| find_id("/etc/passwd", name))
+}
+
+// get_gid_by_groupname 从 /etc/group 中查找组名对应的 gid, 纯数字时直接作为 gid
+pub fn get_gid_by_groupname(name: &str) -> Option<u32> {
+    name.parse::<u32>().ok().or_else(|| find_id("/etc/group", name))
+}
+
+// find_id 在 /etc/passwd 或 /etc/group 中查找第一列为 name 的行, 返回第三列的 id
+fn find_id(path: &str, name: &str) -> Option<u32> {
+    let reader = io::BufReader::new(File::open(path).ok()?);
+
+    reader.lines()
+        .map_while(Result::ok)
+        .find_map(|entry| {
+            let fields: Vec<&str> = entry.split(':').collect();
+            if fields.len() >= 3 && fields[0] == name {
+                fields[2].parse::<u32>().ok()
+            } else {
+                None
+            }
+        })
+}
+
+// get_groupname_by_gid 从 /etc/group 中查找 gid 对应的组名, 格式与 /etc/passwd 相同, 第三列是 gid
+pub fn get_groupname_by_gid(gid: u32) -> Option<String> {
+    if let Ok(group_file) = File::open("/etc/group") {
+        let reader = io::BufReader::new(group_file);
+
+        for entry in reader.lines().map_while(Result::ok) {
+            let fields: Vec<&str> = entry.split(':').collect();
+
+            if fields.len() >= 3 {
+                if let Ok(parsed_gid) = fields[2].parse::<u32>() {
+                    if parsed_gid == gid {
+                        return Some(fields[0].to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    None
+}
+
+// get_usernames 一次性读取 /etc/passwd, 返回 uid 到用户名的映射, 避免逐个查询时反复读文件
+pub fn get_usernames() -> HashMap<u32, String> {
+    let mut usernames = HashMap::new();
+
+    if let Ok(passwd_file) = File::open("/etc/passwd") {
+        let reader = io::BufReader::new(passwd_file);
+
+        for entry in reader.lines().map_while(Result::ok) {
+            let fields: Vec<&str> = entry.split(':').collect();
+
+            if fields.len() >= 3 {
+                if let Ok(uid) = fields[2].parse::<u32>() {
+                    usernames.entry(uid).or_insert_with(|| fields[0].to_string());
+                }
+            }
+        }
+    }
+
+    usernames
+}
+
+// get_groupnames 一次性读取 /etc/group, 返回 gid 到组名的映射
+pub fn get_groupnames() -> HashMap<u32, String> {
+    let mut groupnames = HashMap::new();
+
+    if let Ok(group_file) = File::open("/etc/group") {
+        let reader = io::BufReader::new(group_file);
+
+        for entry in reader.lines().map_while(Result::ok) {
+            let fields: Vec<&str> = entry.split(':').collect();
+
+            if fields.len() >= 3 {
+                if let Ok(gid) = fields[2].parse::<u32>() {
+                    groupnames.entry(gid).or_insert_with(|| fields[0].to_string());
+                }
+            }
+        }
+    }
+
+    groupnames
+}
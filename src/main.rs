@@ -1,21 +1,43 @@
+use std::env;
+use std::process;
+use std::sync::Arc;
+
+use crate::config::agent_config::AgentConfig;
 use crate::router::routers::register_handlers;
 
+mod error;
+
+mod config {
+    pub mod agent_config;
+}
+
 mod node_exporter {
+    pub mod collector;
+    pub mod sampler;
     pub mod mem_utils {
         pub mod meminfo;
     }
     pub mod cpu_utils {
         pub mod cpuinfo;
         pub mod cpuloadavg;
+        pub mod cpustat;
     }
     pub mod disk_utils {
         pub mod diskinfo;
     }
     pub mod file_utils {
+        pub mod filefollow;
         pub mod fileinfo;
     }
+    pub mod net_utils {
+        pub mod netinfo;
+        pub mod socket;
+    }
     pub mod proc_utils {
         pub mod process;
+        pub mod procdetail;
+        pub mod proctop;
+        pub mod proctree;
     }
 }
 
@@ -30,18 +52,23 @@ mod mysql_exporter{
 mod hand {
     pub mod node {
         pub mod file_operation;
+        pub mod file_permission;
         pub mod firewall;
+        pub mod process_control;
         pub mod user;
     }
 }
 
 mod api {
+    pub mod api_error;
+    pub mod path_policy;
     pub mod node_exporter {
         pub mod linux_process_api;
         pub mod linux_cpu_api;
         pub mod linux_memory_api;
         pub mod linux_disk_api;
         pub mod linux_network_api;
+        pub mod linux_metrics_api;
     }
     pub mod linux_file_action_api;
 }
@@ -52,10 +79,19 @@ mod router {
 
 #[tokio::main]
 async fn main() {
+    // 加载配置
+    let config = match AgentConfig::load(env::args().skip(1)) {
+        Ok(config) => Arc::new(config),
+        Err(e) => {
+            eprintln!("Failed to load config: {}", e);
+            process::exit(1);
+        }
+    };
+
     // 注册路由
-    let app = register_handlers();
+    let app = register_handlers(config.clone());
 
-    // run our app with hyper, listening globally on port 4201
-    let listener = tokio::net::TcpListener::bind("0.0.0.0:4201").await.unwrap();
+    // run our app with hyper, listening on the configured address
+    let listener = tokio::net::TcpListener::bind(config.listen_addr()).await.unwrap();
     axum::serve(listener, app).await.unwrap();
 }
\ No newline at end of file
@@ -1,5 +1,6 @@
 
 
+#[allow(dead_code)]
 struct BinlogVariables {
     binlog_cache_size: i32,
     binlog_checksum: String,
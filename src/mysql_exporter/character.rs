@@ -1,4 +1,5 @@
 
+#[allow(dead_code)]
 struct CharacterVariables {
     character_set_client: String,
     character_set_connection: String,
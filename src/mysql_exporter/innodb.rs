@@ -1,5 +1,6 @@
 
 
+#[allow(dead_code)]
 struct InnodbInfo {
     innodb_adaptive_flushing: String,
     innodb_adaptive_flushing_lwm: i32,
@@ -3,8 +3,8 @@ use std::io::Error;
 use std::str::FromStr;
 
 use sqlx::{FromRow, MySqlConnection};
-use sqlx::mysql::MySqlDatabaseError;
 
+#[allow(dead_code)]
 struct MysqlInfo {
     max_connections: u32,           // mysql 的最大连接数
     wait_timeout: u32,              // 客户端闲置多少秒后断开连接。
@@ -19,6 +19,7 @@ struct MysqlInfo {
     max_heap_table_size: u32,   // 用户创建的HEAP表的最大大小。
 }
 
+#[allow(dead_code)]
 impl MysqlInfo {
     pub async fn fetch_info(mut conn: MySqlConnection) -> Result<Self, Error> {
         let max_connections = Self::get_variable_value(&mut conn, "max_connections").await?;
@@ -53,17 +54,17 @@ impl MysqlInfo {
         }
 
         // 执行查询以获取指定变量的值
-        let result: Vec<Variable> = sqlx::query_as("SHOW VARIABLES LIKE $1")
+        let result: Vec<Variable> = sqlx::query_as("SHOW VARIABLES LIKE ?")
             .bind(variable_name)
             .fetch_all(conn)
             .await
-            .map_err(|err| Error::new(io::ErrorKind::Other, err.to_string()))?;
+            .map_err(|err| Error::other(err.to_string()))?;
 
         // 解析结果，获取 Value 列的值并转换为指定类型
         let value = result.into_iter()
             .find(|variable| variable.variable_name == variable_name)
             .and_then(|variable| variable.value.parse::<T>().ok())
-            .ok_or_else(|| MySqlDatabaseError::message(format!("{:?} variable not found", variable_name)))?;
+            .ok_or_else(|| Error::new(io::ErrorKind::NotFound, format!("{:?} variable not found", variable_name)))?;
 
         Ok(value)
     }
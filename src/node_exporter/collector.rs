@@ -0,0 +1,262 @@
+//! Prometheus 文本格式 (exposition format 0.0.4) 大致如下:
+//! # HELP node_load1 1m load average.
+//! # TYPE node_load1 gauge
+//! node_load1 0.21
+//! # HELP node_directory_size_bytes Total size of regular files under the directory.
+//! # TYPE node_directory_size_bytes gauge
+//! node_directory_size_bytes{directory="/var/log"} 1048576
+//!
+//! 每个数据源实现 Collector trait, 由 Registry 统一汇总并渲染成上面的文本。
+
+use std::fmt::Write;
+use std::io;
+use std::time::Instant;
+
+// Prometheus 抓取时使用的 Content-Type
+pub const TEXT_FORMAT_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType {
+    Counter,
+    Gauge,
+}
+
+impl MetricType {
+    fn as_str(&self) -> &'static str {
+        match self {
+            MetricType::Counter => "counter",
+            MetricType::Gauge => "gauge",
+        }
+    }
+}
+
+// Sample 是一条带标签的指标值
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub labels: Vec<(String, String)>,
+    pub value: f64,
+}
+
+// MetricFamily 是同名指标的集合, 对应一组 HELP/TYPE 行
+#[derive(Debug, Clone)]
+pub struct MetricFamily {
+    pub name: String,
+    pub help: String,
+    pub metric_type: MetricType,
+    pub samples: Vec<Sample>,
+}
+
+impl MetricFamily {
+    pub fn new(name: &str, help: &str, metric_type: MetricType) -> Self {
+        Self {
+            name: name.to_string(),
+            help: help.to_string(),
+            metric_type,
+            samples: Vec::new(),
+        }
+    }
+
+    pub fn gauge(name: &str, help: &str) -> Self {
+        Self::new(name, help, MetricType::Gauge)
+    }
+
+    pub fn counter(name: &str, help: &str) -> Self {
+        Self::new(name, help, MetricType::Counter)
+    }
+
+    // with_value 添加一条不带标签的指标
+    pub fn with_value(mut self, value: f64) -> Self {
+        self.samples.push(Sample { labels: Vec::new(), value });
+        self
+    }
+
+    // add 添加一条带标签的指标
+    pub fn add(&mut self, labels: &[(&str, &str)], value: f64) {
+        self.samples.push(Sample {
+            labels: labels.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect(),
+            value,
+        });
+    }
+
+    // render 把指标按文本格式写入 out
+    pub fn render(&self, out: &mut String) {
+        let _ = writeln!(out, "# HELP {} {}", self.name, escape_help(&self.help));
+        let _ = writeln!(out, "# TYPE {} {}", self.name, self.metric_type.as_str());
+
+        for sample in &self.samples {
+            out.push_str(&self.name);
+            if !sample.labels.is_empty() {
+                let labels = sample.labels.iter()
+                    .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+                    .collect::<Vec<_>>()
+                    .join(",");
+                let _ = write!(out, "{{{}}}", labels);
+            }
+            let _ = writeln!(out, " {}", format_value(sample.value));
+        }
+    }
+}
+
+// Collector 是所有指标数据源的公共接口
+pub trait Collector: Send + Sync {
+    // 数据源名称, 作为 collector 标签的值
+    fn name(&self) -> &'static str;
+
+    // 采集一次指标
+    fn collect(&self) -> io::Result<Vec<MetricFamily>>;
+}
+
+// Registry 保存所有注册的 Collector
+#[derive(Default)]
+pub struct Registry {
+    collectors: Vec<Box<dyn Collector>>,
+}
+
+impl Registry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn register<C>(mut self, collector: C) -> Self
+        where
+            C: Collector + 'static,
+    {
+        self.collectors.push(Box::new(collector));
+        self
+    }
+
+    // gather 依次调用所有 Collector 并渲染成文本格式,
+    // 单个 Collector 失败不影响其它 Collector, 失败情况通过 node_scrape_collector_success 反映
+    pub fn gather(&self) -> String {
+        let mut out = String::new();
+        let mut duration = MetricFamily::gauge(
+            "node_scrape_collector_duration_seconds",
+            "node_exporter: Duration of a collector scrape.",
+        );
+        let mut success = MetricFamily::gauge(
+            "node_scrape_collector_success",
+            "node_exporter: Whether a collector succeeded.",
+        );
+
+        for collector in &self.collectors {
+            let start = Instant::now();
+            let result = collector.collect();
+            let elapsed = start.elapsed().as_secs_f64();
+
+            match result {
+                Ok(families) => {
+                    for family in &families {
+                        family.render(&mut out);
+                    }
+                    success.add(&[("collector", collector.name())], 1.0);
+                }
+                Err(e) => {
+                    eprintln!("Collector {} failed: {}", collector.name(), e);
+                    success.add(&[("collector", collector.name())], 0.0);
+                }
+            }
+            duration.add(&[("collector", collector.name())], elapsed);
+        }
+
+        duration.render(&mut out);
+        success.render(&mut out);
+        out
+    }
+}
+
+fn escape_help(help: &str) -> String {
+    help.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn escape_label_value(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+fn format_value(value: f64) -> String {
+    if value.is_nan() {
+        "NaN".to_string()
+    } else if value.is_infinite() {
+        if value > 0.0 { "+Inf".to_string() } else { "-Inf".to_string() }
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Failing;
+
+    impl Collector for Failing {
+        fn name(&self) -> &'static str {
+            "failing"
+        }
+
+        fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+            Err(io::Error::other("boom"))
+        }
+    }
+
+    struct Fixed;
+
+    impl Collector for Fixed {
+        fn name(&self) -> &'static str {
+            "fixed"
+        }
+
+        fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+            Ok(vec![MetricFamily::gauge("node_fixed", "Fixed value.").with_value(1.0)])
+        }
+    }
+
+    #[test]
+    fn renders_help_type_and_samples() {
+        let mut family = MetricFamily::counter("node_cpu_seconds_total", "Seconds the CPUs spent in each mode.");
+        family.add(&[("cpu", "0"), ("mode", "user")], 12.5);
+        family.add(&[("cpu", "0"), ("mode", "idle")], 100.0);
+
+        let mut out = String::new();
+        family.render(&mut out);
+        MetricFamily::gauge("node_load1", "1m load\\average.\nsecond line").with_value(0.21).render(&mut out);
+
+        assert_eq!(out, concat!(
+            "# HELP node_cpu_seconds_total Seconds the CPUs spent in each mode.\n",
+            "# TYPE node_cpu_seconds_total counter\n",
+            "node_cpu_seconds_total{cpu=\"0\",mode=\"user\"} 12.5\n",
+            "node_cpu_seconds_total{cpu=\"0\",mode=\"idle\"} 100\n",
+            "# HELP node_load1 1m load\\\\average.\\nsecond line\n",
+            "# TYPE node_load1 gauge\n",
+            "node_load1 0.21\n",
+        ));
+    }
+
+    #[test]
+    fn escapes_label_values() {
+        assert_eq!(escape_label_value("plain"), "plain");
+        assert_eq!(escape_label_value(r"C:\logs"), r"C:\\logs");
+        assert_eq!(escape_label_value("say \"hi\""), "say \\\"hi\\\"");
+        assert_eq!(escape_label_value("a\nb"), "a\\nb");
+        assert_eq!(escape_label_value("\\\"\n"), "\\\\\\\"\\n");
+    }
+
+    #[test]
+    fn formats_special_values() {
+        assert_eq!(format_value(f64::NAN), "NaN");
+        assert_eq!(format_value(f64::INFINITY), "+Inf");
+        assert_eq!(format_value(f64::NEG_INFINITY), "-Inf");
+        assert_eq!(format_value(0.0), "0");
+        assert_eq!(format_value(-1.5), "-1.5");
+        assert_eq!(format_value(1e21), "1000000000000000000000");
+    }
+
+    #[test]
+    fn failing_collector_does_not_hide_others() {
+        let out = Registry::new().register(Failing).register(Fixed).gather();
+
+        assert!(out.contains("node_fixed 1\n"));
+        assert!(out.contains("node_scrape_collector_success{collector=\"failing\"} 0\n"));
+        assert!(out.contains("node_scrape_collector_success{collector=\"fixed\"} 1\n"));
+        assert!(out.contains("node_scrape_collector_duration_seconds{collector=\"failing\"} "));
+    }
+}
@@ -1,18 +1,358 @@
-use serde::{Deserialize, Serialize};
+//! linux 下的 cpu 型号信息在 /proc/cpuinfo 文件下, 每个逻辑 cpu 一段, 段之间用空行分隔。
+//! x86 下大致如下:
+//! processor       : 0
+//! vendor_id       : GenuineIntel
+//! model name      : Intel(R) Xeon(R) Gold 6248R CPU @ 3.00GHz
+//! cpu MHz         : 3000.000
+//! cache size      : 36608 KB
+//! physical id     : 0
+//! core id         : 0
+//! flags           : fpu vme de pse ...
+//! arm64 下没有 model name 和 vendor_id, 需要通过 CPU implementer 和 CPU part 推断,
+//! 指令集特性在 Features 字段中:
+//! processor       : 0
+//! Features        : fp asimd evtstrm aes ...
+//! CPU implementer : 0x41
+//! CPU part        : 0xd0c
+//!
+//! 物理封装、核心和线程的对应关系以 /sys/devices/system/cpu/cpuN/topology 为准,
+//! NUMA 节点由 /sys/devices/system/node/nodeN/cpulist 得到。
 
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+use std::path::Path;
 
-#[derive(Debug, Serialize, Deserialize)]
-struct CPUStat {
-    usage: f64,
+use serde::Serialize;
+
+// LogicalCpu 描述一个逻辑 cpu (一个硬件线程)
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LogicalCpu {
+    // /proc/cpuinfo 中的 processor 编号
+    pub processor: u32,
+    pub vendor: Option<String>,
+    pub model_name: Option<String>,
+    // 当前频率 (MHz)
+    pub mhz: Option<f64>,
+    // 缓存大小, 保持 /proc/cpuinfo 中的原始写法, 如 "36608 KB"
+    pub cache_size: Option<String>,
+    // 物理封装 (插槽) 编号
+    pub package_id: Option<u32>,
+    // 封装内的核心编号
+    pub core_id: Option<u32>,
+    // 同一核心内的线程序号, 从 0 开始
+    pub thread_id: Option<u32>,
+    pub numa_node: Option<u32>,
+    pub flags: Vec<String>,
+}
+
+// NumaNode 描述一个 NUMA 节点包含的逻辑 cpu
+#[derive(Debug, Clone, Serialize)]
+pub struct NumaNode {
+    pub node: u32,
+    pub cpus: Vec<u32>,
+}
+
+// CpuTopology 是整机的 cpu 型号和拓扑信息
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CpuTopology {
+    // cpu 型号, 取第一个逻辑 cpu 的型号
+    pub model_name: Option<String>,
+    pub vendor: Option<String>,
+    // 所有逻辑 cpu 共有的指令集特性
+    pub flags: Vec<String>,
+    // 物理封装数量
+    pub packages: u32,
+    // 物理核心数量
+    pub cores: u32,
+    // 逻辑 cpu (线程) 数量
+    pub threads: u32,
+    pub cpus: Vec<LogicalCpu>,
+    pub numa_nodes: Vec<NumaNode>,
+}
+
+impl CpuTopology {
+    // 从 /proc/cpuinfo 和 /sys 中读取 cpu 拓扑
+    pub fn init() -> Result<Self, io::Error> {
+        let cpuinfo = fs::read_to_string("/proc/cpuinfo")?;
+        Ok(Self::parse(&cpuinfo, Path::new("/sys")))
+    }
+
+    // parse 解析 cpuinfo 内容, 并从 sys_root 下的 devices/system 补充拓扑信息
+    pub fn parse(cpuinfo: &str, sys_root: &Path) -> Self {
+        let system = sys_root.join("devices/system");
+        let numa_nodes = read_numa_nodes(&system.join("node"));
+
+        let mut cpus = parse_cpuinfo(cpuinfo);
+        for cpu in &mut cpus {
+            let cpu_dir = system.join(format!("cpu/cpu{}", cpu.processor));
+            let topology = cpu_dir.join("topology");
+
+            // sysfs 中的拓扑比 cpuinfo 更准确, 且 arm64 的 cpuinfo 中没有这些字段
+            if let Some(package_id) = read_u32(&topology.join("physical_package_id")) {
+                cpu.package_id = Some(package_id);
+            }
+            if let Some(core_id) = read_u32(&topology.join("core_id")) {
+                cpu.core_id = Some(core_id);
+            }
+            cpu.thread_id = fs::read_to_string(topology.join("thread_siblings_list")).ok()
+                .map(|list| parse_cpu_list(&list))
+                .and_then(|siblings| siblings.iter().position(|id| *id == cpu.processor))
+                .map(|position| position as u32);
+
+            if cpu.mhz.is_none() {
+                cpu.mhz = read_u32(&cpu_dir.join("cpufreq/scaling_cur_freq"))
+                    .map(|khz| khz as f64 / 1000.0);
+            }
+
+            cpu.numa_node = numa_nodes.iter()
+                .find(|node| node.cpus.contains(&cpu.processor))
+                .map(|node| node.node);
+        }
+
+        let packages = cpus.iter()
+            .filter_map(|cpu| cpu.package_id)
+            .collect::<BTreeSet<_>>();
+        let cores = cpus.iter()
+            .filter_map(|cpu| Some((cpu.package_id?, cpu.core_id?)))
+            .collect::<BTreeSet<_>>();
+
+        let flags = match cpus.split_first() {
+            Some((first, rest)) => first.flags.iter()
+                .filter(|flag| rest.iter().all(|cpu| cpu.flags.contains(flag)))
+                .cloned()
+                .collect(),
+            None => Vec::new(),
+        };
+
+        Self {
+            model_name: cpus.first().and_then(|cpu| cpu.model_name.clone()),
+            vendor: cpus.first().and_then(|cpu| cpu.vendor.clone()),
+            flags,
+            packages: packages.len().max(1) as u32,
+            // 拿不到拓扑信息时退化为每个逻辑 cpu 一个核心
+            cores: if cores.is_empty() { cpus.len() as u32 } else { cores.len() as u32 },
+            threads: cpus.len() as u32,
+            cpus,
+            numa_nodes,
+        }
+    }
+}
+
+// parse_cpuinfo 把 /proc/cpuinfo 的每一段解析成 LogicalCpu
+fn parse_cpuinfo(content: &str) -> Vec<LogicalCpu> {
+    let mut cpus = Vec::new();
+    let mut current: Option<LogicalCpu> = None;
+    let mut implementer: Option<u32> = None;
+    let mut part: Option<u32> = None;
+
+    for line in content.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let key = key.trim();
+        let value = value.trim();
+
+        if key == "processor" {
+            if let Some(cpu) = current.take() {
+                cpus.push(finish_arm_cpu(cpu, implementer, part));
+            }
+            implementer = None;
+            part = None;
+            // 老的 arm 内核中 "Processor : AArch64 Processor" 也会出现, 只认数字
+            current = value.parse::<u32>().ok().map(|processor| LogicalCpu {
+                processor,
+                ..Default::default()
+            });
+            continue;
+        }
+
+        let Some(cpu) = current.as_mut() else {
+            continue;
+        };
+
+        match key {
+            "vendor_id" => cpu.vendor = Some(value.to_string()),
+            "model name" => cpu.model_name = Some(value.to_string()),
+            "cpu MHz" => cpu.mhz = value.parse::<f64>().ok(),
+            "cache size" => cpu.cache_size = Some(value.to_string()),
+            "physical id" => cpu.package_id = value.parse::<u32>().ok(),
+            "core id" => cpu.core_id = value.parse::<u32>().ok(),
+            "flags" | "Features" => cpu.flags = value.split_whitespace().map(String::from).collect(),
+            "CPU implementer" => implementer = parse_hex(value),
+            "CPU part" => part = parse_hex(value),
+            _ => {}
+        }
+    }
+
+    if let Some(cpu) = current {
+        cpus.push(finish_arm_cpu(cpu, implementer, part));
+    }
+
+    cpus
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CPUInfo {
-    // cpu 型号
-    model_name: String,
+// finish_arm_cpu 用 CPU implementer 和 CPU part 补全 arm64 缺失的厂商和型号
+fn finish_arm_cpu(mut cpu: LogicalCpu, implementer: Option<u32>, part: Option<u32>) -> LogicalCpu {
+    let Some(implementer) = implementer else {
+        return cpu;
+    };
+
+    if cpu.vendor.is_none() {
+        cpu.vendor = Some(arm_implementer_name(implementer)
+            .map(String::from)
+            .unwrap_or_else(|| format!("0x{:02x}", implementer)));
+    }
+
+    if cpu.model_name.is_none() {
+        cpu.model_name = part.map(|part| {
+            arm_part_name(implementer, part)
+                .map(String::from)
+                .unwrap_or_else(|| format!("0x{:03x}", part))
+        });
+    }
 
-    // 核心数量
-    core_count: u32,
-    // cpu 状态信息
-    cpu_stat: Vec<CPUStat>,
-}
\ No newline at end of file
+    cpu
+}
+
+fn arm_implementer_name(implementer: u32) -> Option<&'static str> {
+    match implementer {
+        0x41 => Some("ARM"),
+        0x42 => Some("Broadcom"),
+        0x43 => Some("Cavium"),
+        0x46 => Some("Fujitsu"),
+        0x48 => Some("HiSilicon"),
+        0x4e => Some("NVIDIA"),
+        0x51 => Some("Qualcomm"),
+        0x61 => Some("Apple"),
+        0xc0 => Some("Ampere"),
+        _ => None,
+    }
+}
+
+fn arm_part_name(implementer: u32, part: u32) -> Option<&'static str> {
+    match (implementer, part) {
+        (0x41, 0xd03) => Some("Cortex-A53"),
+        (0x41, 0xd05) => Some("Cortex-A55"),
+        (0x41, 0xd07) => Some("Cortex-A57"),
+        (0x41, 0xd08) => Some("Cortex-A72"),
+        (0x41, 0xd0b) => Some("Cortex-A76"),
+        (0x41, 0xd0c) => Some("Neoverse-N1"),
+        (0x41, 0xd40) => Some("Neoverse-V1"),
+        (0x41, 0xd49) => Some("Neoverse-N2"),
+        (0x41, 0xd4f) => Some("Neoverse-V2"),
+        (0x48, 0xd01) => Some("Kunpeng-920"),
+        (0xc0, 0xac3) => Some("Ampere-1"),
+        _ => None,
+    }
+}
+
+fn parse_hex(value: &str) -> Option<u32> {
+    u32::from_str_radix(value.trim_start_matches("0x"), 16).ok()
+}
+
+fn read_u32(path: &Path) -> Option<u32> {
+    fs::read_to_string(path).ok()?.trim().parse::<u32>().ok()
+}
+
+// read_numa_nodes 读取 node_dir 下所有 nodeN/cpulist
+fn read_numa_nodes(node_dir: &Path) -> Vec<NumaNode> {
+    let mut nodes = BTreeMap::new();
+
+    if let Ok(entries) = fs::read_dir(node_dir) {
+        for entry in entries.flatten() {
+            let name = entry.file_name();
+            let Some(node) = name.to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|id| id.parse::<u32>().ok()) else {
+                continue;
+            };
+
+            if let Ok(list) = fs::read_to_string(entry.path().join("cpulist")) {
+                nodes.insert(node, parse_cpu_list(&list));
+            }
+        }
+    }
+
+    nodes.into_iter()
+        .map(|(node, cpus)| NumaNode { node, cpus })
+        .collect()
+}
+
+// parse_cpu_list 解析 "0-3,8-11" 格式的 cpu 列表
+pub fn parse_cpu_list(list: &str) -> Vec<u32> {
+    let mut cpus = Vec::new();
+
+    for range in list.trim().split(',').filter(|range| !range.is_empty()) {
+        match range.split_once('-') {
+            Some((start, end)) => {
+                if let (Ok(start), Ok(end)) = (start.parse::<u32>(), end.parse::<u32>()) {
+                    cpus.extend(start..=end);
+                }
+            }
+            None => {
+                if let Ok(cpu) = range.parse::<u32>() {
+                    cpus.push(cpu);
+                }
+            }
+        }
+    }
+
+    cpus
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    fn fixture(name: &str) -> PathBuf {
+        PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/cpuinfo").join(name)
+    }
+
+    fn load(arch: &str) -> CpuTopology {
+        let cpuinfo = fs::read_to_string(fixture(&format!("{}.txt", arch))).unwrap();
+        CpuTopology::parse(&cpuinfo, &fixture(&format!("sys_{}", arch)))
+    }
+
+    #[test]
+    fn parses_x86_64_cpuinfo() {
+        let topology = load("x86_64");
+
+        assert_eq!(topology.model_name.as_deref(), Some("Intel(R) Xeon(R) Gold 6248R CPU @ 3.00GHz"));
+        assert_eq!(topology.vendor.as_deref(), Some("GenuineIntel"));
+        assert_eq!((topology.packages, topology.cores, topology.threads), (1, 2, 4));
+        assert!(topology.flags.iter().any(|flag| flag == "avx512f"));
+
+        let cpu = &topology.cpus[2];
+        assert_eq!(cpu.processor, 2);
+        assert_eq!(cpu.mhz, Some(3000.0));
+        assert_eq!(cpu.cache_size.as_deref(), Some("36608 KB"));
+        assert_eq!((cpu.package_id, cpu.core_id, cpu.thread_id), (Some(0), Some(0), Some(1)));
+        assert_eq!(cpu.numa_node, Some(0));
+    }
+
+    #[test]
+    fn parses_arm64_cpuinfo() {
+        let topology = load("arm64");
+
+        assert_eq!(topology.model_name.as_deref(), Some("Neoverse-N1"));
+        assert_eq!(topology.vendor.as_deref(), Some("ARM"));
+        assert_eq!((topology.packages, topology.cores, topology.threads), (1, 2, 2));
+        assert!(topology.flags.iter().any(|flag| flag == "asimd"));
+
+        let cpu = &topology.cpus[1];
+        assert_eq!(cpu.mhz, Some(2600.0));
+        assert_eq!(cpu.cache_size, None);
+        assert_eq!((cpu.core_id, cpu.thread_id), (Some(1), Some(0)));
+        assert_eq!(cpu.numa_node, Some(1));
+        assert_eq!(topology.numa_nodes.len(), 2);
+    }
+
+    #[test]
+    fn parses_cpu_list_ranges() {
+        assert_eq!(parse_cpu_list("0-3,8,10-11\n"), vec![0, 1, 2, 3, 8, 10, 11]);
+        assert!(parse_cpu_list("").is_empty());
+    }
+}
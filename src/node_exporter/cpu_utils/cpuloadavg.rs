@@ -1,19 +1,20 @@
-/// linux 下的 cpu 状态信息在 /proc/loadavg 文件下, 文件内容大致如下:
-/// 0.00 0.00 0.00 1/164 3582
-/// 第一个 0.00 代表过去1分钟的平均负载。
-/// 第二个 0.00 代表过去5分钟的平均负载。
-/// 第三个 0.00 代表过去15分钟的平均负载。
-/// 1/164 是两个数字的组合,
-/// 1：表示当前运行队列中的进程数（也就是正在运行或等待CPU的进程数）。
-/// 164：表示系统总的进程数或者线程数（包括睡眠中的进程或线程），取决于使用的 Linux 发行版或内核版本。
-/// 3582：表示自系统启动以来发生的上下文切换的次数。
-
-/// 获取CPU的负载平均值，返回一个包含1分钟、5分钟和15分钟负载平均值的元组，
-/// 或者在出错时返回一个错误字符串。
+//! linux 下的 cpu 状态信息在 /proc/loadavg 文件下, 文件内容大致如下:
+//! 0.00 0.00 0.00 1/164 3582
+//! 第一个 0.00 代表过去1分钟的平均负载。
+//! 第二个 0.00 代表过去5分钟的平均负载。
+//! 第三个 0.00 代表过去15分钟的平均负载。
+//! 1/164 是两个数字的组合,
+//! 1：表示当前运行队列中的进程数（也就是正在运行或等待CPU的进程数）。
+//! 164：表示系统总的进程数或者线程数（包括睡眠中的进程或线程），取决于使用的 Linux 发行版或内核版本。
+//! 3582：表示自系统启动以来发生的上下文切换的次数。
 
 use std::fs::File;
-use std::io::Read;
+use std::io::{self, Read};
+
+use crate::node_exporter::collector::{Collector, MetricFamily};
 
+/// 获取CPU的负载平均值，返回一个包含1分钟、5分钟和15分钟负载平均值的元组，
+/// 或者在出错时返回一个错误字符串。
 pub fn get_cpu_loadavg() -> Result<(f32, f32, f32), &'static str> {
     // 负载平均值文件的路径
     let loadavg_path = "/proc/loadavg";
@@ -37,28 +38,19 @@ pub fn get_cpu_loadavg() -> Result<(f32, f32, f32), &'static str> {
 
     // 尝试获取并解析1分钟的负载平均值
     let one_minute_load = match parts.next() {
-        Some(load) => match parse_load(load) {
-            Ok(value) => value, // 如果解析成功，则使用解析后的值
-            Err(error) => return Err(error), // 如果解析失败，则返回错误
-        },
+        Some(load) => parse_load(load)?, // 如果解析失败，则返回错误
         None => return Err("负载平均值文件格式无效"), // 如果没有更多的部分，则返回错误
     };
 
     // 尝试获取并解析5分钟的负载平均值
     let five_minute_load = match parts.next() {
-        Some(load) => match parse_load(load) {
-            Ok(value) => value, // 如果解析成功，则使用解析后的值
-            Err(error) => return Err(error), // 如果解析失败，则返回错误
-        },
+        Some(load) => parse_load(load)?, // 如果解析失败，则返回错误
         None => return Err("负载平均值文件格式无效"), // 如果没有更多的部分，则返回错误
     };
 
     // 尝试获取并解析15分钟的负载平均值
     let fifteen_minute_load = match parts.next() {
-        Some(load) => match parse_load(load) {
-            Ok(value) => value, // 如果解析成功，则使用解析后的值
-            Err(error) => return Err(error), // 如果解析失败，则返回错误
-        },
+        Some(load) => parse_load(load)?, // 如果解析失败，则返回错误
         None => return Err("负载平均值文件格式无效"), // 如果没有更多的部分，则返回错误
     };
 
@@ -70,4 +62,29 @@ pub fn get_cpu_loadavg() -> Result<(f32, f32, f32), &'static str> {
 /// 如果解析成功，则返回Ok包含解析后的值；如果解析失败，则返回Err包含错误消息。
 fn parse_load(load: &str) -> Result<f32, &'static str> {
     load.parse::<f32>().map_err(|_| "无法解析负载平均值为浮点数")
-}
\ No newline at end of file
+}
+
+// LoadAvgCollector 把 /proc/loadavg 导出为 node_load1/5/15 指标
+pub struct LoadAvgCollector;
+
+impl Collector for LoadAvgCollector {
+    fn name(&self) -> &'static str {
+        "loadavg"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let (one, five, fifteen) = get_cpu_loadavg()
+            .map_err(io::Error::other)?;
+
+        Ok(vec![
+            MetricFamily::gauge("node_load1", "1m load average.").with_value(load_to_f64(one)),
+            MetricFamily::gauge("node_load5", "5m load average.").with_value(load_to_f64(five)),
+            MetricFamily::gauge("node_load15", "15m load average.").with_value(load_to_f64(fifteen)),
+        ])
+    }
+}
+
+// load_to_f64 避免 f32 直接转换成 f64 时出现 0.20999999344348907 这样的精度噪音
+fn load_to_f64(load: f32) -> f64 {
+    load.to_string().parse::<f64>().unwrap_or(load as f64)
+}
@@ -0,0 +1,340 @@
+//! linux 下的 cpu 时间信息在 /proc/stat 文件下, 文件内容大致如下:
+//! cpu  4705 356 584 3699176 23060 0 277 0 0 0
+//! cpu0 1393 280 287 924366 5670 0 216 0 0 0
+//! cpu1 1132 22 99 925302 5890 0 16 0 0 0
+//! intr 1462898 ...
+//! 以 cpu 开头的行依次为 user、nice、system、idle、iowait、irq、softirq、steal、guest、guest_nice,
+//! 单位是 jiffies (通常为 1/100 秒)。第一行 cpu 是所有核心的汇总, cpuN 是每个核心。
+//! 这些值是自系统启动以来的累计值, 计算使用率需要对两次采样求差。
+
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::node_exporter::collector::{Collector, MetricFamily};
+use crate::node_exporter::sampler::{Sampler, Snapshot};
+
+// 后台采样间隔
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// CpuTimes 是 /proc/stat 中一行 cpu 的累计 jiffies
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct CpuTimes {
+    pub user: u64,
+    pub nice: u64,
+    pub system: u64,
+    pub idle: u64,
+    pub iowait: u64,
+    pub irq: u64,
+    pub softirq: u64,
+    pub steal: u64,
+    pub guest: u64,
+    pub guest_nice: u64,
+}
+
+impl CpuTimes {
+    // total 返回总 jiffies, guest 和 guest_nice 已经计入 user 和 nice, 不能重复累加
+    pub fn total(&self) -> u64 {
+        self.user + self.nice + self.system + self.idle + self.iowait
+            + self.irq + self.softirq + self.steal
+    }
+
+    fn parse(fields: &[&str]) -> Result<Self, io::Error> {
+        // 老内核没有 steal/guest/guest_nice, 缺失的字段按 0 处理
+        let mut values = [0u64; 10];
+        for (value, field) in values.iter_mut().zip(fields) {
+            *value = field.parse::<u64>()
+                .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("Failed to parse cpu jiffies: {}", e)))?;
+        }
+
+        Ok(Self {
+            user: values[0],
+            nice: values[1],
+            system: values[2],
+            idle: values[3],
+            iowait: values[4],
+            irq: values[5],
+            softirq: values[6],
+            steal: values[7],
+            guest: values[8],
+            guest_nice: values[9],
+        })
+    }
+}
+
+// CpuStat 是一次 /proc/stat 采样的结果
+#[derive(Debug, Clone, Default)]
+pub struct CpuStat {
+    // 所有核心的汇总
+    pub total: CpuTimes,
+    // 每个核心, 按 cpuN 中的 N 排序
+    pub cores: Vec<(u32, CpuTimes)>,
+}
+
+impl CpuStat {
+    // 从 /proc/stat 中读取一次采样
+    pub fn init() -> Result<Self, io::Error> {
+        let content = fs::read_to_string("/proc/stat")?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, io::Error> {
+        let mut stat = Self::default();
+        let mut found_total = false;
+
+        for line in content.lines() {
+            let fields = line.split_whitespace().collect::<Vec<_>>();
+            let Some((key, values)) = fields.split_first() else {
+                continue;
+            };
+
+            if *key == "cpu" {
+                stat.total = CpuTimes::parse(values)?;
+                found_total = true;
+            } else if let Some(id) = key.strip_prefix("cpu").and_then(|id| id.parse::<u32>().ok()) {
+                stat.cores.push((id, CpuTimes::parse(values)?));
+            }
+        }
+
+        if !found_total {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "Missing cpu line in /proc/stat"));
+        }
+
+        stat.cores.sort_by_key(|(id, _)| *id);
+        Ok(stat)
+    }
+}
+
+// CpuUsage 是两次采样之间各项时间所占的百分比
+#[derive(Debug, Clone, Serialize)]
+pub struct CpuUsage {
+    // "cpu" 表示汇总, "cpu0"、"cpu1" 表示具体核心
+    pub cpu: String,
+    // 非空闲时间占比
+    pub usage: f64,
+    pub user: f64,
+    pub nice: f64,
+    pub system: f64,
+    pub idle: f64,
+    pub iowait: f64,
+    pub irq: f64,
+    pub softirq: f64,
+    pub steal: f64,
+    pub guest: f64,
+}
+
+impl CpuUsage {
+    // between 计算 prev 到 cur 之间的使用率
+    pub fn between(cpu: &str, prev: &CpuTimes, cur: &CpuTimes) -> Self {
+        let total = cur.total().saturating_sub(prev.total());
+        let percent = |cur: u64, prev: u64| {
+            if total == 0 {
+                0.0
+            } else {
+                cur.saturating_sub(prev) as f64 / total as f64 * 100.0
+            }
+        };
+
+        let idle = percent(cur.idle, prev.idle);
+        let iowait = percent(cur.iowait, prev.iowait);
+
+        Self {
+            cpu: cpu.to_string(),
+            usage: if total == 0 { 0.0 } else { 100.0 - idle - iowait },
+            user: percent(cur.user, prev.user),
+            nice: percent(cur.nice, prev.nice),
+            system: percent(cur.system, prev.system),
+            idle,
+            iowait,
+            irq: percent(cur.irq, prev.irq),
+            softirq: percent(cur.softirq, prev.softirq),
+            steal: percent(cur.steal, prev.steal),
+            guest: percent(cur.guest, prev.guest),
+        }
+    }
+}
+
+// CpuUsageSnapshot 是某个采样周期内汇总和每个核心的使用率
+#[derive(Debug, Clone, Serialize)]
+pub struct CpuUsageSnapshot {
+    pub total: CpuUsage,
+    pub cores: Vec<CpuUsage>,
+}
+
+impl CpuUsageSnapshot {
+    pub fn between(prev: &CpuStat, cur: &CpuStat) -> Self {
+        let total = CpuUsage::between("cpu", &prev.total, &cur.total);
+
+        // 核心上下线时前一次采样里可能没有这个核心, 此时按启动以来的累计值计算
+        let cores = cur.cores.iter()
+            .map(|(id, times)| {
+                let prev_times = prev.cores.iter()
+                    .find(|(prev_id, _)| prev_id == id)
+                    .map(|(_, times)| *times)
+                    .unwrap_or_default();
+                CpuUsage::between(&format!("cpu{}", id), &prev_times, times)
+            })
+            .collect();
+
+        Self { total, cores }
+    }
+}
+
+impl Snapshot for CpuStat {
+    type Output = CpuUsageSnapshot;
+
+    const SOURCE: &'static str = "/proc/stat";
+    const INTERVAL: Duration = SAMPLE_INTERVAL;
+
+    fn read() -> Result<Self, io::Error> {
+        Self::init()
+    }
+
+    fn between(prev: &Self, cur: &Self, _elapsed: Duration) -> CpuUsageSnapshot {
+        CpuUsageSnapshot::between(prev, cur)
+    }
+}
+
+// CpuSampler 在后台定期读取 /proc/stat, 保存最近一个周期的使用率
+pub type CpuSampler = Sampler<CpuStat>;
+
+impl Sampler<CpuStat> {
+    // usage 返回最近一个周期的使用率, 第一个周期还没结束时返回启动以来的平均使用率
+    pub fn usage(&self) -> Result<CpuUsageSnapshot, io::Error> {
+        match self.latest() {
+            Some(usage) => Ok(usage),
+            None => Ok(CpuUsageSnapshot::between(&CpuStat::default(), &CpuStat::init()?)),
+        }
+    }
+}
+
+// CpuCollector 把 /proc/stat 导出为 node_cpu_seconds_total 指标
+pub struct CpuCollector;
+
+impl Collector for CpuCollector {
+    fn name(&self) -> &'static str {
+        "cpu"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let stat = CpuStat::init()?;
+        let ticks = clock_ticks();
+
+        let mut seconds = MetricFamily::counter(
+            "node_cpu_seconds_total",
+            "Seconds the CPUs spent in each mode.",
+        );
+        let mut guest = MetricFamily::counter(
+            "node_cpu_guest_seconds_total",
+            "Seconds the CPUs spent in guests (VMs) for each mode.",
+        );
+
+        for (id, times) in &stat.cores {
+            let cpu = id.to_string();
+            let modes = [
+                ("user", times.user),
+                ("nice", times.nice),
+                ("system", times.system),
+                ("idle", times.idle),
+                ("iowait", times.iowait),
+                ("irq", times.irq),
+                ("softirq", times.softirq),
+                ("steal", times.steal),
+            ];
+            for (mode, value) in modes {
+                seconds.add(&[("cpu", &cpu), ("mode", mode)], value as f64 / ticks);
+            }
+            guest.add(&[("cpu", &cpu), ("mode", "user")], times.guest as f64 / ticks);
+            guest.add(&[("cpu", &cpu), ("mode", "nice")], times.guest_nice as f64 / ticks);
+        }
+
+        Ok(vec![seconds, guest])
+    }
+}
+
+// clock_ticks 返回每秒的 jiffies 数 (USER_HZ)
+pub fn clock_ticks() -> f64 {
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    if ticks > 0 { ticks as f64 } else { 100.0 }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PROC_STAT: &str = "\
+cpu  4705 356 584 3699176 23060 0 277 10 20 5
+cpu1 1132 22 99 925302 5890 0 16 0 0 0
+cpu0 1393 280 287 924366 5670 0 216 10 20 5
+intr 1462898 0 0
+ctxt 2938211
+btime 1718000000
+";
+
+    #[test]
+    fn parses_total_and_cores() {
+        let stat = CpuStat::parse(PROC_STAT).unwrap();
+
+        assert_eq!(stat.total, CpuTimes {
+            user: 4705, nice: 356, system: 584, idle: 3699176, iowait: 23060,
+            irq: 0, softirq: 277, steal: 10, guest: 20, guest_nice: 5,
+        });
+        // 按核心编号排序
+        assert_eq!(stat.cores.iter().map(|(id, _)| *id).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(stat.cores[1].1.idle, 925302);
+        // guest 已经计入 user, 不重复累加
+        assert_eq!(stat.total.total(), 4705 + 356 + 584 + 3699176 + 23060 + 277 + 10);
+    }
+
+    #[test]
+    fn parses_old_kernels_without_steal_and_guest() {
+        let stat = CpuStat::parse("cpu  10 20 30 40 50 60 70\n").unwrap();
+        assert_eq!((stat.total.softirq, stat.total.steal, stat.total.guest_nice), (70, 0, 0));
+        assert!(stat.cores.is_empty());
+    }
+
+    #[test]
+    fn rejects_invalid_stat() {
+        assert!(CpuStat::parse("intr 1 2 3\n").is_err());
+        assert!(CpuStat::parse("cpu  1 2 x 4\n").is_err());
+    }
+
+    #[test]
+    fn computes_usage_between_samples() {
+        let prev = CpuTimes { user: 100, system: 50, idle: 800, iowait: 50, ..Default::default() };
+        let cur = CpuTimes { user: 150, system: 75, idle: 950, iowait: 75, steal: 25, ..Default::default() };
+
+        // 总增量 = 50 + 25 + 150 + 25 + 25 = 275
+        let usage = CpuUsage::between("cpu0", &prev, &cur);
+        assert_eq!(usage.cpu, "cpu0");
+        assert!((usage.user - 50.0 / 275.0 * 100.0).abs() < 1e-9);
+        assert!((usage.idle - 150.0 / 275.0 * 100.0).abs() < 1e-9);
+        assert!((usage.usage - 100.0 / 275.0 * 100.0).abs() < 1e-9);
+        assert!((usage.steal - 25.0 / 275.0 * 100.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn usage_is_zero_without_elapsed_jiffies() {
+        let times = CpuTimes { user: 100, idle: 900, ..Default::default() };
+        let usage = CpuUsage::between("cpu", &times, &times);
+        assert_eq!((usage.usage, usage.user, usage.idle), (0.0, 0.0, 0.0));
+
+        // 计数器回绕或重置时不会出现负数
+        let usage = CpuUsage::between("cpu", &times, &CpuTimes::default());
+        assert_eq!(usage.usage, 0.0);
+    }
+
+    #[test]
+    fn snapshot_matches_cores_by_id() {
+        let prev = CpuStat::parse("cpu  0 0 0 0\ncpu0 0 0 0 0\n").unwrap();
+        let cur = CpuStat::parse("cpu  10 0 0 10\ncpu0 5 0 0 5\ncpu1 5 0 0 5\n").unwrap();
+
+        let snapshot = CpuUsageSnapshot::between(&prev, &cur);
+        assert_eq!(snapshot.total.usage, 50.0);
+        assert_eq!(snapshot.cores.iter().map(|core| core.cpu.as_str()).collect::<Vec<_>>(), vec!["cpu0", "cpu1"]);
+        // 新上线的 cpu1 按启动以来的累计值计算
+        assert_eq!(snapshot.cores[1].usage, 50.0);
+    }
+}
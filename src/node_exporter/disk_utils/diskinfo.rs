@@ -0,0 +1,532 @@
+//! 挂载信息在 /proc/self/mountinfo 文件下, 每行一个挂载点, 内容大致如下:
+//! 36 35 98:0 /mnt1 /mnt2 rw,noatime master:1 - ext3 /dev/root rw,errors=continue
+//! 依次为挂载 id、父挂载 id、主:次设备号、根目录、挂载点、挂载选项、若干可选字段,
+//! "-" 之后是文件系统类型、挂载源和超级块选项。路径中的空格等字符会被转义为 \040 这样的八进制。
+//!
+//! 块设备 I/O 统计在 /proc/diskstats 文件下, 内容大致如下:
+//!  259       0 nvme0n1 41539 10383 3339058 9811 185032 66377 6286280 90373 0 113220 100184 0 0 0 0
+//! 依次为主设备号、次设备号、设备名、读完成次数、读合并次数、读扇区数、读耗时(ms)、
+//! 写完成次数、写合并次数、写扇区数、写耗时(ms)、正在进行的 I/O 数、I/O 耗时(ms)、加权 I/O 耗时(ms)。
+//! 扇区固定为 512 字节, 这些值都是累计值, 计算速率需要对两次采样求差。
+
+use std::fs;
+use std::io;
+use std::time::Duration;
+
+use nix::sys::statvfs::statvfs;
+use serde::Serialize;
+
+use crate::node_exporter::collector::{Collector, MetricFamily};
+use crate::node_exporter::sampler::{Sampler, Snapshot};
+
+// 后台采样间隔
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// /proc/diskstats 中的扇区大小
+const SECTOR_SIZE: u64 = 512;
+
+// MountInfo 是 /proc/self/mountinfo 中的一行
+#[derive(Debug, Clone, Serialize)]
+pub struct MountInfo {
+    pub mount_id: u32,
+    pub major: u32,
+    pub minor: u32,
+    // 挂载源, 如 /dev/sda1
+    pub device: String,
+    pub mount_point: String,
+    pub fs_type: String,
+    pub options: String,
+}
+
+impl MountInfo {
+    // 读取当前进程可见的所有挂载点
+    pub fn mounts() -> Result<Vec<Self>, io::Error> {
+        let content = fs::read_to_string("/proc/self/mountinfo")?;
+        Ok(content.lines().filter_map(Self::parse_line).collect())
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let (left, right) = line.split_once(" - ")?;
+        let left = left.split_whitespace().collect::<Vec<_>>();
+        let mut right = right.split_whitespace();
+
+        if left.len() < 6 {
+            return None;
+        }
+
+        let (major, minor) = left[2].split_once(':')?;
+
+        Some(Self {
+            mount_id: left[0].parse().ok()?,
+            major: major.parse().ok()?,
+            minor: minor.parse().ok()?,
+            mount_point: unescape_octal(left[4]),
+            options: left[5].to_string(),
+            fs_type: right.next()?.to_string(),
+            device: unescape_octal(right.next().unwrap_or("none")),
+        })
+    }
+}
+
+// FsUsage 是通过 statvfs 得到的文件系统容量
+#[derive(Debug, Clone, Serialize)]
+pub struct FsUsage {
+    pub total_bytes: u64,
+    pub free_bytes: u64,
+    // 非 root 用户可用的空间
+    pub available_bytes: u64,
+    pub used_bytes: u64,
+    // used / (used + available), 与 df 的计算方式一致
+    pub used_percent: f64,
+    pub inodes_total: u64,
+    pub inodes_free: u64,
+    pub inodes_used: u64,
+}
+
+impl FsUsage {
+    pub fn of(mount_point: &str) -> Result<Self, io::Error> {
+        let stat = statvfs(mount_point).map_err(io::Error::from)?;
+        let fragment = stat.fragment_size() as u64;
+
+        let total_bytes = stat.blocks() as u64 * fragment;
+        let free_bytes = stat.blocks_free() as u64 * fragment;
+        let available_bytes = stat.blocks_available() as u64 * fragment;
+        let used_bytes = total_bytes.saturating_sub(free_bytes);
+        let inodes_total = stat.files() as u64;
+        let inodes_free = stat.files_free() as u64;
+
+        let usable = used_bytes + available_bytes;
+        Ok(Self {
+            total_bytes,
+            free_bytes,
+            available_bytes,
+            used_bytes,
+            used_percent: if usable == 0 { 0.0 } else { used_bytes as f64 / usable as f64 * 100.0 },
+            inodes_total,
+            inodes_free,
+            inodes_used: inodes_total.saturating_sub(inodes_free),
+        })
+    }
+}
+
+// MountUsage 是挂载点及其容量
+#[derive(Debug, Clone, Serialize)]
+pub struct MountUsage {
+    #[serde(flatten)]
+    pub mount: MountInfo,
+    pub usage: FsUsage,
+}
+
+impl MountUsage {
+    // filesystems 返回所有挂载点的容量, include_virtual 为 false 时跳过 proc、sysfs 等容量为 0 的虚拟文件系统
+    pub fn filesystems(include_virtual: bool) -> Result<Vec<Self>, io::Error> {
+        let mut result = Vec::new();
+
+        for mount in MountInfo::mounts()? {
+            // 无权限访问或已卸载的挂载点直接跳过
+            let Ok(usage) = FsUsage::of(&mount.mount_point) else {
+                continue;
+            };
+            if !include_virtual && usage.total_bytes == 0 {
+                continue;
+            }
+            result.push(Self { mount, usage });
+        }
+
+        Ok(result)
+    }
+
+    // filesystems_within 与 filesystems 相同, 但每个挂载点的 statvfs 在阻塞线程池中并发执行,
+    // 失联的 NFS 等挂载点会让 statvfs 一直阻塞, 超过 timeout 仍未返回的挂载点直接跳过
+    pub async fn filesystems_within(include_virtual: bool, timeout: Duration) -> Result<Vec<Self>, io::Error> {
+        let mounts = tokio::task::spawn_blocking(MountInfo::mounts).await.map_err(io::Error::other)??;
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for (index, mount) in mounts.iter().enumerate() {
+            let mount_point = mount.mount_point.clone();
+            tasks.spawn_blocking(move || (index, FsUsage::of(&mount_point)));
+        }
+
+        let mut usages = vec![None; mounts.len()];
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            match tokio::time::timeout_at(deadline, tasks.join_next()).await {
+                Ok(Some(Ok((index, Ok(usage))))) => usages[index] = Some(usage),
+                Ok(Some(_)) => {}
+                Ok(None) => break,
+                Err(_) => {
+                    eprintln!("statvfs timed out on {} mount points", tasks.len());
+                    // 阻塞中的 statvfs 无法取消, 只是不再等待它们
+                    tasks.detach_all();
+                    break;
+                }
+            }
+        }
+
+        Ok(mounts.into_iter()
+            .zip(usages)
+            .filter_map(|(mount, usage)| usage.map(|usage| Self { mount, usage }))
+            .filter(|fs| include_virtual || fs.usage.total_bytes != 0)
+            .collect())
+    }
+}
+
+// DiskStat 是 /proc/diskstats 中一个块设备的累计值
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct DiskStat {
+    pub name: String,
+    pub major: u32,
+    pub minor: u32,
+    pub reads_completed: u64,
+    pub reads_merged: u64,
+    pub sectors_read: u64,
+    pub read_time_ms: u64,
+    pub writes_completed: u64,
+    pub writes_merged: u64,
+    pub sectors_written: u64,
+    pub write_time_ms: u64,
+    pub ios_in_progress: u64,
+    pub io_time_ms: u64,
+    pub weighted_io_time_ms: u64,
+}
+
+impl DiskStat {
+    // 读取所有块设备的 I/O 统计, 跳过 ram、loop 等虚拟设备
+    pub fn devices() -> Result<Vec<Self>, io::Error> {
+        let content = fs::read_to_string("/proc/diskstats")?;
+        Ok(content.lines()
+            .filter_map(Self::parse_line)
+            .filter(|stat| !is_virtual_device(&stat.name))
+            .collect())
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 14 {
+            return None;
+        }
+
+        let value = |index: usize| fields[index].parse::<u64>().ok();
+
+        Some(Self {
+            major: fields[0].parse().ok()?,
+            minor: fields[1].parse().ok()?,
+            name: fields[2].to_string(),
+            reads_completed: value(3)?,
+            reads_merged: value(4)?,
+            sectors_read: value(5)?,
+            read_time_ms: value(6)?,
+            writes_completed: value(7)?,
+            writes_merged: value(8)?,
+            sectors_written: value(9)?,
+            write_time_ms: value(10)?,
+            ios_in_progress: value(11)?,
+            io_time_ms: value(12)?,
+            weighted_io_time_ms: value(13)?,
+        })
+    }
+
+    pub fn read_bytes(&self) -> u64 {
+        self.sectors_read * SECTOR_SIZE
+    }
+
+    pub fn written_bytes(&self) -> u64 {
+        self.sectors_written * SECTOR_SIZE
+    }
+}
+
+// DiskIo 是两次采样之间一个块设备的 I/O 增量和速率
+#[derive(Debug, Clone, Serialize)]
+pub struct DiskIo {
+    pub name: String,
+    // 采样间隔 (秒)
+    pub interval: f64,
+    pub read_ops: u64,
+    pub write_ops: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    pub io_time_ms: u64,
+    pub read_ops_per_sec: f64,
+    pub write_ops_per_sec: f64,
+    pub read_bytes_per_sec: f64,
+    pub write_bytes_per_sec: f64,
+    // 设备繁忙时间占比, 与 iostat 的 %util 一致
+    pub utilization: f64,
+    pub ios_in_progress: u64,
+}
+
+impl DiskIo {
+    pub fn between(prev: &DiskStat, cur: &DiskStat, elapsed: Duration) -> Self {
+        let interval = elapsed.as_secs_f64();
+        let rate = |delta: u64| if interval > 0.0 { delta as f64 / interval } else { 0.0 };
+
+        let read_ops = cur.reads_completed.saturating_sub(prev.reads_completed);
+        let write_ops = cur.writes_completed.saturating_sub(prev.writes_completed);
+        let read_bytes = cur.read_bytes().saturating_sub(prev.read_bytes());
+        let write_bytes = cur.written_bytes().saturating_sub(prev.written_bytes());
+        let io_time_ms = cur.io_time_ms.saturating_sub(prev.io_time_ms);
+
+        Self {
+            name: cur.name.clone(),
+            interval,
+            read_ops,
+            write_ops,
+            read_bytes,
+            write_bytes,
+            io_time_ms,
+            read_ops_per_sec: rate(read_ops),
+            write_ops_per_sec: rate(write_ops),
+            read_bytes_per_sec: rate(read_bytes),
+            write_bytes_per_sec: rate(write_bytes),
+            utilization: (rate(io_time_ms) / 1000.0 * 100.0).min(100.0),
+            ios_in_progress: cur.ios_in_progress,
+        }
+    }
+}
+
+impl Snapshot for Vec<DiskStat> {
+    type Output = Vec<DiskIo>;
+
+    const SOURCE: &'static str = "/proc/diskstats";
+    const INTERVAL: Duration = SAMPLE_INTERVAL;
+
+    fn read() -> Result<Self, io::Error> {
+        DiskStat::devices()
+    }
+
+    // 新出现的设备没有上一次采样, 等下一个周期再计算
+    fn between(prev: &Self, cur: &Self, elapsed: Duration) -> Vec<DiskIo> {
+        cur.iter()
+            .filter_map(|stat| {
+                prev.iter()
+                    .find(|prev| prev.name == stat.name)
+                    .map(|prev| DiskIo::between(prev, stat, elapsed))
+            })
+            .collect()
+    }
+}
+
+// DiskSampler 在后台定期读取 /proc/diskstats, 保存最近一个周期的 I/O 速率
+pub type DiskSampler = Sampler<Vec<DiskStat>>;
+
+impl Sampler<Vec<DiskStat>> {
+    // io 返回最近一个周期各块设备的 I/O 速率, 第一个周期结束前为空
+    pub fn io(&self) -> Vec<DiskIo> {
+        self.latest().unwrap_or_default()
+    }
+}
+
+// DiskCollector 导出 node_filesystem_* 和 node_disk_* 指标
+pub struct DiskCollector;
+
+impl Collector for DiskCollector {
+    fn name(&self) -> &'static str {
+        "disk"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let mut size = MetricFamily::gauge("node_filesystem_size_bytes", "Filesystem size in bytes.");
+        let mut free = MetricFamily::gauge("node_filesystem_free_bytes", "Filesystem free space in bytes.");
+        let mut avail = MetricFamily::gauge(
+            "node_filesystem_avail_bytes",
+            "Filesystem space available to non-root users in bytes.",
+        );
+        let mut files = MetricFamily::gauge("node_filesystem_files", "Filesystem total file nodes.");
+        let mut files_free = MetricFamily::gauge("node_filesystem_files_free", "Filesystem total free file nodes.");
+
+        for fs in MountUsage::filesystems(false)? {
+            let labels = [
+                ("device", fs.mount.device.as_str()),
+                ("fstype", fs.mount.fs_type.as_str()),
+                ("mountpoint", fs.mount.mount_point.as_str()),
+            ];
+            size.add(&labels, fs.usage.total_bytes as f64);
+            free.add(&labels, fs.usage.free_bytes as f64);
+            avail.add(&labels, fs.usage.available_bytes as f64);
+            files.add(&labels, fs.usage.inodes_total as f64);
+            files_free.add(&labels, fs.usage.inodes_free as f64);
+        }
+
+        let mut reads = MetricFamily::counter(
+            "node_disk_reads_completed_total",
+            "The total number of reads completed successfully.",
+        );
+        let mut writes = MetricFamily::counter(
+            "node_disk_writes_completed_total",
+            "The total number of writes completed successfully.",
+        );
+        let mut read_bytes = MetricFamily::counter(
+            "node_disk_read_bytes_total",
+            "The total number of bytes read successfully.",
+        );
+        let mut written_bytes = MetricFamily::counter(
+            "node_disk_written_bytes_total",
+            "The total number of bytes written successfully.",
+        );
+        let mut io_time = MetricFamily::counter(
+            "node_disk_io_time_seconds_total",
+            "Total seconds spent doing I/Os.",
+        );
+        let mut io_now = MetricFamily::gauge("node_disk_io_now", "The number of I/Os currently in progress.");
+
+        for stat in DiskStat::devices()? {
+            let labels = [("device", stat.name.as_str())];
+            reads.add(&labels, stat.reads_completed as f64);
+            writes.add(&labels, stat.writes_completed as f64);
+            read_bytes.add(&labels, stat.read_bytes() as f64);
+            written_bytes.add(&labels, stat.written_bytes() as f64);
+            io_time.add(&labels, stat.io_time_ms as f64 / 1000.0);
+            io_now.add(&labels, stat.ios_in_progress as f64);
+        }
+
+        Ok(vec![size, free, avail, files, files_free, reads, writes, read_bytes, written_bytes, io_time, io_now])
+    }
+}
+
+// is_virtual_device 判断是否为 ram、zram、loop、软驱等不需要关注的设备
+fn is_virtual_device(name: &str) -> bool {
+    ["ram", "zram", "loop", "fd"].iter().any(|prefix| {
+        name.strip_prefix(prefix)
+            .is_some_and(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+    })
+}
+
+// unescape_octal 还原 mountinfo 中 \040 形式的八进制转义
+fn unescape_octal(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut result = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'\\' && i + 3 < bytes.len() && bytes[i + 1..i + 4].iter().all(|b| (b'0'..=b'7').contains(b)) {
+            let octal = std::str::from_utf8(&bytes[i + 1..i + 4]).unwrap_or("0");
+            if let Ok(byte) = u8::from_str_radix(octal, 8) {
+                result.push(byte);
+                i += 4;
+                continue;
+            }
+        }
+        result.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&result).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_mountinfo_line() {
+        let mount = MountInfo::parse_line(
+            "36 35 98:0 /mnt1 /mnt/my\\040disk rw,noatime master:1 - ext3 /dev/root rw,errors=continue",
+        ).unwrap();
+
+        assert_eq!(mount.mount_id, 36);
+        assert_eq!((mount.major, mount.minor), (98, 0));
+        assert_eq!(mount.mount_point, "/mnt/my disk");
+        assert_eq!(mount.options, "rw,noatime");
+        assert_eq!(mount.fs_type, "ext3");
+        assert_eq!(mount.device, "/dev/root");
+    }
+
+    #[test]
+    fn parses_mountinfo_line_without_optional_fields() {
+        let mount = MountInfo::parse_line("22 1 0:21 / /proc rw,nosuid - proc proc rw").unwrap();
+        assert_eq!(mount.mount_point, "/proc");
+        assert_eq!(mount.fs_type, "proc");
+
+        assert!(MountInfo::parse_line("22 1 0:21 / /proc rw,nosuid").is_none());
+        assert!(MountInfo::parse_line("22 1 bad / /proc rw - proc proc rw").is_none());
+    }
+
+    #[test]
+    fn unescapes_octal_sequences() {
+        assert_eq!(unescape_octal("/mnt/a\\040b"), "/mnt/a b");
+        assert_eq!(unescape_octal("tab\\011x\\134y"), "tab\tx\\y");
+        // 不完整或非八进制的转义保持原样
+        assert_eq!(unescape_octal("a\\04"), "a\\04");
+        assert_eq!(unescape_octal("a\\089"), "a\\089");
+        assert_eq!(unescape_octal("plain"), "plain");
+    }
+
+    #[test]
+    fn parses_diskstats_line() {
+        let stat = DiskStat::parse_line(
+            " 259       0 nvme0n1 41539 10383 3339058 9811 185032 66377 6286280 90373 0 113220 100184 0 0 0 0",
+        ).unwrap();
+
+        assert_eq!(stat.name, "nvme0n1");
+        assert_eq!((stat.major, stat.minor), (259, 0));
+        assert_eq!(stat.reads_completed, 41539);
+        assert_eq!(stat.read_bytes(), 3339058 * 512);
+        assert_eq!(stat.written_bytes(), 6286280 * 512);
+        assert_eq!(stat.io_time_ms, 113220);
+        assert_eq!(stat.weighted_io_time_ms, 100184);
+
+        assert!(DiskStat::parse_line("8 0 sda 1 2 3").is_none());
+    }
+
+    #[test]
+    fn skips_virtual_devices() {
+        assert!(is_virtual_device("loop0"));
+        assert!(is_virtual_device("ram12"));
+        assert!(is_virtual_device("zram0"));
+        assert!(!is_virtual_device("sda"));
+        assert!(!is_virtual_device("fd"));
+        assert!(!is_virtual_device("loopback"));
+    }
+
+    #[test]
+    fn computes_io_rates_between_samples() {
+        let prev = DiskStat {
+            name: "sda".to_string(),
+            reads_completed: 100,
+            sectors_read: 1000,
+            writes_completed: 50,
+            sectors_written: 2000,
+            io_time_ms: 1000,
+            ..Default::default()
+        };
+        let cur = DiskStat {
+            reads_completed: 300,
+            sectors_read: 5000,
+            writes_completed: 150,
+            sectors_written: 2400,
+            io_time_ms: 2000,
+            ios_in_progress: 3,
+            ..prev.clone()
+        };
+
+        let io = DiskIo::between(&prev, &cur, Duration::from_secs(2));
+        assert_eq!(io.name, "sda");
+        assert_eq!((io.read_ops, io.write_ops), (200, 100));
+        assert_eq!((io.read_bytes, io.write_bytes), (4000 * 512, 400 * 512));
+        assert_eq!(io.read_ops_per_sec, 100.0);
+        assert_eq!(io.write_bytes_per_sec, 400.0 * 512.0 / 2.0);
+        assert_eq!(io.utilization, 50.0);
+        assert_eq!(io.ios_in_progress, 3);
+    }
+
+    #[test]
+    fn io_rates_survive_counter_reset_and_zero_interval() {
+        let prev = DiskStat { name: "sda".to_string(), reads_completed: 500, io_time_ms: 9000, ..Default::default() };
+        let cur = DiskStat { name: "sda".to_string(), reads_completed: 10, io_time_ms: 100, ..Default::default() };
+
+        let io = DiskIo::between(&prev, &cur, Duration::from_secs(1));
+        assert_eq!((io.read_ops, io.io_time_ms, io.utilization), (0, 0, 0.0));
+
+        let io = DiskIo::between(&cur, &cur, Duration::ZERO);
+        assert_eq!((io.read_ops_per_sec, io.utilization), (0.0, 0.0));
+    }
+
+    #[test]
+    fn skips_devices_missing_from_previous_sample() {
+        let sda = DiskStat { name: "sda".to_string(), ..Default::default() };
+        let sdb = DiskStat { name: "sdb".to_string(), ..Default::default() };
+
+        let io = <Vec<DiskStat> as Snapshot>::between(&vec![sda.clone()], &vec![sda, sdb], Duration::from_secs(1));
+        assert_eq!(io.iter().map(|io| io.name.as_str()).collect::<Vec<_>>(), ["sda"]);
+    }
+}
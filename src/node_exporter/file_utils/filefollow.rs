@@ -0,0 +1,290 @@
+//! 与 tail -F 相同, 按固定间隔检查文件的变化:
+//! 1. 文件变长: 从上次读到的位置继续读取, 不完整的最后一行留到下次
+//! 2. 文件变短: 被截断 (如 > app.log 或 copytruncate), 从头开始读取
+//! 3. 路径对应的 inode 变了: 被 logrotate 重命名后创建了新文件, 先把旧文件剩余的内容读完, 再打开新文件从头读取。
+//!    每次最多读取 READ_CHUNK_SIZE, 旧文件剩余的内容较多时分多次读完再切换
+//!
+//! 路径暂时不存在 (重命名后还没创建新文件) 时继续读取旧文件, 等新文件出现后再切换。
+//! 打开文件时不跟随符号链接, 切换到新文件前先由调用方检查路径是否允许访问。
+
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::os::unix::fs::{MetadataExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+
+// 倒序查找最后 N 行时每次读取的块大小
+const TAIL_CHUNK_SIZE: u64 = 64 * 1024;
+
+// 每次 poll 最多读取的字节数
+const READ_CHUNK_SIZE: u64 = TAIL_CHUNK_SIZE * 16;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum FollowEvent {
+    Line(String),
+    // 文件被截断, 从头开始读取
+    Truncated,
+    // 文件被轮转, 切换到同一路径下的新文件
+    Rotated,
+}
+
+// FileFollower 记录正在跟踪的文件和读取位置
+pub struct FileFollower {
+    path: PathBuf,
+    file: File,
+    // (设备号, inode), 用来判断路径是否指向了新文件
+    identity: (u64, u64),
+    position: u64,
+    // 还没有遇到换行符的内容
+    partial: Vec<u8>,
+    // 单行的最大长度, 超过时直接作为一行输出
+    max_line: usize,
+}
+
+impl FileFollower {
+    // open 打开文件并返回最后 lines 行, 之后从文件末尾开始跟踪。
+    // 查找最后 lines 行时最多向前读取 max_line 个字节, 没有换行符的大文件不会被整个读入内存
+    pub fn open<P>(path: P, lines: usize, max_line: usize) -> io::Result<(Self, Vec<String>)>
+        where
+            P: AsRef<Path>,
+    {
+        let path = path.as_ref().to_path_buf();
+        let mut file = open_nofollow(&path)?;
+        let metadata = file.metadata()?;
+        if metadata.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is a directory", path.display())));
+        }
+
+        let position = metadata.len();
+        let max_line = max_line.max(1);
+        let tail = last_lines(&mut file, position, lines, max_line as u64)?;
+
+        let follower = Self {
+            path,
+            file,
+            identity: (metadata.dev(), metadata.ino()),
+            position,
+            partial: Vec::new(),
+            max_line,
+        };
+        Ok((follower, tail))
+    }
+
+    // poll 读取自上次以来追加的内容, 并处理截断和轮转。
+    // 路径指向了新文件时先用 is_denied 检查, 路径被换成禁止访问的文件或符号链接时返回错误
+    pub fn poll(&mut self, is_denied: &dyn Fn(&Path) -> bool) -> io::Result<Vec<FollowEvent>> {
+        let mut events = Vec::new();
+
+        let len = self.file.metadata()?.len();
+        if len < self.position {
+            self.position = 0;
+            self.partial.clear();
+            events.push(FollowEvent::Truncated);
+        }
+        let read = self.read_appended(&mut events)?;
+
+        // 路径不存在时可能正在轮转, 下次再检查
+        let current = match fs::symlink_metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(events),
+            Err(e) => return Err(e),
+        };
+        // 旧文件还没有读完时先不切换, 下次继续读取
+        if (current.dev(), current.ino()) != self.identity && read < READ_CHUNK_SIZE {
+            // 旧文件中没有换行符结尾的最后一行也输出
+            self.flush_partial(&mut events);
+            if is_denied(&self.path) {
+                return Err(io::Error::new(
+                    io::ErrorKind::PermissionDenied,
+                    format!("Access to {} is denied", self.path.display()),
+                ));
+            }
+            self.file = open_nofollow(&self.path)?;
+            let metadata = self.file.metadata()?;
+            self.identity = (metadata.dev(), metadata.ino());
+            self.position = 0;
+            events.push(FollowEvent::Rotated);
+            self.read_appended(&mut events)?;
+        }
+
+        Ok(events)
+    }
+
+    // read_appended 从上次的位置读取最多 READ_CHUNK_SIZE 个字节, 返回读取的字节数
+    fn read_appended(&mut self, events: &mut Vec<FollowEvent>) -> io::Result<u64> {
+        self.file.seek(SeekFrom::Start(self.position))?;
+        let mut buffer = Vec::new();
+        let read = self.file.by_ref().take(READ_CHUNK_SIZE).read_to_end(&mut buffer)? as u64;
+        self.position += read;
+
+        for byte in buffer {
+            if byte == b'\n' {
+                self.emit_line(events);
+            } else {
+                self.partial.push(byte);
+                if self.partial.len() >= self.max_line {
+                    self.flush_partial(events);
+                }
+            }
+        }
+        Ok(read)
+    }
+
+    // flush_partial 输出没有换行符结尾的内容
+    fn flush_partial(&mut self, events: &mut Vec<FollowEvent>) {
+        if !self.partial.is_empty() {
+            self.emit_line(events);
+        }
+    }
+
+    fn emit_line(&mut self, events: &mut Vec<FollowEvent>) {
+        let line = self.partial.strip_suffix(b"\r").unwrap_or(&self.partial);
+        events.push(FollowEvent::Line(String::from_utf8_lossy(line).into_owned()));
+        self.partial.clear();
+    }
+}
+
+// open_nofollow 只读打开文件, 路径是符号链接时失败
+fn open_nofollow(path: &Path) -> io::Result<File> {
+    File::options().read(true).custom_flags(libc::O_NOFOLLOW).open(path)
+}
+
+// last_lines 从文件末尾向前按块读取, 直到找到 lines 行或者读取了 max_bytes 个字节为止。
+// 只统计新读取的块中的换行符, 所有块读完后再拼接
+fn last_lines(file: &mut File, len: u64, lines: usize, max_bytes: u64) -> io::Result<Vec<String>> {
+    if lines == 0 || len == 0 {
+        return Ok(Vec::new());
+    }
+
+    let end = len.saturating_sub(max_bytes);
+    let mut start = len;
+    let mut blocks = Vec::new();
+    let mut newlines = 0;
+    while start > end && newlines < lines {
+        let chunk = TAIL_CHUNK_SIZE.min(start - end);
+        start -= chunk;
+
+        let mut block = vec![0; chunk as usize];
+        file.seek(SeekFrom::Start(start))?;
+        file.read_exact(&mut block)?;
+        // 末尾的换行符不算作新的一行
+        let counted = if blocks.is_empty() { block.strip_suffix(b"\n").unwrap_or(&block) } else { &block };
+        newlines += counted.iter().filter(|byte| **byte == b'\n').count();
+        blocks.push(block);
+    }
+
+    let buffer = blocks.into_iter().rev().flatten().collect::<Vec<_>>();
+    let content = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+    let all = content.split(|byte| *byte == b'\n').collect::<Vec<_>>();
+    Ok(all[all.len().saturating_sub(lines)..].iter()
+        .map(|line| String::from_utf8_lossy(line.strip_suffix(b"\r").unwrap_or(line)).into_owned())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs::{self, OpenOptions};
+    use std::io::Write;
+
+    use super::{FileFollower, FollowEvent, READ_CHUNK_SIZE};
+
+    fn line(text: &str) -> FollowEvent {
+        FollowEvent::Line(text.to_string())
+    }
+
+    #[test]
+    fn returns_last_lines_then_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "one\ntwo\r\nthree\n").unwrap();
+
+        let (mut follower, tail) = FileFollower::open(&path, 2, 1024).unwrap();
+        assert_eq!(tail, vec!["two", "three"]);
+        assert!(follower.poll(&|_| false).unwrap().is_empty());
+
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(b"four\n\nfi").unwrap();
+        assert_eq!(follower.poll(&|_| false).unwrap(), vec![line("four"), line("")]);
+
+        file.write_all(b"ve\n").unwrap();
+        assert_eq!(follower.poll(&|_| false).unwrap(), vec![line("five")]);
+    }
+
+    #[test]
+    fn handles_truncation_and_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "old line\n").unwrap();
+        let (mut follower, _) = FileFollower::open(&path, 0, 1024).unwrap();
+
+        fs::write(&path, "new\n").unwrap();
+        assert_eq!(follower.poll(&|_| false).unwrap(), vec![FollowEvent::Truncated, line("new")]);
+
+        // logrotate: 重命名后旧文件还有写入, 然后创建新文件
+        fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        let mut rotated = OpenOptions::new().append(true).open(dir.path().join("app.log.1")).unwrap();
+        rotated.write_all(b"last old\n").unwrap();
+        assert_eq!(follower.poll(&|_| false).unwrap(), vec![line("last old")]);
+
+        fs::write(&path, "first new\n").unwrap();
+        assert_eq!(follower.poll(&|_| false).unwrap(), vec![FollowEvent::Rotated, line("first new")]);
+    }
+
+    #[test]
+    fn bounds_the_tail_of_files_without_newlines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, format!("{}\nend\n", "x".repeat(300 * 1024))).unwrap();
+
+        // 最多向前读取 max_line 个字节
+        let (_, tail) = FileFollower::open(&path, 2, 1024).unwrap();
+        assert_eq!(tail, vec!["x".repeat(1019), "end".to_string()]);
+        let (_, tail) = FileFollower::open(&path, 1, 1024).unwrap();
+        assert_eq!(tail, vec!["end"]);
+    }
+
+    #[test]
+    fn does_not_switch_to_denied_or_symlinked_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let (path, secret) = (dir.path().join("app.log"), dir.path().join("secret"));
+        fs::write(&path, "").unwrap();
+        fs::write(&secret, "password\n").unwrap();
+
+        let (mut follower, _) = FileFollower::open(&path, 0, 1024).unwrap();
+        fs::remove_file(&path).unwrap();
+        fs::write(&path, "rotated\n").unwrap();
+        let err = follower.poll(&|path| path.ends_with("app.log")).unwrap_err();
+        assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+
+        // 路径被换成指向其他文件的符号链接时不会读取链接指向的内容
+        let (mut follower, _) = FileFollower::open(&path, 0, 1024).unwrap();
+        fs::remove_file(&path).unwrap();
+        std::os::unix::fs::symlink(&secret, &path).unwrap();
+        assert!(follower.poll(&|_| false).is_err());
+        assert!(FileFollower::open(&path, 1, 1024).is_err());
+    }
+
+    #[test]
+    fn drains_rotated_file_before_switching() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        fs::write(&path, "").unwrap();
+        let (mut follower, _) = FileFollower::open(&path, 0, 1024).unwrap();
+
+        // 轮转前写入超过一次 poll 能读取的内容
+        let line_count = READ_CHUNK_SIZE as usize / 10 * 2;
+        let content = (0..line_count).map(|i| format!("{:09}\n", i)).collect::<String>();
+        fs::write(&path, &content).unwrap();
+        fs::rename(&path, dir.path().join("app.log.1")).unwrap();
+        fs::write(&path, "first new\n").unwrap();
+
+        let mut events = Vec::new();
+        for _ in 0..4 {
+            events.extend(follower.poll(&|_| false).unwrap());
+        }
+
+        let rotated = events.iter().position(|event| *event == FollowEvent::Rotated).unwrap();
+        assert_eq!(rotated, line_count);
+        assert_eq!(events[line_count - 1], line(&format!("{:09}", line_count - 1)));
+        assert_eq!(events[rotated + 1..], [line("first new")]);
+    }
+}
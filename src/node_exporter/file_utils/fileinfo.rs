@@ -1,15 +1,24 @@
 use std::{
+    cmp::Ordering,
+    collections::HashMap,
     env::current_dir,
-    ffi::CString,
     fs,
     fs::{File, Metadata},
-    io::{self, BufReader},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom},
+    os::unix::fs::{FileTypeExt, MetadataExt},
     path::{Path, PathBuf},
 };
+use std::sync::atomic::{self, AtomicU64};
 use std::time::SystemTime;
 
+use chrono::{DateTime, Local, TimeZone};
+use glob::Pattern;
+use nix::sys::stat::{major, minor};
+use serde::{Deserialize, Serialize};
 use walkdir::WalkDir;
-use libc::{c_short, stat};
+
+use crate::hand::node::user::{get_groupname_by_gid, get_groupnames, get_username_by_uid, get_usernames};
+use crate::node_exporter::collector::{Collector, MetricFamily};
 
 // FileInfo 结构体包含了一个文件或目录的路径和元数据
 pub struct FileInfo {
@@ -23,30 +32,33 @@ impl FileInfo {
         where
             P: AsRef<Path>
     {
-        let metadata = fs::metadata(path)?;
+        let metadata = fs::metadata(&path)?;
         Ok(Self {
             path: path.as_ref().to_path_buf(),
             metadata,
         })
     }
 
-    // get_file_user 方法用于获取文件的用户uid和gid
-    pub fn user_id(&self) -> Result<(c_short, c_short), io::Error> {
-        let c_path = CString::new(self.path.as_os_str().as_encoded_bytes())?;
-        let mut s: stat = unsafe { std::mem::zeroed() };
-        let result = unsafe { libc::stat(c_path.as_ptr(), &mut s) };
-
-        if result != 0 {
-            return Err(io::Error::last_os_error());
-        }
+    // lstat 方法与 from 相同, 但路径是符号链接时返回链接本身的元数据
+    pub fn lstat<P>(path: P) -> io::Result<Self>
+        where
+            P: AsRef<Path>
+    {
+        let metadata = fs::symlink_metadata(&path)?;
+        Ok(Self {
+            path: path.as_ref().to_path_buf(),
+            metadata,
+        })
+    }
 
-        /*Ok((unsafe { s.st_uid.try_into()? }, unsafe { s.st_gid.try_into()? }))*/
-        let uid = unsafe { s.st_uid };
-        let gid = unsafe { s.st_gid };
+    // path 方法返回创建时传入的路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
 
-        let uid_res = uid.try_into().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        let gid_res = gid.try_into().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
-        Ok((uid_res, gid_res))
+    // user_id 方法用于获取文件的用户uid和gid
+    pub fn user_id(&self) -> (u32, u32) {
+        (self.metadata.uid(), self.metadata.gid())
     }
 
     // create_time 方法用于获取文件的创建时间
@@ -58,42 +70,585 @@ impl FileInfo {
     pub fn update_time(&self) -> io::Result<SystemTime> {
         self.metadata.modified()
     }
+
+    // stat 方法返回与 stat 命令对应的完整文件信息
+    pub fn stat(&self) -> FileStat {
+        let metadata = &self.metadata;
+        let file_type = metadata.file_type();
+        let (uid, gid) = self.user_id();
+
+        let is_device = file_type.is_block_device() || file_type.is_char_device();
+
+        FileStat {
+            path: self.path.clone(),
+            file_type: file_type_name(metadata),
+            size: metadata.len(),
+            mode: format!("{:04o}", metadata.mode() & 0o7777),
+            permissions: permission_string(metadata),
+            inode: metadata.ino(),
+            nlink: metadata.nlink(),
+            device: device_string(metadata.dev()),
+            rdev: is_device.then(|| device_string(metadata.rdev())),
+            uid,
+            gid,
+            user: get_username_by_uid(uid),
+            group: get_groupname_by_gid(gid),
+            atime: rfc3339(metadata.atime(), metadata.atime_nsec()),
+            mtime: rfc3339(metadata.mtime(), metadata.mtime_nsec()),
+            ctime: rfc3339(metadata.ctime(), metadata.ctime_nsec()),
+            // 部分文件系统不记录创建时间, 或者返回 0
+            btime: metadata.created().ok()
+                .filter(|time| *time > SystemTime::UNIX_EPOCH)
+                .map(|time| DateTime::<Local>::from(time).to_rfc3339()),
+            symlink_target: file_type.is_symlink()
+                .then(|| fs::read_link(&self.path).ok())
+                .flatten(),
+        }
+    }
+}
+
+// FileStat 是一个文件的完整信息, 时间均为 RFC 3339 格式
+#[derive(Debug, Clone, Serialize)]
+pub struct FileStat {
+    pub path: PathBuf,
+    // file、directory、symlink、block_device、char_device、fifo、socket
+    pub file_type: &'static str,
+    pub size: u64,
+    // 八进制权限, 包含 setuid、setgid、sticky 位, 如 0644
+    pub mode: String,
+    // 与 ls -l 相同的权限字符串, 如 -rw-r--r--
+    pub permissions: String,
+    pub inode: u64,
+    pub nlink: u64,
+    // 文件所在的设备, 主设备号:次设备号
+    pub device: String,
+    // 块设备、字符设备文件本身的设备号
+    pub rdev: Option<String>,
+    pub uid: u32,
+    pub gid: u32,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub atime: String,
+    pub mtime: String,
+    pub ctime: String,
+    // 文件系统不记录创建时间时为空
+    pub btime: Option<String>,
+    // 符号链接指向的路径
+    pub symlink_target: Option<PathBuf>,
+}
+
+// DirEntryInfo 是目录列表中的一项
+#[derive(Debug, Clone, Serialize)]
+pub struct DirEntryInfo {
+    pub name: String,
+    pub path: PathBuf,
+    // 相对于列出的目录的层级, 直接子项为 1
+    pub depth: usize,
+    pub file_type: &'static str,
+    pub size: u64,
+    pub mode: String,
+    pub permissions: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    pub mtime: String,
+    // 开启 du 时目录下所有普通文件的总大小, 没有权限读取时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dir_size: Option<u64>,
+    #[serde(skip)]
+    modified: i64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSortBy {
+    #[default]
+    Name,
+    Size,
+    Mtime,
+    Type,
+}
+
+// ListOptions 是列目录时的选项
+pub struct ListOptions {
+    // 递归的层数, 1 只列出直接子项
+    pub depth: usize,
+    // 是否包含以 . 开头的文件, 不包含时也不会进入隐藏目录
+    pub hidden: bool,
+    // 只返回文件名匹配的项, 目录本身不匹配时仍然会进入
+    pub pattern: Option<Pattern>,
+    // 是否计算每个子目录的总大小
+    pub du: bool,
+    pub sort: ListSortBy,
+    pub desc: bool,
+    // 最多遍历的项数, 超过时停止遍历
+    pub max_entries: usize,
+    // 排序后返回的范围
+    pub offset: usize,
+    pub limit: usize,
+}
+
+// DirListing 是 list_dir 的结果
+pub struct DirListing {
+    // offset 和 limit 范围内的项
+    pub entries: Vec<DirEntryInfo>,
+    // 分页前的总项数
+    pub total: usize,
+    // 遍历的项数超过 max_entries, 结果不完整
+    pub truncated: bool,
+}
+
+// list_dir 列出目录下的文件, 没有权限读取的子目录会被跳过。
+// is_denied 返回 true 的路径不会出现在结果中, 也不会进入其中遍历或计算大小。
+// du 时只计算返回的这一页中目录的大小; 按大小排序时需要所有目录的大小, 遍历一次整个目录树统计
+pub fn list_dir<P, F>(path: P, options: &ListOptions, is_denied: F) -> io::Result<DirListing>
+    where
+        P: AsRef<Path>,
+        F: Fn(&Path) -> bool,
+{
+    let path = path.as_ref();
+    if !fs::metadata(path)?.is_dir() {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a directory", path.display())));
+    }
+
+    let usernames = get_usernames();
+    let groupnames = get_groupnames();
+    let is_hidden = |name: &str| name.starts_with('.');
+
+    let walker = WalkDir::new(path)
+        .min_depth(1)
+        .max_depth(options.depth.max(1))
+        .into_iter()
+        .filter_entry(|entry| {
+            (options.hidden || !is_hidden(&entry.file_name().to_string_lossy())) && !is_denied(entry.path())
+        });
+
+    let mut entries = Vec::new();
+    let mut truncated = false;
+    for (walked, entry) in walker.flatten().enumerate() {
+        if walked >= options.max_entries {
+            truncated = true;
+            break;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if options.pattern.as_ref().is_some_and(|pattern| !pattern.matches(&name)) {
+            continue;
+        }
+        // 遍历过程中文件可能已经被删除
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+
+        let (uid, gid) = (metadata.uid(), metadata.gid());
+        entries.push(DirEntryInfo {
+            name,
+            path: entry.path().to_path_buf(),
+            depth: entry.depth(),
+            file_type: file_type_name(&metadata),
+            size: metadata.len(),
+            mode: format!("{:04o}", metadata.mode() & 0o7777),
+            permissions: permission_string(&metadata),
+            uid,
+            gid,
+            user: usernames.get(&uid).cloned(),
+            group: groupnames.get(&gid).cloned(),
+            mtime: rfc3339(metadata.mtime(), metadata.mtime_nsec()),
+            dir_size: None,
+            modified: metadata.mtime(),
+        });
+    }
+
+    let sort_by_dir_size = options.du && matches!(options.sort, ListSortBy::Size);
+    if sort_by_dir_size {
+        let mut sizes = dir_sizes(path, &entries, &is_denied);
+        for entry in entries.iter_mut().filter(|entry| entry.file_type == "directory") {
+            entry.dir_size = sizes.remove(&entry.path);
+        }
+    }
+    sort_entries(&mut entries, options.sort, options.desc);
+
+    let total = entries.len();
+    let mut entries = entries.into_iter().skip(options.offset).take(options.limit).collect::<Vec<_>>();
+    if options.du && !sort_by_dir_size {
+        for entry in entries.iter_mut().filter(|entry| entry.file_type == "directory") {
+            entry.dir_size = get_dir_size(&entry.path, &is_denied).ok();
+        }
+    }
+    Ok(DirListing { entries, total, truncated })
+}
+
+// dir_sizes 遍历一次 root 下的所有文件, 把每个文件的大小累加到 entries 中包含它的各级目录上
+fn dir_sizes<F>(root: &Path, entries: &[DirEntryInfo], is_denied: F) -> HashMap<PathBuf, u64>
+    where
+        F: Fn(&Path) -> bool,
+{
+    let mut sizes = entries.iter()
+        .filter(|entry| entry.file_type == "directory")
+        .map(|entry| (entry.path.clone(), 0))
+        .collect::<HashMap<_, _>>();
+    let files = WalkDir::new(root)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|entry| !is_denied(entry.path()))
+        .flatten()
+        .filter(|entry| entry.file_type().is_file());
+    for entry in files {
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        for ancestor in entry.path().ancestors().skip(1).take_while(|ancestor| *ancestor != root) {
+            if let Some(size) = sizes.get_mut(ancestor) {
+                *size += metadata.len();
+            }
+        }
+    }
+    sizes
+}
+
+fn sort_entries(entries: &mut [DirEntryInfo], sort: ListSortBy, desc: bool) {
+    let size = |entry: &DirEntryInfo| entry.dir_size.unwrap_or(entry.size);
+    entries.sort_by(|a, b| {
+        let ordering = match sort {
+            ListSortBy::Name => Ordering::Equal,
+            ListSortBy::Size => size(a).cmp(&size(b)),
+            ListSortBy::Mtime => a.modified.cmp(&b.modified),
+            ListSortBy::Type => a.file_type.cmp(b.file_type),
+        };
+        // 其他字段相同时按路径排序, 保证分页结果稳定
+        let ordering = ordering.then_with(|| a.path.cmp(&b.path));
+        if desc { ordering.reverse() } else { ordering }
+    });
+}
+
+fn file_type_name(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        "symlink"
+    } else if file_type.is_dir() {
+        "directory"
+    } else if file_type.is_file() {
+        "file"
+    } else if file_type.is_block_device() {
+        "block_device"
+    } else if file_type.is_char_device() {
+        "char_device"
+    } else if file_type.is_fifo() {
+        "fifo"
+    } else if file_type.is_socket() {
+        "socket"
+    } else {
+        "unknown"
+    }
+}
+
+// permission_string 生成 ls -l 形式的权限字符串, setuid/setgid/sticky 位分别显示为 s/s/t,
+// 对应的执行位不存在时显示为大写
+fn permission_string(metadata: &Metadata) -> String {
+    let mode = metadata.mode();
+    let file_type = metadata.file_type();
+
+    let kind = if file_type.is_symlink() {
+        'l'
+    } else if file_type.is_dir() {
+        'd'
+    } else if file_type.is_block_device() {
+        'b'
+    } else if file_type.is_char_device() {
+        'c'
+    } else if file_type.is_fifo() {
+        'p'
+    } else if file_type.is_socket() {
+        's'
+    } else {
+        '-'
+    };
+
+    let bit = |mask: u32, c: char| if mode & mask != 0 { c } else { '-' };
+    let special = |exec: u32, special: u32, lower: char| match (mode & exec != 0, mode & special != 0) {
+        (true, true) => lower,
+        (false, true) => lower.to_ascii_uppercase(),
+        (true, false) => 'x',
+        (false, false) => '-',
+    };
+
+    [
+        kind,
+        bit(0o400, 'r'), bit(0o200, 'w'), special(0o100, 0o4000, 's'),
+        bit(0o040, 'r'), bit(0o020, 'w'), special(0o010, 0o2000, 's'),
+        bit(0o004, 'r'), bit(0o002, 'w'), special(0o001, 0o1000, 't'),
+    ].iter().collect()
+}
+
+fn device_string(dev: u64) -> String {
+    format!("{}:{}", major(dev), minor(dev))
+}
+
+fn rfc3339(secs: i64, nsecs: i64) -> String {
+    Local.timestamp_opt(secs, nsecs as u32)
+        .single()
+        .map(|time| time.to_rfc3339())
+        .unwrap_or_default()
 }
 
 // get_current_directory 获取当前所在目录的路径
+#[allow(dead_code)]
 fn get_current_directory() -> io::Result<PathBuf> {
     current_dir()
 }
 
-// get_file_contents 直接获取文件的全部内容。
-pub async fn get_file_contents<P>(filename: P) -> io::Result<String>
+// get_file_contents 获取文件的全部内容, 最多读取 max_bytes + 1 个字节。
+// /proc、/sys 下的文件大小为 0, 调用方需要根据返回内容的长度判断是否超过 max_bytes, 再按需要解码
+pub fn get_file_contents<P>(filename: P, max_bytes: u64) -> io::Result<Vec<u8>>
     where
         P: AsRef<Path>,
 {
-    fs::read_to_string(filename)
+    let mut contents = Vec::new();
+    File::open(filename)?.take(max_bytes.saturating_add(1)).read_to_end(&mut contents)?;
+    Ok(contents)
 }
 
-// get_file_contents_by_line 按行获取文件的内容。
-pub async fn get_file_contents_by_line<P>(filename: P) -> io::Result<BufReader<File>>
+// LinePage 是按行分页读取的一页内容
+#[derive(Debug, Clone, Serialize)]
+pub struct LinePage {
+    pub offset: usize,
+    pub lines: Vec<String>,
+    // 还有更多内容时为下一页的 offset
+    pub next_offset: Option<usize>,
+    // 因为超过 max_bytes 提前结束
+    pub truncated: bool,
+    // 超过 max_bytes 被截断的行, 为文件中的行号 (从 0 开始)
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub truncated_lines: Vec<usize>,
+}
+
+// get_file_contents_by_line 按行获取文件的内容, 跳过前 offset 行, 最多返回 limit 行、max_bytes 字节。
+// 非 utf-8 的内容会被替换成 U+FFFD, 不会导致整页读取失败。
+pub fn get_file_contents_by_line<P>(filename: P, offset: usize, limit: usize, max_bytes: u64) -> io::Result<LinePage>
     where
         P: AsRef<Path>,
 {
-    let file = File::open(filename)?;
-    Ok(BufReader::new(file))
+    let mut reader = BufReader::new(File::open(filename)?);
+    let mut buffer = Vec::new();
+    let mut lines = Vec::new();
+    let mut truncated_lines = Vec::new();
+    let mut bytes = 0;
+
+    // 跳过的行不需要保存, 没有换行符的超长行也不会读入内存
+    for _ in 0..offset {
+        if reader.skip_until(b'\n')? == 0 {
+            return Ok(LinePage { offset, lines, next_offset: None, truncated: false, truncated_lines });
+        }
+    }
+
+    loop {
+        buffer.clear();
+        // 单行最多读取 max_bytes + 1 个字节, 超出的部分跳过
+        if (&mut reader).take(max_bytes.saturating_add(1)).read_until(b'\n', &mut buffer)? == 0 {
+            return Ok(LinePage { offset, lines, next_offset: None, truncated: false, truncated_lines });
+        }
+        let line_truncated = !buffer.ends_with(b"\n") && buffer.len() as u64 > max_bytes;
+        if line_truncated {
+            buffer.truncate(max_bytes as usize);
+            reader.skip_until(b'\n')?;
+        }
+
+        // 能读到下一行说明还有更多内容, 第一行即使超过 max_bytes 也返回, 保证分页能继续
+        bytes += buffer.len() as u64;
+        let truncated = bytes > max_bytes && !lines.is_empty();
+        if lines.len() >= limit || truncated {
+            return Ok(LinePage { offset, next_offset: Some(offset + lines.len()), lines, truncated, truncated_lines });
+        }
+
+        if line_truncated {
+            truncated_lines.push(offset + lines.len());
+        }
+        let line = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        lines.push(String::from_utf8_lossy(line).into_owned());
+    }
 }
 
+// read_range 从 start 开始读取最多 len 个字节
+pub fn read_range<P>(filename: P, start: u64, len: u64) -> io::Result<Vec<u8>>
+    where
+        P: AsRef<Path>,
+{
+    let mut file = File::open(filename)?;
+    file.seek(SeekFrom::Start(start))?;
 
+    let mut buffer = Vec::new();
+    file.take(len).read_to_end(&mut buffer)?;
+    Ok(buffer)
+}
 
-pub fn get_dir_size<P>(path: P) -> io::Result<u64>
+// get_dir_size 统计目录下所有普通文件的总大小, 跳过 is_denied 返回 true 的路径
+pub fn get_dir_size<P, F>(path: P, is_denied: F) -> io::Result<u64>
     where
-        P: AsRef<Path>
+        P: AsRef<Path>,
+        F: Fn(&Path) -> bool,
 {
     let mut size = 0;
-    for entry in WalkDir::new(path) {
+    for entry in WalkDir::new(path).into_iter().filter_entry(|entry| !is_denied(entry.path())) {
         let entry = entry?;
         if entry.file_type().is_file() {
             size += fs::metadata(entry.path())?.len();
         }
     }
     Ok(size)
-}
\ No newline at end of file
+}
+
+// DirSizeCollector 导出配置中指定目录的总大小
+pub struct DirSizeCollector {
+    paths: Vec<PathBuf>,
+    // 统计目录大小失败的累计次数
+    errors: AtomicU64,
+}
+
+impl DirSizeCollector {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self { paths, errors: AtomicU64::new(0) }
+    }
+}
+
+impl Collector for DirSizeCollector {
+    fn name(&self) -> &'static str {
+        "dirsize"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let mut family = MetricFamily::gauge(
+            "node_directory_size_bytes",
+            "Total size of regular files under the directory.",
+        );
+
+        // 某个目录不可读时跳过该目录并计数, 不影响其它目录
+        for path in &self.paths {
+            match get_dir_size(path, |_| false) {
+                Ok(size) => family.add(&[("directory", &path.to_string_lossy())], size as f64),
+                Err(e) => {
+                    eprintln!("Failed to get size of {}: {}", path.display(), e);
+                    self.errors.fetch_add(1, atomic::Ordering::Relaxed);
+                }
+            }
+        }
+
+        let errors = MetricFamily::counter(
+            "node_directory_size_errors_total",
+            "Number of directories whose size could not be read.",
+        ).with_value(self.errors.load(atomic::Ordering::Relaxed) as f64);
+
+        Ok(vec![family, errors])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn dir_size_collector_skips_unreadable_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("a"), b"12345").unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/b"), b"123").unwrap();
+
+        let collector = DirSizeCollector::new(vec![dir.path().join("missing"), dir.path().to_path_buf()]);
+        let families = collector.collect().unwrap();
+
+        let sizes = &families[0].samples;
+        assert_eq!(sizes.len(), 1);
+        assert_eq!(sizes[0].labels[0].1, dir.path().to_string_lossy());
+        assert_eq!(sizes[0].value, 8.0);
+        assert_eq!(families[1].samples[0].value, 1.0);
+
+        // 失败次数是累计值
+        let families = collector.collect().unwrap();
+        assert_eq!(families[1].samples[0].value, 2.0);
+    }
+
+    #[test]
+    fn computes_dir_sizes_for_the_page_or_once_for_size_sort() {
+        let dir = tempfile::tempdir().unwrap();
+        for (name, size) in [("a/x", 10), ("a/b/y", 20), ("c/z", 5), (".h/w", 7)] {
+            let path = dir.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, vec![0; size]).unwrap();
+        }
+        let options = |sort, offset, limit| ListOptions {
+            depth: 2,
+            hidden: false,
+            pattern: None,
+            du: true,
+            sort,
+            desc: true,
+            max_entries: 100,
+            offset,
+            limit,
+        };
+        let sizes = |listing: DirListing| listing.entries.into_iter()
+            .map(|entry| (entry.name, entry.dir_size))
+            .collect::<Vec<_>>();
+
+        let listing = list_dir(dir.path(), &options(ListSortBy::Size, 0, 3), |_| false).unwrap();
+        assert_eq!(listing.total, 5);
+        assert_eq!(sizes(listing), [("a".to_string(), Some(30)), ("b".to_string(), Some(20)), ("x".to_string(), None)]);
+
+        // 只计算当前页中目录的大小, 被禁止的路径不计入
+        let listing = list_dir(dir.path(), &options(ListSortBy::Name, 1, 3), |path| path.ends_with("y")).unwrap();
+        assert_eq!(listing.total, 5);
+        assert_eq!(sizes(listing), [("c".to_string(), Some(5)), ("x".to_string(), None), ("b".to_string(), Some(0))]);
+    }
+
+    #[test]
+    fn truncates_lines_longer_than_max_bytes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("long.txt");
+        fs::write(&path, format!("{}\nshort\n{}", "x".repeat(100), "y".repeat(50))).unwrap();
+
+        let page = get_file_contents_by_line(&path, 0, 10, 8).unwrap();
+        assert_eq!(page.lines, ["xxxxxxxx"]);
+        assert_eq!(page.truncated_lines, [0]);
+        assert_eq!(page.next_offset, Some(1));
+        assert!(page.truncated);
+
+        let page = get_file_contents_by_line(&path, 1, 10, 8).unwrap();
+        assert_eq!(page.lines, ["short"]);
+        assert!(page.truncated_lines.is_empty());
+        assert_eq!(page.next_offset, Some(2));
+
+        // 最后一行没有换行符
+        let page = get_file_contents_by_line(&path, 2, 10, 8).unwrap();
+        assert_eq!(page.lines, ["yyyyyyyy"]);
+        assert_eq!(page.truncated_lines, [2]);
+        assert_eq!(page.next_offset, None);
+    }
+
+    #[test]
+    fn skips_long_lines_without_reading_them_into_pages() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("skip.txt");
+        fs::write(&path, format!("{}\na\nb\n", "z".repeat(10_000))).unwrap();
+
+        let page = get_file_contents_by_line(&path, 1, 10, 1024).unwrap();
+        assert_eq!(page.lines, ["a", "b"]);
+        assert_eq!(page.next_offset, None);
+
+        let page = get_file_contents_by_line(&path, 5, 10, 1024).unwrap();
+        assert!(page.lines.is_empty());
+        assert_eq!(page.next_offset, None);
+    }
+
+    #[test]
+    fn reads_at_most_one_byte_past_the_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("contents.txt");
+        fs::write(&path, "0123456789").unwrap();
+
+        assert_eq!(get_file_contents(&path, 4).unwrap(), b"01234");
+        assert_eq!(get_file_contents(&path, 10).unwrap(), b"0123456789");
+        assert_eq!(get_file_contents("/proc/self/status", 16).unwrap().len(), 17);
+    }
+}
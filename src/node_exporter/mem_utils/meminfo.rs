@@ -1,7 +1,9 @@
 use std::fs::File;
-use std::io::{BufRead, BufReader};
+use std::io::{self, BufRead, BufReader};
 use serde::{Deserialize, Serialize};
 
+use crate::node_exporter::collector::{Collector, MetricFamily};
+
 // 定义一个名为 MemInfo 的结构体，用于存储 Linux 系统的内存信息
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MemInfo {
@@ -63,3 +65,29 @@ impl MemInfo {
         })
     }
 }
+
+// MemInfoCollector 把 /proc/meminfo 导出为 node_memory_* 指标
+pub struct MemInfoCollector;
+
+impl Collector for MemInfoCollector {
+    fn name(&self) -> &'static str {
+        "meminfo"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let meminfo = MemInfo::init()
+            .map_err(|e| io::Error::other(e.to_string()))?;
+
+        // /proc/meminfo 的单位是 KB, Prometheus 统一使用字节
+        Ok(vec![
+            MetricFamily::gauge("node_memory_MemTotal_bytes", "Memory information field MemTotal_bytes.")
+                .with_value((meminfo.total * 1024) as f64),
+            MetricFamily::gauge("node_memory_MemFree_bytes", "Memory information field MemFree_bytes.")
+                .with_value((meminfo.free * 1024) as f64),
+            MetricFamily::gauge("node_memory_Buffers_bytes", "Memory information field Buffers_bytes.")
+                .with_value((meminfo.buffers * 1024) as f64),
+            MetricFamily::gauge("node_memory_Cached_bytes", "Memory information field Cached_bytes.")
+                .with_value((meminfo.cached * 1024) as f64),
+        ])
+    }
+}
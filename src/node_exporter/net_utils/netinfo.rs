@@ -0,0 +1,345 @@
+//! 网卡流量统计在 /proc/net/dev 文件下, 内容大致如下:
+//! Inter-|   Receive                                                |  Transmit
+//!  face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+//!     lo:  253318    2937    0    0    0     0          0         0   253318    2937    0    0    0     0       0          0
+//!   eth0: 9371821   13390    0    0    0     0          0         0  1298014   10021    0    0    0     0       0          0
+//! 前两行是表头, 之后每行一个网卡, 冒号后面依次是 8 个接收字段和 8 个发送字段, 都是累计值。
+//!
+//! 网卡的状态、速率、MTU 和 MAC 地址在 /sys/class/net/<网卡>/{operstate,speed,mtu,address} 下,
+//! 其中 speed 的单位是 Mbps, 虚拟网卡或未连接的网卡读取 speed 会失败或得到 -1。
+
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::Duration;
+
+use serde::Serialize;
+
+use crate::node_exporter::collector::{Collector, MetricFamily};
+use crate::node_exporter::sampler::{Sampler, Snapshot};
+
+// 后台采样间隔
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+// NetDevStat 是 /proc/net/dev 中一个网卡的累计值
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetDevStat {
+    pub name: String,
+    pub rx_bytes: u64,
+    pub rx_packets: u64,
+    pub rx_errors: u64,
+    pub rx_dropped: u64,
+    pub tx_bytes: u64,
+    pub tx_packets: u64,
+    pub tx_errors: u64,
+    pub tx_dropped: u64,
+}
+
+impl NetDevStat {
+    // 读取所有网卡的流量统计
+    pub fn interfaces() -> Result<Vec<Self>, io::Error> {
+        let content = fs::read_to_string("/proc/net/dev")?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Vec<Self> {
+        content.lines()
+            // 跳过两行表头
+            .skip(2)
+            .filter_map(Self::parse_line)
+            .collect()
+    }
+
+    fn parse_line(line: &str) -> Option<Self> {
+        let (name, values) = line.split_once(':')?;
+        let values = values.split_whitespace()
+            .map(|value| value.parse::<u64>().ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        if values.len() < 16 {
+            return None;
+        }
+
+        Some(Self {
+            name: name.trim().to_string(),
+            rx_bytes: values[0],
+            rx_packets: values[1],
+            rx_errors: values[2],
+            rx_dropped: values[3],
+            tx_bytes: values[8],
+            tx_packets: values[9],
+            tx_errors: values[10],
+            tx_dropped: values[11],
+        })
+    }
+}
+
+// NetLink 是 /sys/class/net/<网卡> 下的链路信息
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct NetLink {
+    // up、down、unknown 等
+    pub operstate: Option<String>,
+    // 链路速率 (Mbps), 虚拟网卡没有
+    pub speed_mbps: Option<u64>,
+    pub mtu: Option<u32>,
+    pub address: Option<String>,
+}
+
+impl NetLink {
+    pub fn of(name: &str) -> Self {
+        let dir = Path::new("/sys/class/net").join(name);
+        let read = |file: &str| fs::read_to_string(dir.join(file)).ok().map(|value| value.trim().to_string());
+
+        Self {
+            operstate: read("operstate"),
+            // 未连接时 speed 为 -1, 解析失败即视为没有
+            speed_mbps: read("speed").and_then(|speed| speed.parse::<u64>().ok()),
+            mtu: read("mtu").and_then(|mtu| mtu.parse::<u32>().ok()),
+            address: read("address"),
+        }
+    }
+}
+
+// NetInterface 是网卡的链路信息和累计流量
+#[derive(Debug, Clone, Serialize)]
+pub struct NetInterface {
+    #[serde(flatten)]
+    pub stat: NetDevStat,
+    #[serde(flatten)]
+    pub link: NetLink,
+}
+
+impl NetInterface {
+    pub fn interfaces() -> Result<Vec<Self>, io::Error> {
+        Ok(NetDevStat::interfaces()?
+            .into_iter()
+            .map(|stat| {
+                let link = NetLink::of(&stat.name);
+                Self { stat, link }
+            })
+            .collect())
+    }
+}
+
+// NetRate 是两次采样之间一个网卡的速率
+#[derive(Debug, Clone, Serialize)]
+pub struct NetRate {
+    pub name: String,
+    // 采样间隔 (秒)
+    pub interval: f64,
+    pub rx_bytes_per_sec: f64,
+    pub tx_bytes_per_sec: f64,
+    pub rx_packets_per_sec: f64,
+    pub tx_packets_per_sec: f64,
+    pub rx_errors_per_sec: f64,
+    pub tx_errors_per_sec: f64,
+    pub rx_dropped_per_sec: f64,
+    pub tx_dropped_per_sec: f64,
+    pub speed_mbps: Option<u64>,
+    // 接收和发送方向分别占链路速率的百分比, 没有链路速率时为空
+    pub rx_utilization: Option<f64>,
+    pub tx_utilization: Option<f64>,
+}
+
+impl NetRate {
+    pub fn between(prev: &NetDevStat, cur: &NetDevStat, elapsed: Duration, speed_mbps: Option<u64>) -> Self {
+        let interval = elapsed.as_secs_f64();
+        let rate = |cur: u64, prev: u64| {
+            if interval > 0.0 { cur.saturating_sub(prev) as f64 / interval } else { 0.0 }
+        };
+
+        let rx_bytes_per_sec = rate(cur.rx_bytes, prev.rx_bytes);
+        let tx_bytes_per_sec = rate(cur.tx_bytes, prev.tx_bytes);
+        // 链路速率是 Mbps, 换算成字节每秒
+        let utilization = |bytes_per_sec: f64| {
+            speed_mbps
+                .filter(|speed| *speed > 0)
+                .map(|speed| bytes_per_sec * 8.0 / (speed as f64 * 1_000_000.0) * 100.0)
+        };
+
+        Self {
+            name: cur.name.clone(),
+            interval,
+            rx_bytes_per_sec,
+            tx_bytes_per_sec,
+            rx_packets_per_sec: rate(cur.rx_packets, prev.rx_packets),
+            tx_packets_per_sec: rate(cur.tx_packets, prev.tx_packets),
+            rx_errors_per_sec: rate(cur.rx_errors, prev.rx_errors),
+            tx_errors_per_sec: rate(cur.tx_errors, prev.tx_errors),
+            rx_dropped_per_sec: rate(cur.rx_dropped, prev.rx_dropped),
+            tx_dropped_per_sec: rate(cur.tx_dropped, prev.tx_dropped),
+            speed_mbps,
+            rx_utilization: utilization(rx_bytes_per_sec),
+            tx_utilization: utilization(tx_bytes_per_sec),
+        }
+    }
+}
+
+// NetSnapshot 是一次采样得到的各网卡累计值和链路速率 (Mbps)
+#[derive(Debug, Clone, Default)]
+pub struct NetSnapshot {
+    pub interfaces: Vec<(NetDevStat, Option<u64>)>,
+}
+
+impl Snapshot for NetSnapshot {
+    type Output = Vec<NetRate>;
+
+    const SOURCE: &'static str = "/proc/net/dev";
+    const INTERVAL: Duration = SAMPLE_INTERVAL;
+
+    // 链路速率要读 sysfs, 和累计值一起在采样时读取
+    fn read() -> Result<Self, io::Error> {
+        let interfaces = NetDevStat::interfaces()?
+            .into_iter()
+            .map(|stat| {
+                let speed_mbps = NetLink::of(&stat.name).speed_mbps;
+                (stat, speed_mbps)
+            })
+            .collect();
+        Ok(Self { interfaces })
+    }
+
+    // 新出现的网卡没有上一次采样, 等下一个周期再计算
+    fn between(prev: &Self, cur: &Self, elapsed: Duration) -> Vec<NetRate> {
+        cur.interfaces.iter()
+            .filter_map(|(stat, speed_mbps)| {
+                prev.interfaces.iter()
+                    .find(|(prev, _)| prev.name == stat.name)
+                    .map(|(prev, _)| NetRate::between(prev, stat, elapsed, *speed_mbps))
+            })
+            .collect()
+    }
+}
+
+// NetSampler 在后台定期读取 /proc/net/dev, 保存最近一个周期的网卡速率
+pub type NetSampler = Sampler<NetSnapshot>;
+
+impl Sampler<NetSnapshot> {
+    // rates 返回最近一个周期各网卡的速率, 第一个周期结束前为空
+    pub fn rates(&self) -> Vec<NetRate> {
+        self.latest().unwrap_or_default()
+    }
+}
+
+// NetCollector 导出 node_network_* 指标
+pub struct NetCollector;
+
+impl Collector for NetCollector {
+    fn name(&self) -> &'static str {
+        "netdev"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let mut families = [
+            ("node_network_receive_bytes_total", "Network device statistic receive_bytes."),
+            ("node_network_receive_packets_total", "Network device statistic receive_packets."),
+            ("node_network_receive_errs_total", "Network device statistic receive_errs."),
+            ("node_network_receive_drop_total", "Network device statistic receive_drop."),
+            ("node_network_transmit_bytes_total", "Network device statistic transmit_bytes."),
+            ("node_network_transmit_packets_total", "Network device statistic transmit_packets."),
+            ("node_network_transmit_errs_total", "Network device statistic transmit_errs."),
+            ("node_network_transmit_drop_total", "Network device statistic transmit_drop."),
+        ].map(|(name, help)| MetricFamily::counter(name, help));
+
+        let mut up = MetricFamily::gauge("node_network_up", "Value is 1 if operstate is 'up', 0 otherwise.");
+        let mut mtu = MetricFamily::gauge("node_network_mtu_bytes", "Network device property: mtu_bytes");
+        let mut speed = MetricFamily::gauge("node_network_speed_bytes", "Network device property: speed_bytes");
+
+        for interface in NetInterface::interfaces()? {
+            let stat = &interface.stat;
+            let labels = [("device", stat.name.as_str())];
+            let values = [
+                stat.rx_bytes, stat.rx_packets, stat.rx_errors, stat.rx_dropped,
+                stat.tx_bytes, stat.tx_packets, stat.tx_errors, stat.tx_dropped,
+            ];
+            for (family, value) in families.iter_mut().zip(values) {
+                family.add(&labels, value as f64);
+            }
+
+            let link = &interface.link;
+            up.add(&labels, if link.operstate.as_deref() == Some("up") { 1.0 } else { 0.0 });
+            if let Some(value) = link.mtu {
+                mtu.add(&labels, value as f64);
+            }
+            if let Some(value) = link.speed_mbps {
+                speed.add(&labels, value as f64 * 1_000_000.0 / 8.0);
+            }
+        }
+
+        let mut result = families.to_vec();
+        result.extend([up, mtu, speed]);
+        Ok(result)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const NET_DEV: &str = "\
+Inter-|   Receive                                                |  Transmit
+ face |bytes    packets errs drop fifo frame compressed multicast|bytes    packets errs drop fifo colls carrier compressed
+    lo:  253318    2937    0    0    0     0          0         0   253318    2937    0    0    0     0       0          0
+  eth0:19371821   13390    1    2    0     0          0         0  1298014   10021    3    4    0     0       0          0
+  bad0: 1 2 3
+";
+
+    fn stat(name: &str, rx_bytes: u64, tx_bytes: u64) -> NetDevStat {
+        NetDevStat { name: name.to_string(), rx_bytes, tx_bytes, ..Default::default() }
+    }
+
+    #[test]
+    fn parses_proc_net_dev() {
+        let stats = NetDevStat::parse(NET_DEV);
+        assert_eq!(stats.iter().map(|stat| stat.name.as_str()).collect::<Vec<_>>(), ["lo", "eth0"]);
+
+        // 数值较大时冒号后面没有空格
+        let eth0 = &stats[1];
+        assert_eq!((eth0.rx_bytes, eth0.rx_packets, eth0.rx_errors, eth0.rx_dropped), (19371821, 13390, 1, 2));
+        assert_eq!((eth0.tx_bytes, eth0.tx_packets, eth0.tx_errors, eth0.tx_dropped), (1298014, 10021, 3, 4));
+    }
+
+    #[test]
+    fn computes_rates_and_utilization() {
+        let prev = stat("eth0", 1_000, 0);
+        let cur = stat("eth0", 2_501_000, 250_000);
+
+        let rate = NetRate::between(&prev, &cur, Duration::from_secs(2), Some(100));
+        assert_eq!(rate.rx_bytes_per_sec, 1_250_000.0);
+        assert_eq!(rate.tx_bytes_per_sec, 125_000.0);
+        // 1.25 MB/s = 10 Mbps, 占 100 Mbps 的 10%
+        assert_eq!(rate.rx_utilization, Some(10.0));
+        assert_eq!(rate.tx_utilization, Some(1.0));
+
+        let rate = NetRate::between(&prev, &cur, Duration::from_secs(2), None);
+        assert_eq!(rate.rx_utilization, None);
+        let rate = NetRate::between(&prev, &cur, Duration::from_secs(2), Some(0));
+        assert_eq!(rate.rx_utilization, None);
+    }
+
+    #[test]
+    fn counter_wraparound_yields_zero_rate() {
+        // 计数器回绕或网卡重建后累计值变小, 不应得到负数或巨大的速率
+        let prev = stat("eth0", u64::MAX - 10, 5_000);
+        let cur = stat("eth0", 100, 6_000);
+
+        let rate = NetRate::between(&prev, &cur, Duration::from_secs(1), Some(1000));
+        assert_eq!(rate.rx_bytes_per_sec, 0.0);
+        assert_eq!(rate.rx_utilization, Some(0.0));
+        assert_eq!(rate.tx_bytes_per_sec, 1_000.0);
+
+        let rate = NetRate::between(&prev, &cur, Duration::ZERO, None);
+        assert_eq!(rate.tx_bytes_per_sec, 0.0);
+    }
+
+    #[test]
+    fn snapshot_uses_current_link_speed_and_skips_new_interfaces() {
+        let prev = NetSnapshot { interfaces: vec![(stat("eth0", 0, 0), Some(10))] };
+        let cur = NetSnapshot { interfaces: vec![(stat("eth0", 1_250_000, 0), Some(100)), (stat("eth1", 1, 1), None)] };
+
+        let rates = NetSnapshot::between(&prev, &cur, Duration::from_secs(1));
+        assert_eq!(rates.len(), 1);
+        assert_eq!(rates[0].speed_mbps, Some(100));
+        assert_eq!(rates[0].rx_utilization, Some(10.0));
+    }
+}
@@ -0,0 +1,300 @@
+//! socket 信息在 /proc/net/{tcp,tcp6,udp,udp6} 文件下, 内容大致如下:
+//!   sl  local_address rem_address   st tx_queue rx_queue tr tm->when retrnsmt   uid  timeout inode
+//!    0: 0100007F:0CEA 00000000:0000 0A 00000000:00000000 00:00000000 00000000   999        0 21543 1 ...
+//! 地址是按本机字节序打印的 16 进制 (127.0.0.1 在小端机器上是 0100007F), 端口是普通的 16 进制,
+//! st 是连接状态, 最后的 inode 可以和 /proc/<pid>/fd 下 socket:[inode] 形式的链接对应起来找到所属进程。
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use serde::Serialize;
+
+use crate::node_exporter::collector::{Collector, MetricFamily};
+use crate::node_exporter::proc_utils::process::ProcessStatus;
+
+// 需要读取的 socket 表
+const PROTOCOLS: [&str; 4] = ["tcp", "tcp6", "udp", "udp6"];
+
+// Socket 是 socket 表中的一行
+#[derive(Debug, Clone, Serialize)]
+pub struct Socket {
+    // tcp、tcp6、udp、udp6
+    pub protocol: &'static str,
+    pub local_addr: IpAddr,
+    pub local_port: u16,
+    pub remote_addr: IpAddr,
+    pub remote_port: u16,
+    pub state: &'static str,
+    pub tx_queue: u64,
+    pub rx_queue: u64,
+    pub uid: u32,
+    pub inode: u64,
+    // 所属进程, 进程已退出或没有权限读取其 fd 时为空
+    pub pid: Option<u32>,
+}
+
+impl Socket {
+    // sockets 读取所有 tcp/udp socket, with_pid 为 true 时通过 /proc/<pid>/fd 关联所属进程
+    pub fn sockets(with_pid: bool) -> Result<Vec<Self>, io::Error> {
+        let mut sockets = Vec::new();
+
+        for protocol in PROTOCOLS {
+            // 内核关闭了 ipv6 时没有 tcp6/udp6
+            let content = match fs::read_to_string(format!("/proc/net/{}", protocol)) {
+                Ok(content) => content,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            sockets.extend(content.lines().skip(1).filter_map(|line| Self::parse_line(protocol, line)));
+        }
+
+        if with_pid {
+            let owners = socket_owners()?;
+            for socket in &mut sockets {
+                socket.pid = owners.get(&socket.inode).copied();
+            }
+        }
+
+        Ok(sockets)
+    }
+
+    fn parse_line(protocol: &'static str, line: &str) -> Option<Self> {
+        let fields = line.split_whitespace().collect::<Vec<_>>();
+        if fields.len() < 10 {
+            return None;
+        }
+
+        let (local_addr, local_port) = parse_endpoint(fields[1])?;
+        let (remote_addr, remote_port) = parse_endpoint(fields[2])?;
+        let state = u8::from_str_radix(fields[3], 16).ok()?;
+        let (tx_queue, rx_queue) = fields[4].split_once(':')?;
+
+        Some(Self {
+            protocol,
+            local_addr,
+            local_port,
+            remote_addr,
+            remote_port,
+            state: if protocol.starts_with("tcp") { tcp_state_name(state) } else { udp_state_name(state) },
+            tx_queue: u64::from_str_radix(tx_queue, 16).ok()?,
+            rx_queue: u64::from_str_radix(rx_queue, 16).ok()?,
+            uid: fields[7].parse().ok()?,
+            inode: fields[9].parse().ok()?,
+            pid: None,
+        })
+    }
+}
+
+// SocketSummary 是按协议和状态汇总的 socket 数量
+#[derive(Debug, Clone, Serialize)]
+pub struct SocketSummary {
+    pub total: usize,
+    // 协议 -> 状态 -> 数量
+    pub protocols: BTreeMap<&'static str, BTreeMap<&'static str, usize>>,
+}
+
+impl SocketSummary {
+    pub fn from(sockets: &[Socket]) -> Self {
+        let mut protocols: BTreeMap<&'static str, BTreeMap<&'static str, usize>> = BTreeMap::new();
+        for socket in sockets {
+            *protocols.entry(socket.protocol).or_default().entry(socket.state).or_default() += 1;
+        }
+
+        Self {
+            total: sockets.len(),
+            protocols,
+        }
+    }
+}
+
+// SocketCollector 导出各状态的 tcp 连接数
+pub struct SocketCollector;
+
+impl Collector for SocketCollector {
+    fn name(&self) -> &'static str {
+        "tcpstat"
+    }
+
+    fn collect(&self) -> io::Result<Vec<MetricFamily>> {
+        let sockets = Socket::sockets(false)?;
+        let mut states: BTreeMap<&'static str, usize> = BTreeMap::new();
+        for socket in sockets.iter().filter(|socket| socket.protocol.starts_with("tcp")) {
+            *states.entry(socket.state).or_default() += 1;
+        }
+
+        let mut family = MetricFamily::gauge("node_tcp_connection_states", "Number of connection states.");
+        for (state, count) in states {
+            family.add(&[("state", &state.to_ascii_lowercase())], count as f64);
+        }
+
+        Ok(vec![family])
+    }
+}
+
+// socket_owners 遍历所有进程的 fd, 建立 socket inode 到 pid 的映射
+fn socket_owners() -> Result<HashMap<u64, u32>, io::Error> {
+    let mut owners = HashMap::new();
+
+    for pid in ProcessStatus::get_pids()? {
+        // 进程可能已经退出, 或者没有权限读取其他用户进程的 fd
+        let Ok(entries) = fs::read_dir(format!("/proc/{}/fd", pid)) else {
+            continue;
+        };
+
+        for entry in entries.flatten() {
+            let Ok(target) = fs::read_link(entry.path()) else {
+                continue;
+            };
+            let inode = target.to_str()
+                .and_then(|target| target.strip_prefix("socket:["))
+                .and_then(|target| target.strip_suffix(']'))
+                .and_then(|inode| inode.parse::<u64>().ok());
+            if let Some(inode) = inode {
+                owners.entry(inode).or_insert(pid);
+            }
+        }
+    }
+
+    Ok(owners)
+}
+
+// parse_endpoint 解析 0100007F:0CEA 形式的地址和端口
+fn parse_endpoint(value: &str) -> Option<(IpAddr, u16)> {
+    let (addr, port) = value.split_once(':')?;
+    let port = u16::from_str_radix(port, 16).ok()?;
+
+    let addr = match addr.len() {
+        8 => IpAddr::V4(Ipv4Addr::from(u32::from_str_radix(addr, 16).ok()?.to_ne_bytes())),
+        32 => {
+            // ipv6 地址由 4 个按本机字节序打印的 32 位整数组成
+            let mut bytes = [0u8; 16];
+            for (i, chunk) in bytes.chunks_mut(4).enumerate() {
+                let word = u32::from_str_radix(addr.get(i * 8..i * 8 + 8)?, 16).ok()?;
+                chunk.copy_from_slice(&word.to_ne_bytes());
+            }
+            IpAddr::V6(Ipv6Addr::from(bytes))
+        }
+        _ => return None,
+    };
+
+    Some((addr, port))
+}
+
+// tcp_state_name 对应内核 include/net/tcp_states.h
+fn tcp_state_name(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x02 => "SYN_SENT",
+        0x03 => "SYN_RECV",
+        0x04 => "FIN_WAIT1",
+        0x05 => "FIN_WAIT2",
+        0x06 => "TIME_WAIT",
+        0x07 => "CLOSE",
+        0x08 => "CLOSE_WAIT",
+        0x09 => "LAST_ACK",
+        0x0A => "LISTEN",
+        0x0B => "CLOSING",
+        0x0C => "NEW_SYN_RECV",
+        _ => "UNKNOWN",
+    }
+}
+
+// udp 没有连接状态, 只区分是否 connect 过
+fn udp_state_name(state: u8) -> &'static str {
+    match state {
+        0x01 => "ESTABLISHED",
+        0x07 => "UNCONN",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // hex_v4 和 hex_v6 按内核的方式 (本机字节序) 打印地址, 使测试与机器字节序无关
+    fn hex_v4(addr: Ipv4Addr) -> String {
+        format!("{:08X}", u32::from_ne_bytes(addr.octets()))
+    }
+
+    fn hex_v6(addr: Ipv6Addr) -> String {
+        addr.octets()
+            .chunks(4)
+            .map(|chunk| format!("{:08X}", u32::from_ne_bytes(chunk.try_into().unwrap())))
+            .collect()
+    }
+
+    #[test]
+    fn parses_ipv4_endpoint() {
+        let value = format!("{}:0CEA", hex_v4(Ipv4Addr::new(192, 168, 1, 20)));
+        assert_eq!(parse_endpoint(&value), Some((IpAddr::V4(Ipv4Addr::new(192, 168, 1, 20)), 3306)));
+    }
+
+    #[test]
+    fn parses_ipv6_endpoint() {
+        let addr: Ipv6Addr = "2001:db8::8a2e:370:7334".parse().unwrap();
+        let value = format!("{}:01BB", hex_v6(addr));
+        assert_eq!(parse_endpoint(&value), Some((IpAddr::V6(addr), 443)));
+
+        let mapped: Ipv6Addr = "::ffff:127.0.0.1".parse().unwrap();
+        let value = format!("{}:0050", hex_v6(mapped));
+        assert_eq!(parse_endpoint(&value), Some((IpAddr::V6(mapped), 80)));
+    }
+
+    #[test]
+    fn rejects_malformed_endpoints() {
+        assert_eq!(parse_endpoint("0100007F"), None);
+        assert_eq!(parse_endpoint("0100007:0CEA"), None);
+        assert_eq!(parse_endpoint("0100007F:XYZ"), None);
+        assert_eq!(parse_endpoint("GG00007F:0CEA"), None);
+    }
+
+    // 小端机器上 /proc/net/tcp 和 /proc/net/tcp6 的原始内容
+    #[cfg(target_endian = "little")]
+    #[test]
+    fn parses_little_endian_proc_net_lines() {
+        let tcp = Socket::parse_line(
+            "tcp",
+            "   0: 0100007F:0CEA 00000000:0000 0A 00000000:00000002 00:00000000 00000000   999        0 21543 1 0000000000000000 100 0 0 10 0",
+        ).unwrap();
+        assert_eq!(tcp.local_addr, IpAddr::V4(Ipv4Addr::LOCALHOST));
+        assert_eq!(tcp.local_port, 3306);
+        assert_eq!(tcp.remote_addr, IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+        assert_eq!(tcp.state, "LISTEN");
+        assert_eq!((tcp.tx_queue, tcp.rx_queue), (0, 2));
+        assert_eq!((tcp.uid, tcp.inode), (999, 21543));
+
+        let tcp6 = Socket::parse_line(
+            "tcp6",
+            "   1: 00000000000000000000000001000000:0016 0000FFFF00000000B80D0120000000000:0000 01 00000000:00000000 00:00000000 00000000     0        0 1234 1",
+        );
+        // 远端地址多了一位, 整行无效
+        assert!(tcp6.is_none());
+
+        let tcp6 = Socket::parse_line(
+            "tcp6",
+            "   1: 00000000000000000000000001000000:0016 B80D0120000000000000000001000000:C350 01 00000000:00000000 00:00000000 00000000     0        0 1234 1",
+        ).unwrap();
+        assert_eq!(tcp6.local_addr, IpAddr::V6(Ipv6Addr::LOCALHOST));
+        assert_eq!(tcp6.local_port, 22);
+        assert_eq!(tcp6.remote_addr, "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(tcp6.remote_port, 50000);
+        assert_eq!(tcp6.state, "ESTABLISHED");
+    }
+
+    #[test]
+    fn udp_uses_its_own_state_names() {
+        let line = format!(
+            "  5: {}:0035 {}:0000 07 00000000:00000000 00:00000000 00000000   101        0 4242 2",
+            hex_v4(Ipv4Addr::new(127, 0, 0, 53)),
+            hex_v4(Ipv4Addr::UNSPECIFIED),
+        );
+        let udp = Socket::parse_line("udp", &line).unwrap();
+        assert_eq!(udp.local_port, 53);
+        assert_eq!(udp.state, "UNCONN");
+
+        assert!(Socket::parse_line("udp", "  5: 0100007F:0035 00000000:0000 07").is_none());
+    }
+}
@@ -0,0 +1,389 @@
+//! 单个进程的详细信息, 在 ProcessStatus 的基础上读取 /proc/<pid> 下的以下文件:
+//! fd/<n>: 指向打开文件的符号链接, socket、pipe 等显示为 socket:[12345]、pipe:[67890],
+//! 已删除但仍被打开的文件末尾带 " (deleted)"。fdinfo/<n> 中的 pos 为当前读写偏移。
+//! limits: 与 ulimit 对应的资源限制, 按表头 "Soft Limit"、"Hard Limit"、"Units" 的位置切分列:
+//! Max open files            1024                 524288               files
+//! maps: 每行一个内存区域, 依次为 地址范围、权限、偏移、设备、inode、路径 (匿名映射没有路径):
+//! 55d530a8c000-55d530a8e000 r--p 00000000 fe:00 317783                     /usr/bin/head
+//! cgroup: 每行 "层级 ID:控制器列表:路径", cgroup v2 只有一行 0::/path
+//! ns/<name>: 指向 net:[4026531840] 形式的符号链接, 方括号中的 inode 相同说明处于同一命名空间
+//! cwd、exe: 工作目录和可执行文件的符号链接, 内核线程没有 exe
+//! environ: 以 \0 分隔的 KEY=VALUE, 可能包含密码等敏感信息, 默认只返回变量名
+//! fd、environ、cwd、exe 需要与目标进程相同的用户或 root 权限, 没有权限时对应字段为空。
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io;
+
+use serde::Serialize;
+
+use crate::hand::node::user::get_usernames;
+use crate::node_exporter::proc_utils::process::{get_boot_time, ProcessStatus};
+
+// 默认情况下环境变量的值用它代替
+pub const REDACTED: &str = "<redacted>";
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessDetail {
+    #[serde(flatten)]
+    pub process: ProcessStatus,
+    pub cwd: Option<String>,
+    pub exe: Option<String>,
+    pub fds: Option<Vec<OpenFile>>,
+    pub limits: Vec<ProcessLimit>,
+    pub maps: Option<MapsSummary>,
+    pub cgroups: Vec<CgroupEntry>,
+    // 命名空间类型 -> inode
+    pub namespaces: Option<BTreeMap<String, u64>>,
+    pub environ: Option<BTreeMap<String, String>>,
+}
+
+// OpenFile 是进程打开的一个文件描述符
+#[derive(Debug, Clone, Serialize)]
+pub struct OpenFile {
+    pub fd: u32,
+    // 符号链接指向的目标
+    pub target: String,
+    // file、socket、pipe、anon_inode 等
+    pub kind: String,
+    // 文件已被删除但仍被进程打开
+    pub deleted: bool,
+    // 当前读写偏移
+    pub pos: Option<u64>,
+}
+
+// ProcessLimit 是 /proc/<pid>/limits 中的一行, 值为空表示 unlimited
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessLimit {
+    pub name: String,
+    pub soft: Option<u64>,
+    pub hard: Option<u64>,
+    pub units: Option<String>,
+}
+
+// MapsSummary 按类型汇总进程的内存映射, 大小单位为 KB
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MapsSummary {
+    pub regions: usize,
+    pub total: u64,
+    // 映射了文件的区域 (可执行文件、动态库等)
+    pub file: u64,
+    // 没有路径的匿名映射
+    pub anon: u64,
+    pub heap: u64,
+    pub stack: u64,
+    // 可执行的区域
+    pub executable: u64,
+    // 映射的文件, 去重后按路径排序
+    pub files: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CgroupEntry {
+    pub hierarchy: u32,
+    // cgroup v2 的控制器列表为空
+    pub controllers: Vec<String>,
+    pub path: String,
+}
+
+impl ProcessDetail {
+    // of 读取单个进程的详细信息, show_environ 为 false 时隐藏环境变量的值
+    pub fn of(pid: u32, show_environ: bool) -> Result<Self, io::Error> {
+        let process = ProcessStatus::from_pid(pid, &get_usernames(), get_boot_time()?)
+            .map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => io::Error::new(io::ErrorKind::NotFound, format!("No such process {}", pid)),
+                _ => e,
+            })?;
+
+        Ok(Self {
+            process,
+            // 没有权限时为空, 内核线程没有 exe
+            cwd: read_link(pid, "cwd").ok(),
+            exe: read_link(pid, "exe").ok(),
+            fds: permitted(open_files(pid))?,
+            limits: ProcessLimit::parse(&fs::read_to_string(format!("/proc/{}/limits", pid))?),
+            maps: permitted(fs::read_to_string(format!("/proc/{}/maps", pid)))?
+                .map(|content| MapsSummary::parse(&content)),
+            cgroups: CgroupEntry::parse(&fs::read_to_string(format!("/proc/{}/cgroup", pid))?),
+            namespaces: permitted(namespaces(pid))?,
+            environ: permitted(fs::read(format!("/proc/{}/environ", pid)))?
+                .map(|content| parse_environ(&content, show_environ)),
+        })
+    }
+}
+
+impl ProcessLimit {
+    pub fn parse(content: &str) -> Vec<Self> {
+        let mut lines = content.lines();
+        let Some(header) = lines.next() else {
+            return Vec::new();
+        };
+        let (Some(soft), Some(hard), Some(units)) =
+            (header.find("Soft Limit"), header.find("Hard Limit"), header.find("Units")) else {
+            return Vec::new();
+        };
+
+        let column = |line: &str, start: usize, end: usize| {
+            line.get(start..end.min(line.len())).unwrap_or_default().trim().to_string()
+        };
+        let value = |value: String| value.parse::<u64>().ok();
+
+        lines.filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                let units_value = column(line, units, line.len());
+                Self {
+                    name: column(line, 0, soft),
+                    soft: value(column(line, soft, hard)),
+                    hard: value(column(line, hard, units)),
+                    units: if units_value.is_empty() { None } else { Some(units_value) },
+                }
+            })
+            .collect()
+    }
+}
+
+impl MapsSummary {
+    pub fn parse(content: &str) -> Self {
+        let mut summary = Self::default();
+        let mut files = BTreeSet::new();
+
+        for line in content.lines() {
+            let mut fields = line.split_whitespace();
+            let (Some(range), Some(perms)) = (fields.next(), fields.next()) else {
+                continue;
+            };
+            let Some((start, end)) = range.split_once('-') else {
+                continue;
+            };
+            let (Ok(start), Ok(end)) = (u64::from_str_radix(start, 16), u64::from_str_radix(end, 16)) else {
+                continue;
+            };
+            // 跳过偏移、设备、inode, 剩下的是路径, 路径中可能有空格
+            let path = fields.skip(3).collect::<Vec<_>>().join(" ");
+            let size = end.saturating_sub(start) / 1024;
+
+            summary.regions += 1;
+            summary.total += size;
+            if perms.contains('x') {
+                summary.executable += size;
+            }
+            match path.as_str() {
+                "" => summary.anon += size,
+                "[heap]" => summary.heap += size,
+                path if path.starts_with("[stack") => summary.stack += size,
+                // [vdso]、[vvar] 等内核提供的区域
+                path if path.starts_with('[') => {}
+                path => {
+                    summary.file += size;
+                    files.insert(path.to_string());
+                }
+            }
+        }
+
+        summary.files = files.into_iter().collect();
+        summary
+    }
+}
+
+impl CgroupEntry {
+    pub fn parse(content: &str) -> Vec<Self> {
+        content.lines()
+            .filter_map(|line| {
+                let mut parts = line.splitn(3, ':');
+                let hierarchy = parts.next()?.parse::<u32>().ok()?;
+                let controllers = parts.next()?;
+                let path = parts.next()?;
+                Some(Self {
+                    hierarchy,
+                    controllers: controllers.split(',').filter(|name| !name.is_empty()).map(str::to_string).collect(),
+                    path: path.to_string(),
+                })
+            })
+            .collect()
+    }
+}
+
+// permitted 把没有权限的错误转换成 None, 其他错误原样返回。
+// 内核线程读取 environ 时返回 ESRCH, 同样当作不可读取
+fn permitted<T>(result: Result<T, io::Error>) -> Result<Option<T>, io::Error> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(e) if e.kind() == io::ErrorKind::PermissionDenied => Ok(None),
+        Err(e) if e.raw_os_error() == Some(libc::ESRCH) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn read_link(pid: u32, name: &str) -> Result<String, io::Error> {
+    Ok(fs::read_link(format!("/proc/{}/{}", pid, name))?.to_string_lossy().into_owned())
+}
+
+fn open_files(pid: u32) -> Result<Vec<OpenFile>, io::Error> {
+    let mut files = Vec::new();
+
+    for entry in fs::read_dir(format!("/proc/{}/fd", pid))? {
+        let entry = entry?;
+        let Some(fd) = entry.file_name().to_str().and_then(|name| name.parse::<u32>().ok()) else {
+            continue;
+        };
+        // 读取过程中 fd 可能已经被关闭
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        let target = target.to_string_lossy().into_owned();
+
+        let kind = if target.starts_with('/') {
+            "file".to_string()
+        } else {
+            // socket:[12345]、pipe:[67890]、anon_inode:[eventfd]
+            target.split(':').next().unwrap_or_default().to_string()
+        };
+        let pos = fs::read_to_string(format!("/proc/{}/fdinfo/{}", pid, fd)).ok()
+            .and_then(|content| content.lines()
+                .find_map(|line| line.strip_prefix("pos:"))
+                .and_then(|value| value.trim().parse::<u64>().ok()));
+
+        files.push(OpenFile {
+            fd,
+            deleted: kind == "file" && target.ends_with(" (deleted)"),
+            target,
+            kind,
+            pos,
+        });
+    }
+
+    files.sort_by_key(|file| file.fd);
+    Ok(files)
+}
+
+fn namespaces(pid: u32) -> Result<BTreeMap<String, u64>, io::Error> {
+    let mut namespaces = BTreeMap::new();
+
+    for entry in fs::read_dir(format!("/proc/{}/ns", pid))? {
+        let entry = entry?;
+        let Ok(target) = fs::read_link(entry.path()) else {
+            continue;
+        };
+        // net:[4026531840]
+        let inode = target.to_str()
+            .and_then(|target| target.split_once(":["))
+            .and_then(|(_, inode)| inode.strip_suffix(']'))
+            .and_then(|inode| inode.parse::<u64>().ok());
+        if let Some(inode) = inode {
+            namespaces.insert(entry.file_name().to_string_lossy().into_owned(), inode);
+        }
+    }
+
+    Ok(namespaces)
+}
+
+fn parse_environ(content: &[u8], show_values: bool) -> BTreeMap<String, String> {
+    content.split(|byte| *byte == 0)
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            let entry = String::from_utf8_lossy(entry);
+            let (key, value) = entry.split_once('=').unwrap_or((&entry, ""));
+            let value = if show_values { value.to_string() } else { REDACTED.to_string() };
+            (key.to_string(), value)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn environ_values_are_redacted_by_default() {
+        let content = b"PATH=/usr/bin\0DB_PASSWORD=hunter2\0EMPTY=\0NOEQUALS\0URL=a=b\0";
+
+        let environ = parse_environ(content, false);
+        assert_eq!(environ.keys().collect::<Vec<_>>(), ["DB_PASSWORD", "EMPTY", "NOEQUALS", "PATH", "URL"]);
+        assert!(environ.values().all(|value| value == REDACTED));
+
+        let environ = parse_environ(content, true);
+        assert_eq!(environ["DB_PASSWORD"], "hunter2");
+        assert_eq!(environ["EMPTY"], "");
+        assert_eq!(environ["NOEQUALS"], "");
+        // 只在第一个 '=' 处切分
+        assert_eq!(environ["URL"], "a=b");
+    }
+
+    #[test]
+    fn parses_limits_by_header_columns() {
+        let content = "\
+Limit                     Soft Limit           Hard Limit           Units     
+Max cpu time              unlimited            unlimited            seconds   
+Max open files            1024                 524288               files     
+Max nice priority         0                    0                    
+";
+        let limits = ProcessLimit::parse(content);
+
+        assert_eq!(limits.len(), 3);
+        assert_eq!(limits[0].name, "Max cpu time");
+        assert_eq!((limits[0].soft, limits[0].hard), (None, None));
+        assert_eq!(limits[0].units.as_deref(), Some("seconds"));
+        assert_eq!(limits[1].name, "Max open files");
+        assert_eq!((limits[1].soft, limits[1].hard), (Some(1024), Some(524288)));
+        assert_eq!(limits[2].units, None);
+
+        assert!(ProcessLimit::parse("").is_empty());
+        assert!(ProcessLimit::parse("garbage header\nMax open files 1 2 files").is_empty());
+    }
+
+    #[test]
+    fn summarizes_maps() {
+        let content = "\
+55d530a8c000-55d530a8e000 r--p 00000000 fe:00 317783                     /usr/bin/head
+55d530a8e000-55d530a92000 r-xp 00002000 fe:00 317783                     /usr/bin/head
+55d531000000-55d531021000 rw-p 00000000 00:00 0                          [heap]
+7f0000000000-7f0000100000 rw-p 00000000 00:00 0 
+7f0000100000-7f0000102000 r--p 00000000 fe:00 42                         /tmp/my file (deleted)
+7ffc00000000-7ffc00021000 rw-p 00000000 00:00 0                          [stack]
+7ffc00100000-7ffc00102000 r-xp 00000000 00:00 0                          [vdso]
+not a maps line
+";
+        let maps = MapsSummary::parse(content);
+
+        assert_eq!(maps.regions, 7);
+        assert_eq!(maps.file, 8 + 16 + 8);
+        assert_eq!(maps.heap, 132);
+        assert_eq!(maps.anon, 1024);
+        assert_eq!(maps.stack, 132);
+        assert_eq!(maps.executable, 16 + 8);
+        assert_eq!(maps.total, 8 + 16 + 132 + 1024 + 8 + 132 + 8);
+        assert_eq!(maps.files, ["/tmp/my file (deleted)", "/usr/bin/head"]);
+    }
+
+    #[test]
+    fn parses_cgroup_v1_and_v2() {
+        let cgroups = CgroupEntry::parse("12:cpu,cpuacct:/system.slice/nginx.service\n0::/user.slice/a:b\nbad line\n");
+
+        assert_eq!(cgroups.len(), 2);
+        assert_eq!(cgroups[0].hierarchy, 12);
+        assert_eq!(cgroups[0].controllers, ["cpu", "cpuacct"]);
+        assert_eq!(cgroups[0].path, "/system.slice/nginx.service");
+        assert!(cgroups[1].controllers.is_empty());
+        // 路径中的 ':' 不参与切分
+        assert_eq!(cgroups[1].path, "/user.slice/a:b");
+    }
+
+    #[test]
+    fn permission_errors_become_none() {
+        let denied = permitted::<()>(Err(io::Error::from(io::ErrorKind::PermissionDenied))).unwrap();
+        assert!(denied.is_none());
+        let exited = permitted::<()>(Err(io::Error::from_raw_os_error(libc::ESRCH))).unwrap();
+        assert!(exited.is_none());
+        assert!(permitted::<()>(Err(io::Error::from(io::ErrorKind::NotFound))).is_err());
+    }
+
+    #[test]
+    fn detail_of_own_process_hides_environ_values() {
+        let detail = ProcessDetail::of(std::process::id(), false).unwrap();
+        let environ = detail.environ.unwrap();
+
+        assert!(environ.contains_key("PATH"));
+        assert!(environ.values().all(|value| value == REDACTED));
+        assert!(detail.fds.unwrap().iter().any(|file| file.fd == 0));
+    }
+}
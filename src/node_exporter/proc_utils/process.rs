@@ -1,16 +1,142 @@
+//! 进程信息在 /proc/<pid> 目录下, 主要用到以下文件:
+//! stat: 一行以空格分隔的字段, 大致如下:
+//! 9990 (cat) R 9984 9990 9984 0 -1 4194304 79 0 0 0 0 0 0 0 20 0 1 0 273883 2703360 285 ...
+//! 依次为 pid、(comm)、状态、ppid、pgrp、session、tty_nr、tpgid、flags、minflt、cminflt、majflt、cmajflt、
+//! utime、stime、cutime、cstime、priority、nice、num_threads、itrealvalue、starttime、vsize、rss ...
+//! comm 中可能包含空格和括号, 所以以最后一个 ')' 为分界。utime、stime、starttime 的单位是 jiffies。
+//! status: 每行一个 "键: 值", 其中 Uid 依次为 real、effective、saved、fs uid, VmRSS/VmSize 单位是 kB,
+//! 内核线程没有 VmRSS/VmSize。
+//! cmdline: 以 \0 分隔的启动参数, 内核线程为空。
+
 use std::{fs, io};
+use std::collections::{BTreeMap, HashMap};
 
 use std::path::PathBuf;
-use serde::{Deserialize, Serialize};
+use serde::Serialize;
+
+use crate::hand::node::user::get_usernames;
+use crate::node_exporter::collector::{Collector, MetricFamily};
+use crate::node_exporter::cpu_utils::cpustat::clock_ticks;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct ProcessStatus {
-    pid: u32,
-    name: String,
-    command: String,
-    pr: u32,
-    nice: u32,
-    status_info: String
+    pub pid: u32,
+    pub ppid: u32,
+    // /proc/<pid>/stat 中的 comm, 最长 15 个字符
+    pub name: String,
+    pub command: String,
+    // R 运行、S 睡眠、D 不可中断睡眠、Z 僵尸、T 停止、I 空闲 ...
+    pub state: char,
+    pub uid: u32,
+    pub user: Option<String>,
+    // 与 top 中的 PR 一致, 实时进程为负数
+    pub pr: i64,
+    pub nice: i64,
+    pub threads: u64,
+    // 虚拟内存 (KB)
+    pub virt: u64,
+    // 常驻内存 (KB)
+    pub res: u64,
+    // 用户态和内核态 cpu 时间 (jiffies)
+    pub utime: u64,
+    pub stime: u64,
+    // 累计 cpu 时间 (秒)
+    pub cpu_time: f64,
+    // 启动时间 (unix 时间戳, 秒)
+    pub start_time: u64,
+}
+
+// ProcStat 是 /proc/<pid>/stat 中用到的字段
+#[derive(Debug, Clone, Default)]
+pub struct ProcStat {
+    pub comm: String,
+    pub state: char,
+    pub ppid: u32,
+    pub pgrp: i32,
+    pub utime: u64,
+    pub stime: u64,
+    pub priority: i64,
+    pub nice: i64,
+    pub num_threads: u64,
+    // 自系统启动以来的 jiffies
+    pub starttime: u64,
+}
+
+impl ProcStat {
+    pub fn read(pid: u32) -> Result<Self, io::Error> {
+        let content = fs::read_to_string(format!("/proc/{}/stat", pid))?;
+        Self::parse(&content)
+    }
+
+    pub fn parse(content: &str) -> Result<Self, io::Error> {
+        let invalid = |message: &str| io::Error::new(io::ErrorKind::InvalidData, message.to_string());
+
+        let open = content.find('(').ok_or_else(|| invalid("stat 格式无效"))?;
+        // comm 中可能包含空格和括号, 以最后一个 ')' 为准
+        let close = content.rfind(')').filter(|close| *close > open).ok_or_else(|| invalid("stat 格式无效"))?;
+
+        let comm = content[open + 1..close].to_string();
+        let fields = content[close + 1..].split_whitespace().collect::<Vec<_>>();
+
+        if fields.len() < 20 {
+            return Err(invalid("stat 字段数量不足"));
+        }
+
+        fn number<T: std::str::FromStr>(fields: &[&str], index: usize) -> Result<T, io::Error> {
+            fields[index].parse::<T>()
+                .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, format!("stat 第 {} 个字段解析失败", index + 3)))
+        }
+
+        Ok(Self {
+            comm,
+            state: fields[0].chars().next().unwrap_or('?'),
+            ppid: number(&fields, 1)?,
+            pgrp: number(&fields, 2)?,
+            utime: number(&fields, 11)?,
+            stime: number(&fields, 12)?,
+            priority: number(&fields, 15)?,
+            nice: number(&fields, 16)?,
+            num_threads: number(&fields, 17)?,
+            starttime: number(&fields, 19)?,
+        })
+    }
+}
+
+// ProcStatusFile 是 /proc/<pid>/status 中用到的字段
+#[derive(Debug, Clone, Default)]
+pub struct ProcStatusFile {
+    // real uid
+    pub uid: u32,
+    pub vm_size: u64,
+    pub vm_rss: u64,
+}
+
+impl ProcStatusFile {
+    pub fn read(pid: u32) -> Result<Self, io::Error> {
+        let content = fs::read_to_string(format!("/proc/{}/status", pid))?;
+        Ok(Self::parse(&content))
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut result = Self::default();
+
+        for line in content.lines() {
+            let mut parts = line.split_whitespace();
+            let Some(key) = parts.next() else {
+                continue;
+            };
+            let value = parts.next().and_then(|value| value.parse::<u64>().ok()).unwrap_or_default();
+
+            match key {
+                "Uid:" => result.uid = value as u32,
+                "VmSize:" => result.vm_size = value,
+                "VmRSS:" => result.vm_rss = value,
+                _ => {}
+            }
+        }
+
+        result
+    }
 }
 
 impl ProcessStatus {
@@ -18,25 +144,50 @@ impl ProcessStatus {
         let mut processes = Vec::new();
 
         let pids = Self::get_pids()?;
+        let usernames = get_usernames();
+        let boot_time = get_boot_time()?;
 
         for pid in pids {
-            let name = Self::get_process_name(&pid);
-            let command = Self::get_process_cmdline(&pid)?;
-            let status_info = Self::get_process_status(pid)?;
-
-            processes.push(Self {
-                pid,
-                name,
-                command,
-                pr: 0,
-                nice: 0,
-                status_info,
-            });
+            // 读取过程中进程可能已经退出, 直接跳过
+            match Self::from_pid(pid, &usernames, boot_time) {
+                Ok(process) => processes.push(process),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) if e.raw_os_error() == Some(libc::ESRCH) => continue,
+                Err(e) => return Err(e),
+            }
         }
         Ok(processes)
     }
 
-    fn get_pids() -> Result<Vec<u32>, io::Error> {
+    // 读取单个进程的信息
+    pub fn from_pid(pid: u32, usernames: &HashMap<u32, String>, boot_time: u64) -> Result<Self, io::Error> {
+        let stat = ProcStat::read(pid)?;
+        let status = ProcStatusFile::read(pid)?;
+        let command = Self::get_process_cmdline(&pid)?;
+        let ticks = clock_ticks();
+
+        Ok(Self {
+            pid,
+            ppid: stat.ppid,
+            // 内核线程没有 cmdline, 与 ps 一样用 [comm] 表示
+            command: if command.is_empty() { format!("[{}]", stat.comm) } else { command },
+            name: stat.comm,
+            state: stat.state,
+            uid: status.uid,
+            user: usernames.get(&status.uid).cloned(),
+            pr: stat.priority,
+            nice: stat.nice,
+            threads: stat.num_threads,
+            virt: status.vm_size,
+            res: status.vm_rss,
+            utime: stat.utime,
+            stime: stat.stime,
+            cpu_time: (stat.utime + stat.stime) as f64 / ticks,
+            start_time: boot_time + (stat.starttime as f64 / ticks) as u64,
+        })
+    }
+
+    pub(crate) fn get_pids() -> Result<Vec<u32>, io::Error> {
         let mut process_pids = Vec::new();
 
         // 遍历/proc目录获取进程 pid
@@ -44,98 +195,119 @@ impl ProcessStatus {
             let entry = entry?;
             let path = entry.path();
 
-/*            // 检查是否为目录并且名字可以转化为整数
-            if path.is_dir() && path.file_name()
-                .and_then(|name| name.to_str())
-                .map_or(false, |name| name.parse::<u32>().is_ok())
-            {
-                let pid = path.file_name().unwrap().to_str().unwrap().parse::<u32>()?;
-                process_pids.push(pid);
-            }
-        }
-        Ok(process_pids)*/
-
         if path.is_dir() {
             let file_name = path.file_name().and_then(|name| name.to_str());
 
-            if let Some(name) = file_name {
-                match name.parse::<u32>() {
-                    Ok(pid) => process_pids.push(pid),
-                    Err(err) => return Err(io::Error::new(
-                        io::ErrorKind::InvalidInput,
-                        format!("Failed to parse PID: {}", err),
-                    )),
-                }
+            // /proc 下还有 sys、net 等非进程目录, 只保留名字是数字的目录
+            if let Some(Ok(pid)) = file_name.map(|name| name.parse::<u32>()) {
+                process_pids.push(pid);
             }
         }
     }
     Ok(process_pids)
     }
 
-    pub fn get_process_name(pid: &u32) -> String{
-        "TODO".parse().unwrap()
-    }
-
     fn get_process_cmdline(pid: &u32) -> Result<String, io::Error> {
         // 读取进程的cmdline信息
         let cmdline = PathBuf::from(format!("/proc/{}/cmdline", pid));
-        if let Ok(cmdline_content) = fs::read_to_string(cmdline) {
-            Ok(cmdline_content.trim().replace('\0', " "))
-        } else {
-            return Err(io::Error::new(
-                io::ErrorKind::Other,
-                format!("Failed to read cmdline for PID {}", pid),
-            ));
-        }
+        // 启动参数不一定是合法的 utf-8
+        let cmdline_content = String::from_utf8_lossy(&fs::read(cmdline)?).into_owned();
+        Ok(cmdline_content.trim_end_matches('\0').replace('\0', " "))
     }
+}
 
-    fn get_process_status(pid: u32) -> Result<String, io::Error> {
-        // 读取进程的状态信息（status）
-        let status_path = PathBuf::from(format!("/proc/{}/status", pid));
-        // 读取文件内容并转换为字符串
-        let status_content = fs::read_to_string(status_path)?;
-        // 将每一行内容合并成一个单一的字符串，每行之间用换行符分隔
-        let combined_status = status_content.lines().map(|line| line.to_string()).collect::<Vec<_>>().join("\n");
+// get_boot_time 从 /proc/stat 的 btime 行读取系统启动时间 (unix 时间戳, 秒)
+pub fn get_boot_time() -> Result<u64, io::Error> {
+    let content = fs::read_to_string("/proc/stat")?;
+    content.lines()
+        .find_map(|line| line.strip_prefix("btime "))
+        .and_then(|value| value.trim().parse::<u64>().ok())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Missing btime in /proc/stat"))
+}
+
+// ProcessCollector 导出进程数量和各状态的进程数
+pub struct ProcessCollector;
 
-        Ok(combined_status)
+impl Collector for ProcessCollector {
+    fn name(&self) -> &'static str {
+        "processes"
     }
 
-    fn reorganize_process_information(p: String) -> Result<ProcessStatus, io::Error> {
-        let mut result = Self {
-            pid: 0,
-            name: String::new(),
-            command: String::new(),
-            pr: 0,
-            nice: 0,
-            status_info: String::new(),
-        };
-
-        for line in p.lines() {
-            let mut parts = line.split_whitespace();
-            let key = parts.next().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "行格式无效"))?;
-            let value = parts.next();
+    fn collect(&self) -> Result<Vec<MetricFamily>, io::Error> {
+        let pids = ProcessStatus::get_pids()?;
 
-            match key {
-                "Pid:" => {
-                    result.pid = value.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "PID解析失败"))?
-                        .parse::<u32>()
-                        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("PID解析失败: {}", e)))?;
-                },
-                "Name:" => {
-                    result.name = value.map(String::from).unwrap_or_default();
-                },
-                _ => {}
+        let mut states: BTreeMap<char, usize> = BTreeMap::new();
+        let mut threads = 0;
+        for pid in &pids {
+            if let Ok(stat) = ProcStat::read(*pid) {
+                *states.entry(stat.state).or_default() += 1;
+                threads += stat.num_threads;
             }
         }
 
-        Ok(result)
+        let mut state_family = MetricFamily::gauge("node_processes_state", "Number of processes in each state.");
+        for (state, count) in states {
+            state_family.add(&[("state", &state.to_string())], count as f64);
+        }
+
+        Ok(vec![
+            MetricFamily::gauge("node_processes_pids", "Number of PIDs").with_value(pids.len() as f64),
+            MetricFamily::gauge("node_processes_threads", "Allocated threads in system").with_value(threads as f64),
+            state_family,
+        ])
     }
+}
 
-    fn get_nice(){}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-    fn get_virt(){}
+    // 从 state 开始的 /proc/<pid>/stat 字段, pgrp 为 42, utime/stime 为 100/50, starttime 为 12345
+    const STAT_TAIL: &str = "S 1 42 42 0 -1 4194560 500 0 0 0 100 50 0 0 20 0 3 0 12345 10000000 200 18446744073709551615";
 
+    #[test]
+    fn parses_stat() {
+        let stat = ProcStat::parse(&format!("1234 (nginx) {}", STAT_TAIL)).unwrap();
 
-}
+        assert_eq!(stat.comm, "nginx");
+        assert_eq!(stat.state, 'S');
+        assert_eq!((stat.ppid, stat.pgrp), (1, 42));
+        assert_eq!((stat.utime, stat.stime), (100, 50));
+        assert_eq!((stat.priority, stat.nice), (20, 0));
+        assert_eq!(stat.num_threads, 3);
+        assert_eq!(stat.starttime, 12345);
+    }
 
+    #[test]
+    fn parses_comm_with_spaces_and_parentheses() {
+        let stat = ProcStat::parse(&format!("1 (a) b) {}", STAT_TAIL)).unwrap();
+        assert_eq!(stat.comm, "a) b");
+        assert_eq!(stat.ppid, 1);
 
+        let stat = ProcStat::parse(&format!("7 (Web Content (x)) {}", STAT_TAIL)).unwrap();
+        assert_eq!(stat.comm, "Web Content (x)");
+        assert_eq!(stat.starttime, 12345);
+
+        let stat = ProcStat::parse(&format!("8 () {}", STAT_TAIL)).unwrap();
+        assert_eq!(stat.comm, "");
+    }
+
+    #[test]
+    fn rejects_malformed_stat() {
+        for content in ["", "1 nginx S 1", "1 ) (nginx", "1 (nginx) S 1 42", "1 (nginx) S x 42 42 0 -1 0 0 0 0 0 1 1 0 0 20 0 1 0 1"] {
+            let err = ProcStat::parse(content).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{:?}", content);
+        }
+    }
+
+    #[test]
+    fn parses_status_file() {
+        let status = ProcStatusFile::parse("Name:\tnginx\nUid:\t33\t33\t33\t33\nVmSize:\t  10240 kB\nVmRSS:\t   2048 kB\n");
+        assert_eq!(status.uid, 33);
+        assert_eq!((status.vm_size, status.vm_rss), (10240, 2048));
+
+        // 内核线程没有 VmSize/VmRSS
+        let status = ProcStatusFile::parse("Name:\tkthreadd\nUid:\t0\t0\t0\t0\n");
+        assert_eq!((status.uid, status.vm_size, status.vm_rss), (0, 0, 0));
+    }
+}
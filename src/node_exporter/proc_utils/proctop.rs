@@ -0,0 +1,180 @@
+//! 单次读取 /proc/<pid>/stat 只能得到进程启动以来累计的 cpu 时间, 无法知道进程 "现在" 是否繁忙。
+//! ProcessSampler 在后台定期采样, 保存每个进程上一次的 utime + stime, 用两次采样的差值计算 %CPU,
+//! 计算方式与 top 一致: 进程 jiffies 增量 / (采样间隔秒数 * 每秒 jiffies) * 100, 多线程进程可能超过 100。
+//! %MEM 为常驻内存占 MemTotal 的百分比。
+
+use std::collections::HashMap;
+use std::io;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+
+use crate::node_exporter::cpu_utils::cpustat::clock_ticks;
+use crate::node_exporter::mem_utils::meminfo::MemInfo;
+use crate::node_exporter::proc_utils::process::ProcessStatus;
+use crate::node_exporter::sampler::{Sampler, Snapshot};
+
+// 后台采样间隔, 与 top 的默认刷新间隔一致
+pub const SAMPLE_INTERVAL: Duration = Duration::from_secs(3);
+
+// ProcessUsage 是一个进程在最近一个采样周期内的资源占用
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessUsage {
+    #[serde(flatten)]
+    pub process: ProcessStatus,
+    pub cpu_percent: f64,
+    pub mem_percent: f64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SortBy {
+    #[default]
+    Cpu,
+    Mem,
+}
+
+// ProcessSnapshot 是一次采样得到的所有进程和系统总内存
+#[derive(Debug, Clone, Default)]
+pub struct ProcessSnapshot {
+    pub processes: Vec<ProcessStatus>,
+    pub mem_total: u64,
+}
+
+impl Snapshot for ProcessSnapshot {
+    type Output = Vec<ProcessUsage>;
+
+    const SOURCE: &'static str = "processes";
+    const INTERVAL: Duration = SAMPLE_INTERVAL;
+
+    fn read() -> Result<Self, io::Error> {
+        let processes = ProcessStatus::processes()?;
+        let mem_total = MemInfo::init()
+            .map_err(|e| io::Error::other(e.to_string()))?
+            .total;
+        Ok(Self { processes, mem_total })
+    }
+
+    fn between(prev: &Self, cur: &Self, elapsed: Duration) -> Vec<ProcessUsage> {
+        let elapsed = elapsed.as_secs_f64();
+        let ticks = clock_ticks();
+
+        // (pid, 启动时间) -> 上一次的 utime + stime, 加上启动时间是为了区分被复用的 pid
+        let last = prev.processes.iter()
+            .map(|process| ((process.pid, process.start_time), process.utime + process.stime))
+            .collect::<HashMap<_, _>>();
+
+        cur.processes.iter()
+            .map(|process| {
+                let jiffies = process.utime + process.stime;
+
+                // 新启动的进程没有上一次采样, 本周期按 0 计算
+                let cpu_percent = match last.get(&(process.pid, process.start_time)) {
+                    Some(prev) if elapsed > 0.0 => jiffies.saturating_sub(*prev) as f64 / (elapsed * ticks) * 100.0,
+                    _ => 0.0,
+                };
+                let mem_percent = if cur.mem_total == 0 {
+                    0.0
+                } else {
+                    process.res as f64 / cur.mem_total as f64 * 100.0
+                };
+
+                ProcessUsage { process: process.clone(), cpu_percent, mem_percent }
+            })
+            .collect()
+    }
+}
+
+// ProcessSampler 在后台定期采样所有进程的 cpu 时间
+pub type ProcessSampler = Sampler<ProcessSnapshot>;
+
+impl Sampler<ProcessSnapshot> {
+    // usage 返回最近一个采样周期内所有进程的资源占用, 第一个周期结束前为空
+    pub fn usage(&self) -> Vec<ProcessUsage> {
+        self.latest().unwrap_or_default()
+    }
+
+    // top 返回按 by 排序后占用最高的 n 个进程
+    pub fn top(&self, by: SortBy, n: usize) -> Vec<ProcessUsage> {
+        let mut usage = self.usage();
+        let key = |usage: &ProcessUsage| match by {
+            SortBy::Cpu => usage.cpu_percent,
+            SortBy::Mem => usage.mem_percent,
+        };
+        usage.sort_by(|a, b| key(b).total_cmp(&key(a)));
+        usage.truncate(n);
+        usage
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn process(pid: u32, start_time: u64, utime: u64, stime: u64, res: u64) -> ProcessStatus {
+        ProcessStatus {
+            pid,
+            ppid: 1,
+            name: format!("p{}", pid),
+            command: String::new(),
+            state: 'S',
+            uid: 0,
+            user: None,
+            pr: 20,
+            nice: 0,
+            threads: 1,
+            virt: 0,
+            res,
+            utime,
+            stime,
+            cpu_time: 0.0,
+            start_time,
+        }
+    }
+
+    fn snapshot(processes: Vec<ProcessStatus>) -> ProcessSnapshot {
+        ProcessSnapshot { processes, mem_total: 1000 }
+    }
+
+    fn cpu_of(usage: &[ProcessUsage], pid: u32) -> f64 {
+        usage.iter().find(|usage| usage.process.pid == pid).unwrap().cpu_percent
+    }
+
+    #[test]
+    fn computes_cpu_and_memory_percent_from_deltas() {
+        let ticks = clock_ticks();
+        let prev = snapshot(vec![process(10, 100, 50, 50, 250)]);
+        // 两秒内用掉 ticks 个 jiffies, 即一秒 cpu 时间
+        let cur = snapshot(vec![process(10, 100, 50 + ticks as u64 / 2, 50 + ticks as u64 / 2, 250)]);
+
+        let usage = ProcessSnapshot::between(&prev, &cur, Duration::from_secs(2));
+        assert!((cpu_of(&usage, 10) - 50.0).abs() < 1.0);
+        assert_eq!(usage[0].mem_percent, 25.0);
+    }
+
+    #[test]
+    fn reused_pid_is_treated_as_a_new_process() {
+        let prev = snapshot(vec![process(10, 100, 1, 1, 0), process(11, 100, 1, 1, 0)]);
+        // pid 10 退出后被一个新进程复用, 启动时间不同; pid 12 是新进程
+        let cur = snapshot(vec![
+            process(10, 200, 9000, 9000, 0),
+            process(11, 100, 1, 1, 0),
+            process(12, 300, 500, 500, 0),
+        ]);
+
+        let usage = ProcessSnapshot::between(&prev, &cur, Duration::from_secs(3));
+        assert_eq!(usage.len(), 3);
+        assert_eq!(cpu_of(&usage, 10), 0.0);
+        assert_eq!(cpu_of(&usage, 11), 0.0);
+        assert_eq!(cpu_of(&usage, 12), 0.0);
+    }
+
+    #[test]
+    fn zero_interval_or_memory_yields_zero() {
+        let prev = snapshot(vec![process(10, 100, 0, 0, 10)]);
+        let cur = ProcessSnapshot { processes: vec![process(10, 100, 100, 0, 10)], mem_total: 0 };
+
+        let usage = ProcessSnapshot::between(&prev, &cur, Duration::ZERO);
+        assert_eq!((usage[0].cpu_percent, usage[0].mem_percent), (0.0, 0.0));
+    }
+}
@@ -0,0 +1,223 @@
+//! 根据 /proc/<pid>/stat 中的 ppid 把进程组织成树, ppid 不在进程列表中的进程 (如 1 号进程和 2 号 kthreadd) 作为根。
+//! 每个节点带上整个子树的 %CPU、常驻内存合计, 用来定位是哪个父进程拉起了大量消耗资源的子进程。
+//! 文本格式与 pstree 类似, cpu 和 rss 为子树合计:
+//! systemd(1) cpu=0.3% rss=1024000K
+//! ├─sshd(812) cpu=0.0% rss=8192K
+//! │ └─bash(901) cpu=0.0% rss=4096K
+//! └─mysqld(1020) cpu=12.5% rss=819200K
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::node_exporter::proc_utils::proctop::ProcessUsage;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProcessNode {
+    pub pid: u32,
+    pub ppid: u32,
+    pub name: String,
+    pub command: String,
+    pub user: Option<String>,
+    pub state: char,
+    pub cpu_percent: f64,
+    // 常驻内存 (KB)
+    pub res: u64,
+    // 包含自身在内整个子树的合计
+    pub subtree_cpu_percent: f64,
+    pub subtree_res: u64,
+    pub subtree_processes: usize,
+    pub children: Vec<ProcessNode>,
+}
+
+impl ProcessNode {
+    // build 把进程列表组织成树, root 不为空时只返回以该进程为根的子树
+    pub fn build(processes: &[ProcessUsage], root: Option<u32>) -> Vec<Self> {
+        let pids = processes.iter().map(|usage| usage.process.pid).collect::<HashSet<_>>();
+
+        // ppid -> 子进程, 按 pid 排序保证输出稳定
+        let mut children: BTreeMap<u32, Vec<&ProcessUsage>> = BTreeMap::new();
+        let mut roots = Vec::new();
+        for usage in processes {
+            let process = &usage.process;
+            if process.ppid != process.pid && pids.contains(&process.ppid) {
+                children.entry(process.ppid).or_default().push(usage);
+            } else {
+                roots.push(usage);
+            }
+        }
+        for list in children.values_mut() {
+            list.sort_by_key(|usage| usage.process.pid);
+        }
+        roots.sort_by_key(|usage| usage.process.pid);
+
+        let mut visited = HashSet::new();
+        match root {
+            Some(pid) => processes.iter()
+                .find(|usage| usage.process.pid == pid)
+                .map(|usage| Self::build_node(usage, &children, &mut visited))
+                .into_iter()
+                .collect(),
+            None => roots.into_iter()
+                .map(|usage| Self::build_node(usage, &children, &mut visited))
+                .collect(),
+        }
+    }
+
+    fn build_node(
+        usage: &ProcessUsage,
+        children: &BTreeMap<u32, Vec<&ProcessUsage>>,
+        visited: &mut HashSet<u32>,
+    ) -> Self {
+        let process = &usage.process;
+        visited.insert(process.pid);
+
+        // 采样过程中 pid 被复用时可能形成环, 已经访问过的进程不再展开
+        let nodes = children.get(&process.pid)
+            .map(|list| list.iter()
+                .filter(|child| !visited.contains(&child.process.pid))
+                .copied()
+                .collect::<Vec<_>>())
+            .unwrap_or_default()
+            .into_iter()
+            .map(|child| Self::build_node(child, children, visited))
+            .collect::<Vec<_>>();
+
+        Self {
+            pid: process.pid,
+            ppid: process.ppid,
+            name: process.name.clone(),
+            command: process.command.clone(),
+            user: process.user.clone(),
+            state: process.state,
+            cpu_percent: usage.cpu_percent,
+            res: process.res,
+            subtree_cpu_percent: usage.cpu_percent + nodes.iter().map(|node| node.subtree_cpu_percent).sum::<f64>(),
+            subtree_res: process.res + nodes.iter().map(|node| node.subtree_res).sum::<u64>(),
+            subtree_processes: 1 + nodes.iter().map(|node| node.subtree_processes).sum::<usize>(),
+            children: nodes,
+        }
+    }
+}
+
+// render_tree 把进程树渲染成 pstree 风格的文本
+pub fn render_tree(roots: &[ProcessNode]) -> String {
+    let mut out = String::new();
+    for root in roots {
+        write_node(&mut out, root, "", None);
+    }
+    out
+}
+
+fn write_node(out: &mut String, node: &ProcessNode, prefix: &str, is_last: Option<bool>) {
+    let branch = match is_last {
+        None => "",
+        Some(true) => "└─",
+        Some(false) => "├─",
+    };
+    let _ = writeln!(
+        out,
+        "{}{}{}({}) cpu={:.1}% rss={}K",
+        prefix, branch, node.name, node.pid, node.subtree_cpu_percent, node.subtree_res,
+    );
+
+    let child_prefix = match is_last {
+        None => prefix.to_string(),
+        Some(true) => format!("{}  ", prefix),
+        Some(false) => format!("{}│ ", prefix),
+    };
+    for (i, child) in node.children.iter().enumerate() {
+        write_node(out, child, &child_prefix, Some(i + 1 == node.children.len()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::node_exporter::proc_utils::process::ProcessStatus;
+
+    fn usage(pid: u32, ppid: u32, name: &str, cpu_percent: f64, res: u64) -> ProcessUsage {
+        ProcessUsage {
+            process: ProcessStatus {
+                pid,
+                ppid,
+                name: name.to_string(),
+                command: String::new(),
+                state: 'S',
+                uid: 0,
+                user: None,
+                pr: 20,
+                nice: 0,
+                threads: 1,
+                virt: 0,
+                res,
+                utime: 0,
+                stime: 0,
+                cpu_time: 0.0,
+                start_time: 0,
+            },
+            cpu_percent,
+            mem_percent: 0.0,
+        }
+    }
+
+    fn processes() -> Vec<ProcessUsage> {
+        vec![
+            usage(1020, 1, "mysqld", 12.5, 800),
+            usage(901, 812, "bash", 0.0, 40),
+            usage(1, 0, "systemd", 0.25, 100),
+            usage(812, 1, "sshd", 0.0, 80),
+            usage(2, 0, "kthreadd", 0.0, 0),
+        ]
+    }
+
+    #[test]
+    fn builds_tree_with_subtree_totals() {
+        let tree = ProcessNode::build(&processes(), None);
+
+        assert_eq!(tree.iter().map(|node| node.pid).collect::<Vec<_>>(), [1, 2]);
+        let systemd = &tree[0];
+        // 子进程按 pid 排序
+        assert_eq!(systemd.children.iter().map(|node| node.pid).collect::<Vec<_>>(), [812, 1020]);
+        assert_eq!(systemd.subtree_processes, 4);
+        assert_eq!(systemd.subtree_res, 1020);
+        assert_eq!(systemd.subtree_cpu_percent, 12.75);
+        assert_eq!(systemd.children[0].subtree_res, 120);
+        assert_eq!(systemd.children[0].children[0].name, "bash");
+    }
+
+    #[test]
+    fn builds_subtree_for_root_pid() {
+        let tree = ProcessNode::build(&processes(), Some(812));
+        assert_eq!(tree.len(), 1);
+        assert_eq!((tree[0].pid, tree[0].subtree_processes), (812, 2));
+
+        assert!(ProcessNode::build(&processes(), Some(4242)).is_empty());
+    }
+
+    #[test]
+    fn orphans_become_roots_and_cycles_terminate() {
+        // 父进程不在列表中的作为根, pid 复用形成的环不会无限递归
+        let processes = vec![usage(10, 11, "a", 0.0, 1), usage(11, 10, "b", 0.0, 1), usage(20, 99, "orphan", 0.0, 1)];
+
+        let tree = ProcessNode::build(&processes, None);
+        assert_eq!(tree.iter().map(|node| node.pid).collect::<Vec<_>>(), [20]);
+
+        let tree = ProcessNode::build(&processes, Some(10));
+        assert_eq!(tree[0].subtree_processes, 2);
+        assert!(tree[0].children[0].children.is_empty());
+    }
+
+    #[test]
+    fn renders_pstree_style_text() {
+        let text = render_tree(&ProcessNode::build(&processes(), Some(1)));
+
+        assert_eq!(text, "\
+systemd(1) cpu=12.8% rss=1020K
+├─sshd(812) cpu=0.0% rss=120K
+│ └─bash(901) cpu=0.0% rss=40K
+└─mysqld(1020) cpu=12.5% rss=800K
+");
+    }
+}
@@ -0,0 +1,137 @@
+//! /proc/stat、/proc/diskstats、/proc/net/dev 以及各进程的 cpu 时间都是累计值,
+//! 计算使用率或速率需要定期采样, 再对相邻两次采样求差。
+//! Sampler 负责后台定时采样和保存最近一次的结果, 各数据源只需要为自己的快照类型实现 Snapshot。
+
+use std::io;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+// Snapshot 是一次采样得到的累计值
+pub trait Snapshot: Default + Send + Sync + 'static {
+    // 两次采样求差的结果
+    type Output: Clone + Send + Sync + 'static;
+
+    // 数据源名称, 用于日志
+    const SOURCE: &'static str;
+
+    // 后台采样间隔
+    const INTERVAL: Duration;
+
+    // read 读取一次快照, 在阻塞线程池中执行
+    fn read() -> Result<Self, io::Error>;
+
+    // between 计算 prev 到 cur 之间的结果, elapsed 是两次采样的时间间隔
+    fn between(prev: &Self, cur: &Self, elapsed: Duration) -> Self::Output;
+}
+
+// Sampler 在后台定期采样, 保存最近一个周期的结果
+pub struct Sampler<S: Snapshot> {
+    state: RwLock<SamplerState<S>>,
+}
+
+struct SamplerState<S: Snapshot> {
+    last: Arc<S>,
+    last_at: Instant,
+    output: Option<S::Output>,
+}
+
+impl<S: Snapshot> Sampler<S> {
+    fn new(first: S, at: Instant) -> Self {
+        Self {
+            state: RwLock::new(SamplerState {
+                last: Arc::new(first),
+                last_at: at,
+                output: None,
+            }),
+        }
+    }
+
+    // spawn 读取第一次快照并启动后台采样任务, 需要在 tokio 运行时中调用
+    pub fn spawn() -> Arc<Self> {
+        let first = S::read().unwrap_or_else(|e| {
+            eprintln!("Failed to sample {}: {}", S::SOURCE, e);
+            S::default()
+        });
+        let sampler = Arc::new(Self::new(first, Instant::now()));
+
+        let background = Arc::clone(&sampler);
+        tokio::spawn(async move {
+            let start = tokio::time::Instant::now() + S::INTERVAL;
+            let mut interval = tokio::time::interval_at(start, S::INTERVAL);
+            loop {
+                interval.tick().await;
+                // 读取 /proc 是阻塞操作, 放到阻塞线程池中执行
+                let sampler = Arc::clone(&background);
+                match tokio::task::spawn_blocking(move || sampler.sample()).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => eprintln!("Failed to sample {}: {}", S::SOURCE, e),
+                    Err(e) => eprintln!("Failed to sample {}: {}", S::SOURCE, e),
+                }
+            }
+        });
+
+        sampler
+    }
+
+    fn sample(&self) -> Result<(), io::Error> {
+        let cur = S::read()?;
+        self.record(cur, Instant::now());
+        Ok(())
+    }
+
+    // record 在锁外求差, 只在交换快照和结果时持有写锁
+    fn record(&self, cur: S, at: Instant) {
+        let (prev, prev_at) = {
+            let state = self.state.read().unwrap_or_else(|e| e.into_inner());
+            (Arc::clone(&state.last), state.last_at)
+        };
+        let output = S::between(&prev, &cur, at.saturating_duration_since(prev_at));
+
+        let mut state = self.state.write().unwrap_or_else(|e| e.into_inner());
+        state.last = Arc::new(cur);
+        state.last_at = at;
+        state.output = Some(output);
+    }
+
+    // latest 返回最近一个周期的结果, 第一个周期结束前为空
+    pub fn latest(&self) -> Option<S::Output> {
+        self.state.read().unwrap_or_else(|e| e.into_inner()).output.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Counter(u64);
+
+    impl Snapshot for Counter {
+        type Output = f64;
+
+        const SOURCE: &'static str = "counter";
+        const INTERVAL: Duration = Duration::from_secs(1);
+
+        fn read() -> Result<Self, io::Error> {
+            Ok(Self(0))
+        }
+
+        fn between(prev: &Self, cur: &Self, elapsed: Duration) -> f64 {
+            cur.0.saturating_sub(prev.0) as f64 / elapsed.as_secs_f64()
+        }
+    }
+
+    #[test]
+    fn records_the_delta_between_consecutive_snapshots() {
+        let start = Instant::now();
+        let sampler = Sampler::new(Counter(100), start);
+        assert_eq!(sampler.latest(), None);
+
+        sampler.record(Counter(300), start + Duration::from_secs(2));
+        assert_eq!(sampler.latest(), Some(100.0));
+
+        // 下一次求差以上一次采样为基准
+        sampler.record(Counter(400), start + Duration::from_secs(6));
+        assert_eq!(sampler.latest(), Some(25.0));
+    }
+}
@@ -1,12 +1,24 @@
-use axum::{ Router };
+use std::sync::Arc;
+
+use axum::{ Extension, Router };
 use crate::api::node_exporter::linux_memory_api::memory_stats_api;
+use crate::api::node_exporter::linux_cpu_api::cpu_stat_api;
+use crate::api::node_exporter::linux_disk_api::disk_stats_api;
+use crate::api::node_exporter::linux_network_api::network_stats_api;
 use crate::api::linux_file_action_api::linux_file_action_api;
 use crate::api::node_exporter::linux_process_api::process_api;
+use crate::api::node_exporter::linux_metrics_api::metrics_api;
+use crate::config::agent_config::AgentConfig;
 
-pub fn register_handlers() -> Router {
-    // 创建主路由
+pub fn register_handlers(config: Arc<AgentConfig>) -> Router {
+    // 创建主路由, 配置通过 Extension 共享给所有 handler
     Router::new()
         .nest("/memory", memory_stats_api())
-        .nest("/file", linux_file_action_api())
-        .nest("proc", process_api())
+        .nest("/cpu", cpu_stat_api())
+        .nest("/disk", disk_stats_api())
+        .nest("/network", network_stats_api())
+        .nest("/file", linux_file_action_api(&config))
+        .nest("/proc", process_api())
+        .merge(metrics_api(&config))
+        .layer(Extension(config))
 }
\ No newline at end of file